@@ -0,0 +1,693 @@
+use crate::{
+    pattern::{MatchArm, Pattern},
+    Expression, FunctionDeclaration, Literal, Statement, Token,
+};
+
+// Machine-readable views of the front end, for tools that want the tokens or the tree without
+// linking against the interpreter. Tokens and tree nodes are first turned into the generic `Node`
+// below so that every output format uses exactly the same node kinds and field names.
+
+pub enum Format {
+    Json,
+    Sexpr,
+    Dot,
+}
+
+impl Format {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "json" => Some(Format::Json),
+            "sexpr" => Some(Format::Sexpr),
+            "dot" => Some(Format::Dot),
+            _ => None,
+        }
+    }
+}
+
+enum Value {
+    Text(String),
+    Number(f64),
+    Integer(i64),
+    Boolean(bool),
+    Null,
+    Node(Node),
+    List(Vec<Node>),
+}
+
+struct Node {
+    kind: &'static str,
+    fields: Vec<(&'static str, Value)>,
+}
+
+impl Node {
+    fn new(kind: &'static str) -> Self {
+        Self {
+            kind,
+            fields: vec![],
+        }
+    }
+
+    fn field(mut self, name: &'static str, value: Value) -> Self {
+        self.fields.push((name, value));
+        self
+    }
+}
+
+pub fn dump_tokens(tokens: &[Token]) -> String {
+    let nodes: Vec<Node> = tokens.iter().map(token_node).collect();
+
+    let lines: Vec<String> = nodes
+        .iter()
+        .map(|node| format!("  {}", to_json(node)))
+        .collect();
+
+    format!("[\n{}\n]\n", lines.join(",\n"))
+}
+
+pub fn dump_ast(statements: &[Statement], format: &Format) -> String {
+    let program = Node::new("Program").field(
+        "statements",
+        Value::List(statements.iter().map(statement_node).collect()),
+    );
+
+    match format {
+        Format::Json => format!("{}\n", to_json(&program)),
+        Format::Sexpr => format!("{}\n", to_sexpr(&program)),
+        Format::Dot => to_dot(&program),
+    }
+}
+
+fn token_node(token: &Token) -> Node {
+    Node::new("Token")
+        .field("type", Value::Text(format!("{:?}", token.get_token_type())))
+        .field("lexeme", Value::Text(token.lexeme().to_string()))
+        .field("literal", literal_value(token.get_literal()))
+        .field("line", Value::Integer(token.get_line() as i64))
+}
+
+fn literal_value(literal: &Literal) -> Value {
+    match literal {
+        Literal::Number(value) => Value::Number(*value),
+        Literal::Int(value) => Value::Integer(*value),
+        Literal::Text(value) => Value::Text(value.clone()),
+        Literal::Boolean(value) => Value::Boolean(*value),
+        Literal::Nil => Value::Null,
+        Literal::Error { .. }
+        | Literal::Module(_)
+        | Literal::Native(_)
+        | Literal::Function(_)
+        | Literal::Enum(_)
+        | Literal::Constructor(..)
+        | Literal::Variant(_)
+        | Literal::List(_)
+        | Literal::Map(_)
+        | Literal::Range { .. }
+        | Literal::Generator(_)
+        | Literal::GeneratorNext(_)
+        | Literal::Lines(_) => Value::Text(literal.to_string()),
+    }
+}
+
+fn boxed(node: Node) -> Value {
+    Value::Node(node)
+}
+
+fn statement_node(statement: &Statement) -> Node {
+    match statement {
+        Statement::Expression { expression } => {
+            Node::new("Expression").field("expression", boxed(expression_node(expression)))
+        }
+        Statement::Print {
+            keyword,
+            expression,
+        } => Node::new("Print")
+            .field("line", Value::Integer(keyword.get_line() as i64))
+            .field("expression", boxed(expression_node(expression))),
+        Statement::Var { token, expression } => Node::new("Var")
+            .field("name", Value::Text(token.lexeme().to_string()))
+            .field("line", Value::Integer(token.get_line() as i64))
+            .field("initializer", boxed(expression_node(expression))),
+        Statement::Const { token, expression } => Node::new("Const")
+            .field("name", Value::Text(token.lexeme().to_string()))
+            .field("line", Value::Integer(token.get_line() as i64))
+            .field("initializer", boxed(expression_node(expression))),
+        Statement::Destructure {
+            keyword,
+            pattern,
+            expression,
+        } => Node::new("Destructure")
+            .field("keyword", Value::Text(keyword.lexeme().to_string()))
+            .field("pattern", boxed(pattern_node(pattern)))
+            .field("line", Value::Integer(keyword.get_line() as i64))
+            .field("initializer", boxed(expression_node(expression))),
+        Statement::For {
+            keyword,
+            key,
+            value,
+            iterable,
+            body,
+        } => Node::new("For")
+            .field(
+                "key",
+                key.as_ref()
+                    .map_or(Value::Null, |key| boxed(pattern_node(key))),
+            )
+            .field("value", boxed(pattern_node(value)))
+            .field("iterable", boxed(expression_node(iterable)))
+            .field("body", boxed(statement_node(body)))
+            .field("line", Value::Integer(keyword.get_line() as i64)),
+        Statement::Block { statements, line } => Node::new("Block")
+            .field("line", Value::Integer(*line as i64))
+            .field(
+                "statements",
+                Value::List(statements.iter().map(statement_node).collect()),
+            ),
+        Statement::If {
+            condition,
+            then_statement,
+        } => Node::new("If")
+            .field("condition", boxed(expression_node(condition)))
+            .field("then", boxed(statement_node(then_statement))),
+        Statement::IfElse {
+            condition,
+            then_statement,
+            else_stament,
+        } => Node::new("IfElse")
+            .field("condition", boxed(expression_node(condition)))
+            .field("then", boxed(statement_node(then_statement)))
+            .field("else", boxed(statement_node(else_stament))),
+        Statement::Comment { token, .. } => Node::new("Comment")
+            .field("text", Value::Text(token.lexeme().to_string()))
+            .field("line", Value::Integer(token.get_line() as i64)),
+        Statement::BlankLine { token } => {
+            Node::new("BlankLine").field("line", Value::Integer(token.get_line() as i64))
+        }
+        Statement::Import {
+            keyword,
+            path,
+            name,
+        } => Node::new("Import")
+            .field("line", Value::Integer(keyword.get_line() as i64))
+            .field("path", literal_value(path.get_literal()))
+            .field("name", Value::Text(name.lexeme().to_string())),
+        Statement::Throw {
+            keyword,
+            expression,
+        } => Node::new("Throw")
+            .field("line", Value::Integer(keyword.get_line() as i64))
+            .field("expression", boxed(expression_node(expression))),
+        Statement::Try {
+            try_statement,
+            catch_clause,
+            finally_statement,
+        } => {
+            let (catch_name, catch_statement) = match catch_clause {
+                Some((name, statement)) => (
+                    Value::Text(name.lexeme().to_string()),
+                    boxed(statement_node(statement)),
+                ),
+                None => (Value::Null, Value::Null),
+            };
+
+            Node::new("Try")
+                .field("body", boxed(statement_node(try_statement)))
+                .field("catchName", catch_name)
+                .field("catch", catch_statement)
+                .field(
+                    "finally",
+                    finally_statement
+                        .as_ref()
+                        .map_or(Value::Null, |statement| boxed(statement_node(statement))),
+                )
+        }
+        Statement::Function { declaration } => function_node(declaration),
+        Statement::Match {
+            keyword,
+            subject,
+            arms,
+            ..
+        } => match_node(keyword, subject, arms, statement_node),
+        Statement::Enum {
+            keyword,
+            name,
+            variants,
+            ..
+        } => Node::new("Enum")
+            .field("name", Value::Text(name.lexeme().to_string()))
+            .field(
+                "variants",
+                Value::List(
+                    variants
+                        .iter()
+                        .map(|(variant, fields)| {
+                            Node::new("Variant")
+                                .field("name", Value::Text(variant.lexeme().to_string()))
+                                .field(
+                                    "fields",
+                                    Value::List(
+                                        fields
+                                            .iter()
+                                            .map(|field| {
+                                                Node::new("Field").field(
+                                                    "name",
+                                                    Value::Text(field.lexeme().to_string()),
+                                                )
+                                            })
+                                            .collect(),
+                                    ),
+                                )
+                        })
+                        .collect(),
+                ),
+            )
+            .field("line", Value::Integer(keyword.get_line() as i64)),
+        Statement::Yield { keyword, value } => Node::new("Yield")
+            .field("line", Value::Integer(keyword.get_line() as i64))
+            .field(
+                "value",
+                value
+                    .as_ref()
+                    .map_or(Value::Null, |value| boxed(expression_node(value))),
+            ),
+        Statement::Return { keyword, value } => Node::new("Return")
+            .field("line", Value::Integer(keyword.get_line() as i64))
+            .field(
+                "value",
+                value
+                    .as_ref()
+                    .map_or(Value::Null, |value| boxed(expression_node(value))),
+            ),
+    }
+}
+
+// Named declarations and lambdas share a node kind; lambdas have a null name.
+fn function_node(declaration: &FunctionDeclaration) -> Node {
+    let parameters = declaration
+        .parameters
+        .iter()
+        .map(|parameter| {
+            Node::new("Parameter")
+                .field("name", Value::Text(parameter.name.lexeme().to_string()))
+                .field(
+                    "default",
+                    parameter
+                        .default
+                        .as_ref()
+                        .map_or(Value::Null, |default| boxed(expression_node(default))),
+                )
+                .field("rest", Value::Boolean(parameter.rest))
+                .field("line", Value::Integer(parameter.name.get_line() as i64))
+        })
+        .collect();
+
+    Node::new("Function")
+        .field(
+            "name",
+            declaration
+                .name
+                .as_ref()
+                .map_or(Value::Null, |name| Value::Text(name.lexeme().to_string())),
+        )
+        .field("parameters", Value::List(parameters))
+        .field("arrow", Value::Boolean(declaration.arrow))
+        .field("generator", Value::Boolean(declaration.generator))
+        .field(
+            "body",
+            Value::List(declaration.body.iter().map(statement_node).collect()),
+        )
+        .field(
+            "line",
+            Value::Integer(declaration.keyword.get_line() as i64),
+        )
+}
+
+fn expression_node(expression: &Expression) -> Node {
+    match expression {
+        Expression::Grouping { expression } => {
+            Node::new("Grouping").field("expression", boxed(expression_node(expression)))
+        }
+        Expression::Binary {
+            left,
+            operator,
+            right,
+        } => operator_node("Binary", operator)
+            .field("left", boxed(expression_node(left)))
+            .field("right", boxed(expression_node(right))),
+        Expression::Logical {
+            left,
+            operator,
+            right,
+        } => operator_node("Logical", operator)
+            .field("left", boxed(expression_node(left)))
+            .field("right", boxed(expression_node(right))),
+        Expression::Conditional {
+            condition,
+            question,
+            then_branch,
+            else_branch,
+        } => Node::new("Conditional")
+            .field("condition", boxed(expression_node(condition)))
+            .field("then", boxed(expression_node(then_branch)))
+            .field("else", boxed(expression_node(else_branch)))
+            .field("line", Value::Integer(question.get_line() as i64)),
+        Expression::Range {
+            start,
+            operator,
+            end,
+            step,
+        } => operator_node("Range", operator)
+            .field("start", boxed(expression_node(start)))
+            .field("end", boxed(expression_node(end)))
+            .field(
+                "step",
+                step.as_ref()
+                    .map_or(Value::Null, |step| boxed(expression_node(step))),
+            ),
+        Expression::Unary { operator, right } => {
+            operator_node("Unary", operator).field("right", boxed(expression_node(right)))
+        }
+        Expression::Literal {
+            literal_value,
+            line,
+        } => Node::new("Literal")
+            .field("value", self::literal_value(literal_value))
+            .field("line", Value::Integer(*line as i64)),
+        Expression::Var { name } => Node::new("Variable")
+            .field("name", Value::Text(name.lexeme().to_string()))
+            .field("line", Value::Integer(name.get_line() as i64)),
+        Expression::Assignment { name, value } => Node::new("Assignment")
+            .field("name", Value::Text(name.lexeme().to_string()))
+            .field("line", Value::Integer(name.get_line() as i64))
+            .field("value", boxed(expression_node(value))),
+        Expression::Destructure {
+            pattern,
+            equals,
+            value,
+        } => Node::new("DestructuringAssignment")
+            .field("pattern", boxed(pattern_node(pattern)))
+            .field("line", Value::Integer(equals.get_line() as i64))
+            .field("value", boxed(expression_node(value))),
+        Expression::CompoundAssignment {
+            name,
+            operator,
+            value,
+        } => operator_node("CompoundAssignment", operator)
+            .field("name", Value::Text(name.lexeme().to_string()))
+            .field("value", boxed(expression_node(value))),
+        Expression::Increment {
+            name,
+            operator,
+            prefix,
+        } => operator_node("Increment", operator)
+            .field("name", Value::Text(name.lexeme().to_string()))
+            .field("prefix", Value::Boolean(*prefix)),
+        Expression::Get {
+            object,
+            name,
+            optional,
+        } => Node::new("Get")
+            .field("object", boxed(expression_node(object)))
+            .field("name", Value::Text(name.lexeme().to_string()))
+            .field("optional", Value::Boolean(*optional))
+            .field("line", Value::Integer(name.get_line() as i64)),
+        Expression::Call {
+            callee,
+            paren,
+            arguments,
+            named,
+        } => Node::new("Call")
+            .field("callee", boxed(expression_node(callee)))
+            .field(
+                "arguments",
+                Value::List(arguments.iter().map(expression_node).collect()),
+            )
+            .field(
+                "named",
+                Value::List(
+                    named
+                        .iter()
+                        .map(|(name, value)| {
+                            Node::new("NamedArgument")
+                                .field("name", Value::Text(name.lexeme().to_string()))
+                                .field("value", boxed(expression_node(value)))
+                        })
+                        .collect(),
+                ),
+            )
+            .field("line", Value::Integer(paren.get_line() as i64)),
+        Expression::List { bracket, elements } => Node::new("List")
+            .field(
+                "elements",
+                Value::List(elements.iter().map(expression_node).collect()),
+            )
+            .field("line", Value::Integer(bracket.get_line() as i64)),
+        Expression::Index {
+            object,
+            bracket,
+            index,
+        } => Node::new("Index")
+            .field("object", boxed(expression_node(object)))
+            .field("index", boxed(expression_node(index)))
+            .field("line", Value::Integer(bracket.get_line() as i64)),
+        Expression::Map { brace, entries } => Node::new("Map")
+            .field(
+                "entries",
+                Value::List(
+                    entries
+                        .iter()
+                        .map(|(key, value)| {
+                            Node::new("Entry")
+                                .field("key", Value::Text(Expression::map_key(key)))
+                                .field("value", boxed(expression_node(value)))
+                        })
+                        .collect(),
+                ),
+            )
+            .field("line", Value::Integer(brace.get_line() as i64)),
+        Expression::Function { declaration } => function_node(declaration),
+        Expression::Match {
+            keyword,
+            subject,
+            arms,
+        } => match_node(keyword, subject, arms, expression_node),
+    }
+}
+
+// Statement and expression forms share a node kind, only the kind of their arm bodies differs.
+fn match_node<T>(
+    keyword: &Token,
+    subject: &Expression,
+    arms: &[MatchArm<T>],
+    body_node: fn(&T) -> Node,
+) -> Node {
+    let arms = arms
+        .iter()
+        .map(|arm| {
+            Node::new("Arm")
+                .field("pattern", boxed(pattern_node(&arm.pattern)))
+                .field(
+                    "guard",
+                    arm.guard
+                        .as_ref()
+                        .map_or(Value::Null, |guard| boxed(expression_node(guard))),
+                )
+                .field("body", boxed(body_node(&arm.body)))
+        })
+        .collect();
+
+    Node::new("Match")
+        .field("subject", boxed(expression_node(subject)))
+        .field("arms", Value::List(arms))
+        .field("line", Value::Integer(keyword.get_line() as i64))
+}
+
+fn pattern_node(pattern: &Pattern) -> Node {
+    match pattern {
+        Pattern::Wildcard { .. } => Node::new("WildcardPattern"),
+        Pattern::Literal { value, .. } => {
+            Node::new("LiteralPattern").field("value", literal_value(value))
+        }
+        Pattern::Binding { name } => Node::new("BindingPattern")
+            .field("name", Value::Text(name.lexeme().to_string()))
+            .field("line", Value::Integer(name.get_line() as i64)),
+        Pattern::Alternative { alternatives } => Node::new("AlternativePattern").field(
+            "alternatives",
+            Value::List(alternatives.iter().map(pattern_node).collect()),
+        ),
+        Pattern::List { elements, rest, .. } => Node::new("ListPattern")
+            .field(
+                "elements",
+                Value::List(elements.iter().map(pattern_node).collect()),
+            )
+            .field(
+                "rest",
+                rest.as_ref()
+                    .map_or(Value::Null, |rest| Value::Text(rest.lexeme().to_string())),
+            ),
+        Pattern::Map { entries, .. } => Node::new("MapPattern").field(
+            "entries",
+            Value::List(
+                entries
+                    .iter()
+                    .map(|(key, pattern)| {
+                        Node::new("Entry")
+                            .field("key", Value::Text(Expression::map_key(key)))
+                            .field("pattern", boxed(pattern_node(pattern)))
+                    })
+                    .collect(),
+            ),
+        ),
+        Pattern::Variant {
+            enumeration,
+            name,
+            fields,
+        } => Node::new("VariantPattern")
+            .field(
+                "enum",
+                enumeration.as_ref().map_or(Value::Null, |enumeration| {
+                    Value::Text(enumeration.lexeme().to_string())
+                }),
+            )
+            .field("name", Value::Text(name.lexeme().to_string()))
+            .field(
+                "fields",
+                fields.as_ref().map_or(Value::Null, |fields| {
+                    Value::List(fields.iter().map(pattern_node).collect())
+                }),
+            )
+            .field("line", Value::Integer(name.get_line() as i64)),
+        Pattern::Default {
+            pattern, default, ..
+        } => Node::new("DefaultPattern")
+            .field("pattern", boxed(pattern_node(pattern)))
+            .field("default", boxed(expression_node(default))),
+    }
+}
+
+fn operator_node(kind: &'static str, operator: &Token) -> Node {
+    Node::new(kind)
+        .field("operator", Value::Text(operator.lexeme().to_string()))
+        .field("line", Value::Integer(operator.get_line() as i64))
+}
+
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+
+    for c in text.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            c if (c as u32) < 0x20 => escaped.push_str(&format!("\\u{:04x}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+
+    escaped
+}
+
+fn to_json(node: &Node) -> String {
+    let mut fields = vec![format!("\"node\": \"{}\"", node.kind)];
+
+    for (name, value) in &node.fields {
+        let value = match value {
+            Value::Node(node) => to_json(node),
+            Value::List(nodes) => {
+                let nodes: Vec<String> = nodes.iter().map(to_json).collect();
+                format!("[{}]", nodes.join(", "))
+            }
+            scalar => scalar_to_string(scalar),
+        };
+
+        fields.push(format!("\"{}\": {}", name, value));
+    }
+
+    format!("{{{}}}", fields.join(", "))
+}
+
+fn to_sexpr(node: &Node) -> String {
+    let mut parts = vec![node.kind.to_string()];
+
+    for (name, value) in &node.fields {
+        let value = match value {
+            Value::Node(node) => to_sexpr(node),
+            Value::List(nodes) => {
+                let nodes: Vec<String> = nodes.iter().map(to_sexpr).collect();
+                format!("({})", nodes.join(" "))
+            }
+            Value::Null => "nil".to_string(),
+            scalar => scalar_to_string(scalar),
+        };
+
+        parts.push(format!(":{} {}", name, value));
+    }
+
+    format!("({})", parts.join(" "))
+}
+
+fn scalar_to_string(value: &Value) -> String {
+    match value {
+        Value::Text(text) => format!("\"{}\"", escape(text)),
+        // A float keeps its decimal point so that `2.0` can't be mistaken for the integer `2`.
+        Value::Number(number) if number.is_finite() && number.fract() == 0.0 => {
+            format!("{:.1}", number)
+        }
+        Value::Number(number) => number.to_string(),
+        Value::Integer(number) => number.to_string(),
+        Value::Boolean(boolean) => boolean.to_string(),
+        _ => "null".to_string(),
+    }
+}
+
+// Scalar fields become part of the node label, child nodes become edges named after the field.
+// List elements are numbered so their order survives in the graph.
+fn to_dot(node: &Node) -> String {
+    let mut lines = vec![
+        "digraph ast {".to_string(),
+        "  node [shape=box];".to_string(),
+    ];
+    let mut next_id = 0;
+
+    dot_node(node, &mut lines, &mut next_id);
+
+    lines.push("}".to_string());
+    format!("{}\n", lines.join("\n"))
+}
+
+fn dot_node(node: &Node, lines: &mut Vec<String>, next_id: &mut usize) -> usize {
+    let id = *next_id;
+    *next_id += 1;
+
+    let mut label = vec![node.kind.to_string()];
+    let mut edges = vec![];
+
+    for (name, value) in &node.fields {
+        match value {
+            Value::Node(child) => {
+                let child_id = dot_node(child, lines, next_id);
+                edges.push(format!("  n{} -> n{} [label=\"{}\"];", id, child_id, name));
+            }
+            Value::List(children) => {
+                for (index, child) in children.iter().enumerate() {
+                    let child_id = dot_node(child, lines, next_id);
+                    edges.push(format!(
+                        "  n{} -> n{} [label=\"{}[{}]\"];",
+                        id, child_id, name, index
+                    ));
+                }
+            }
+            scalar => label.push(format!("{}: {}", name, scalar_to_string(scalar))),
+        }
+    }
+
+    lines.push(format!(
+        "  n{} [label=\"{}\"];",
+        id,
+        escape(&label.join("\n"))
+    ));
+    lines.extend(edges);
+
+    id
+}
@@ -1,53 +1,184 @@
-use std::collections::HashMap;
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::rc::Rc;
 
-use crate::Literal;
+use crate::{
+    heap::{self, Tracer},
+    interner::Symbol,
+    list::drop_scopes,
+    Literal,
+};
 
+// A handle on a chain of scopes. Cloning it shares the scopes instead of copying them, so that a
+// closure sees (and updates) the variables around it after the code that created it has moved on.
 #[derive(Clone)]
 pub struct Environment {
-    variables: HashMap<String, Literal>,
-    enclosing: Option<Box<Environment>>,
+    scope: Rc<RefCell<Scope>>,
+}
+
+pub(crate) struct Scope {
+    variables: HashMap<Symbol, Literal>,
+    // The variables declared with `const`, which can't be assigned once defined.
+    constants: HashSet<Symbol>,
+    enclosing: Option<Environment>,
+}
+
+impl Default for Environment {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Environment {
     pub fn new() -> Self {
-        Self {
-            variables: HashMap::new(),
-            enclosing: None,
-        }
+        Self::new_with_enclosing(None)
     }
 
-    pub fn new_with_enclosing(environment: Option<Box<Environment>>) -> Self {
-        Self {
+    pub fn new_with_enclosing(environment: Option<Environment>) -> Self {
+        let scope = Rc::new(RefCell::new(Scope {
             variables: HashMap::new(),
+            constants: HashSet::new(),
             enclosing: environment,
-        }
+        }));
+        heap::register(&scope);
+
+        Self { scope }
     }
 
-    pub fn get(&self, variable_name: &str) -> Option<&Literal> {
-        let value = self.variables.get(variable_name);
+    pub fn get(&self, variable_name: impl Into<Symbol>) -> Option<Literal> {
+        let variable_name = variable_name.into();
+        let scope = self.scope.borrow();
 
-        match (value, &self.enclosing) {
-            (Some(value), _) => Some(value),
+        match (scope.variables.get(&variable_name), &scope.enclosing) {
+            (Some(value), _) => Some(value.clone()),
             (None, Some(enclosing)) => enclosing.get(variable_name),
-            _ => panic!("Undefined variable '{}'.", variable_name),
+            _ => None,
+        }
+    }
+
+    // Moves the current scope under a new, empty one. Every `push_scope` has to be matched by a
+    // `pop_scope`, even when the code in between fails, so that an error never leaves the
+    // interpreter running in a scope it has already left.
+    pub fn push_scope(&mut self) {
+        *self = Self::new_with_enclosing(Some(self.clone()));
+    }
+
+    pub fn pop_scope(&mut self) {
+        let enclosing = self.scope.borrow().enclosing.clone();
+
+        if let Some(enclosing) = enclosing {
+            *self = enclosing;
+        }
+    }
+
+    // Binds a name in a scope of its own, like a parameter or a pattern binding, where it can't
+    // clash with a constant.
+    pub fn define(&mut self, variable_name: impl Into<Symbol>, value: Literal) {
+        self.scope
+            .borrow_mut()
+            .variables
+            .insert(variable_name.into(), value);
+    }
+
+    // Binds a name a declaration introduces. Declaring a constant again in the scope that holds it
+    // would replace it as surely as assigning it, so that fails for the same reason `assign` does.
+    pub fn declare(
+        &mut self,
+        variable_name: impl Into<Symbol>,
+        value: Literal,
+        constant: bool,
+    ) -> Result<(), String> {
+        let variable_name = variable_name.into();
+        let mut scope = self.scope.borrow_mut();
+
+        if scope.constants.contains(&variable_name) {
+            return Err(format!(
+                "Constant {} is already declared in this scope.",
+                variable_name
+            ));
+        }
+
+        if constant {
+            scope.constants.insert(variable_name);
+        }
+        scope.variables.insert(variable_name, value);
+
+        Ok(())
+    }
+
+    // The resolver already rejects assignments to constants it can see, so failing here only
+    // catches the ones it can't, like a function assigning a constant declared after it.
+    pub fn assign(
+        &mut self,
+        variable_name: impl Into<Symbol>,
+        value: Literal,
+    ) -> Result<(), String> {
+        let variable_name = variable_name.into();
+        let mut scope = self.scope.borrow_mut();
+
+        if scope.constants.contains(&variable_name) {
+            return Err(format!("Can't assign to constant {}.", variable_name));
         }
+
+        if let Some(previous_value) = scope.variables.get_mut(&variable_name) {
+            *previous_value = value;
+            return Ok(());
+        }
+
+        match &mut scope.enclosing {
+            Some(next) => next.assign(variable_name, value),
+            None => Err(format!("Variable {} has not been defined.", variable_name)),
+        }
+    }
+
+    pub(crate) fn trace(&self, tracer: &mut Tracer) {
+        tracer.reference(heap::Object::Scope(Rc::clone(&self.scope)));
     }
 
-    pub fn define(&mut self, variable_name: &str, value: Literal) {
-        self.variables.insert(variable_name.to_string(), value);
+    // Empties the scope if this is the last reference to it, handing out its values and the scope
+    // around it, see `drop_scopes`.
+    pub(crate) fn take_unshared(self) -> Option<(Vec<Literal>, Option<Environment>)> {
+        let mut scope = Rc::try_unwrap(self.scope).ok()?.into_inner();
+
+        Some((
+            scope.variables.drain().map(|(_, value)| value).collect(),
+            scope.enclosing.take(),
+        ))
     }
+}
 
-    pub fn assign(&mut self, variable_name: &str, value: Literal) -> bool {
-        let previous_value = self.variables.get(variable_name);
+// A scope can hold the last reference to a function whose closure holds the last reference to
+// another scope, and so on, see `drop_scopes`.
+impl Drop for Scope {
+    fn drop(&mut self) {
+        let values = self.variables.drain().map(|(_, value)| value).collect();
+
+        drop_scopes(values, self.enclosing.take().into_iter().collect());
+    }
+}
 
-        match (previous_value, &mut self.enclosing) {
-            (Some(_), _) => {
-                self.variables.insert(variable_name.to_string(), value);
+impl Scope {
+    // The bytes its variables take up, on top of the scope itself.
+    pub(crate) fn allocated_bytes(&self) -> usize {
+        self.variables.capacity() * size_of::<(Symbol, Literal)>()
+    }
+
+    pub(crate) fn trace(&self, tracer: &mut Tracer) {
+        self.variables
+            .values()
+            .for_each(|value| tracer.literal(value));
+
+        if let Some(enclosing) = &self.enclosing {
+            enclosing.trace(tracer);
+        }
+    }
 
-                true
-            }
-            (None, Some(next)) => next.assign(variable_name, value),
-            (None, None) => false,
+    // Empties a scope the collector found unreachable, handing out what it held.
+    pub(crate) fn take(&mut self) -> Scope {
+        Scope {
+            variables: std::mem::take(&mut self.variables),
+            constants: std::mem::take(&mut self.constants),
+            enclosing: self.enclosing.take(),
         }
     }
 }
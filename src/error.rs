@@ -0,0 +1,153 @@
+use std::fmt;
+
+use crate::Literal;
+
+// Everything that stops the normal flow of execution at runtime. Built-in failures (division by
+// zero, undefined variables, type mismatches, ...) are raised as a `Literal::Error` value, so a
+// `catch` clause sees them exactly like a value thrown by the script itself. Hitting one of the
+// sandbox limits is a `LimitExceeded` instead, which no `catch` clause can intercept. A `return`
+// travels up to its function as a `Return`, which is not an error at all.
+#[derive(Clone, PartialEq, Debug)]
+pub enum RuntimeError {
+    Exception {
+        value: Literal,
+        line: u32,
+        trace: Vec<StackFrame>,
+    },
+    LimitExceeded {
+        limit: Limit,
+        line: u32,
+        trace: Vec<StackFrame>,
+    },
+    Return {
+        value: Literal,
+        line: u32,
+    },
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Limit {
+    Steps,
+    Deadline,
+    Memory,
+    Cancelled,
+}
+
+// One entry of the interpreter's call stack. While a frame is active, `line` is the line it last
+// handed control to another frame from; in a trace it is the line the frame was at when the
+// error went through it.
+#[derive(Clone, PartialEq, Debug)]
+pub struct StackFrame {
+    pub function: String,
+    pub file: Option<String>,
+    pub line: u32,
+}
+
+impl RuntimeError {
+    pub fn new(message: impl Into<String>, line: u32) -> Self {
+        Self::throw(
+            Literal::Error {
+                message: message.into(),
+                line,
+            },
+            line,
+        )
+    }
+
+    pub fn limit(limit: Limit, line: u32) -> Self {
+        RuntimeError::LimitExceeded {
+            limit,
+            line,
+            trace: vec![],
+        }
+    }
+
+    pub fn throw(value: Literal, line: u32) -> Self {
+        RuntimeError::Exception {
+            value,
+            line,
+            trace: vec![],
+        }
+    }
+
+    // Records that the error is leaving `frame`, innermost frames first. The innermost frame is
+    // reported at the line the error was raised on, every other one at its call site.
+    pub fn unwind(mut self, frame: &StackFrame) -> Self {
+        let (RuntimeError::Exception { line, trace, .. }
+        | RuntimeError::LimitExceeded { line, trace, .. }) = &mut self
+        else {
+            return self;
+        };
+
+        let line = if trace.is_empty() { *line } else { frame.line };
+        trace.push(StackFrame {
+            line,
+            ..frame.clone()
+        });
+
+        self
+    }
+}
+
+impl fmt::Display for StackFrame {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "[line {}] in {}", self.line, self.function)?;
+
+        match &self.file {
+            Some(file) => write!(f, " ({})", file),
+            None => Ok(()),
+        }
+    }
+}
+
+impl fmt::Display for Limit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Limit::Steps => write!(f, "instruction budget exhausted"),
+            Limit::Deadline => write!(f, "deadline exceeded"),
+            Limit::Memory => write!(f, "memory cap exceeded"),
+            Limit::Cancelled => write!(f, "cancelled"),
+        }
+    }
+}
+
+impl fmt::Display for RuntimeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (line, trace) = match self {
+            RuntimeError::Exception { value, line, trace } => {
+                match value {
+                    Literal::Error { message, .. } => write!(f, "{}", message)?,
+                    value => write!(f, "Uncaught exception: {}", value)?,
+                }
+
+                (line, trace)
+            }
+            RuntimeError::LimitExceeded { limit, line, trace } => {
+                write!(f, "Execution stopped: {}.", limit)?;
+
+                (line, trace)
+            }
+            // The parser only accepts `return` inside functions, which always catch it.
+            RuntimeError::Return { line, .. } => {
+                return write!(f, "Can't return from top-level code.\n[line {}]", line);
+            }
+        };
+
+        if trace.is_empty() {
+            return write!(f, "\n[line {}]", line);
+        }
+
+        // Deep recursion repeats the same frame hundreds of times, which is shown once.
+        for run in trace.chunk_by(|a, b| a == b) {
+            write!(f, "\n{}", run[0])?;
+
+            match run.len() {
+                1 => (),
+                2 => write!(f, "\n{}", run[1])?,
+                count => write!(f, "\n... previous frame repeated {} more times", count - 1)?,
+            }
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,616 @@
+use crate::{
+    pattern::{MatchArm, Pattern},
+    Expression, FunctionDeclaration, Literal, Parser, Scanner, Statement, Token,
+};
+
+const INDENT: &str = "    ";
+const MAX_WIDTH: usize = 100;
+
+// Parses the source with its comments and prints it back in the canonical layout: four spaces of
+// indentation, one statement per line, single spaces around binary operators, opening braces on
+// the line of the statement that owns them and `} else {` on one line. Expressions that do not fit
+// in `MAX_WIDTH` columns are broken before each operator of their outermost chain.
+pub fn format(source: &str) -> String {
+    let mut scanner = Scanner::new_with_comments(source);
+    let mut parser = Parser::new(scanner.scan_tokens().to_vec());
+
+    let mut formatter = Formatter::default();
+    formatter.statements(&parser.parse(), 0);
+
+    formatter.output
+}
+
+#[derive(Default)]
+struct Formatter {
+    output: String,
+}
+
+impl Formatter {
+    // Blank lines between statements are kept, one for every run of them, but not at the start or
+    // the end of a block.
+    fn statements(&mut self, statements: &[Statement], depth: usize) {
+        for (index, statement) in statements.iter().enumerate() {
+            match statement {
+                Statement::BlankLine { .. } => {
+                    if index > 0 && index + 1 < statements.len() {
+                        self.output.push('\n');
+                    }
+                }
+                Statement::Comment {
+                    token,
+                    trailing: true,
+                } if self.output.ends_with('\n') => {
+                    self.output.pop();
+                    self.output.push(' ');
+                    self.output.push_str(token.lexeme());
+                    self.output.push('\n');
+                }
+                _ => {
+                    self.line(depth);
+                    self.statement(statement, depth);
+                    self.output.push('\n');
+                }
+            }
+        }
+    }
+
+    fn statement(&mut self, statement: &Statement, depth: usize) {
+        match statement {
+            Statement::Expression { expression } => {
+                self.expression(expression, depth, "", ";");
+            }
+            Statement::Print { expression, .. } => {
+                self.expression(expression, depth, "print ", ";");
+            }
+            Statement::Var { token, expression } => match expression {
+                Expression::Literal {
+                    literal_value: Literal::Nil,
+                    ..
+                } => self.push(&format!("var {};", token.lexeme())),
+                _ => self.expression(
+                    expression,
+                    depth,
+                    &format!("var {} = ", token.lexeme()),
+                    ";",
+                ),
+            },
+            Statement::Const { token, expression } => self.expression(
+                expression,
+                depth,
+                &format!("const {} = ", token.lexeme()),
+                ";",
+            ),
+            Statement::Destructure {
+                keyword,
+                pattern,
+                expression,
+            } => self.expression(
+                expression,
+                depth,
+                &format!("{} {} = ", keyword.lexeme(), pattern_source(pattern)),
+                ";",
+            ),
+            Statement::For {
+                key,
+                value,
+                iterable,
+                body,
+                ..
+            } => {
+                let variables = match key {
+                    Some(key) => format!("{}, {}", pattern_source(key), pattern_source(value)),
+                    None => pattern_source(value),
+                };
+
+                self.expression(iterable, depth, &format!("for ({} in ", variables), ")");
+                self.branch(body, depth);
+            }
+            Statement::Block { statements, .. } => self.block(statements, depth),
+            Statement::If {
+                condition,
+                then_statement,
+            } => {
+                self.expression(condition, depth, "if (", ")");
+                self.branch(then_statement, depth);
+            }
+            Statement::IfElse {
+                condition,
+                then_statement,
+                else_stament,
+            } => {
+                self.expression(condition, depth, "if (", ")");
+                self.branch(then_statement, depth);
+
+                if matches!(**then_statement, Statement::Block { .. }) {
+                    self.push(" else");
+                } else {
+                    self.push("\n");
+                    self.line(depth);
+                    self.push("else");
+                }
+
+                match **else_stament {
+                    Statement::If { .. } | Statement::IfElse { .. } => {
+                        self.push(" ");
+                        self.statement(else_stament, depth);
+                    }
+                    _ => self.branch(else_stament, depth),
+                }
+            }
+            Statement::Comment { token, .. } => self.push(token.lexeme()),
+            Statement::BlankLine { .. } => (),
+            Statement::Import { path, name, .. } => {
+                self.push(&format!("import {} as {};", path.lexeme(), name.lexeme()));
+            }
+            Statement::Throw { expression, .. } => {
+                self.expression(expression, depth, "throw ", ";");
+            }
+            Statement::Try {
+                try_statement,
+                catch_clause,
+                finally_statement,
+            } => {
+                self.push("try ");
+                self.statement(try_statement, depth);
+
+                if let Some((name, catch_statement)) = catch_clause {
+                    self.push(&format!(" catch ({}) ", name.lexeme()));
+                    self.statement(catch_statement, depth);
+                }
+
+                if let Some(finally_statement) = finally_statement {
+                    self.push(" finally ");
+                    self.statement(finally_statement, depth);
+                }
+            }
+            Statement::Function { declaration } => {
+                self.push(&function_source(declaration, depth));
+            }
+            Statement::Return { value: None, .. } => self.push("return;"),
+            Statement::Return {
+                value: Some(value), ..
+            } => self.expression(value, depth, "return ", ";"),
+            Statement::Yield { value: None, .. } => self.push("yield;"),
+            Statement::Yield {
+                value: Some(value), ..
+            } => self.expression(value, depth, "yield ", ";"),
+            Statement::Match {
+                subject,
+                arms,
+                comments,
+                ..
+            } => {
+                self.expression(subject, depth, "match (", ") {\n");
+
+                for (index, arm) in arms.iter().enumerate() {
+                    self.comments(comments, index, arms.len(), depth + 1);
+                    self.line(depth + 1);
+                    self.push(&arm_head(arm, depth + 1));
+                    self.statement(&arm.body, depth + 1);
+                    self.push("\n");
+                }
+                self.comments(comments, arms.len(), arms.len(), depth + 1);
+
+                self.line(depth);
+                self.push("}");
+            }
+            Statement::Enum {
+                name,
+                variants,
+                comments,
+                ..
+            } => {
+                let count = variants.len();
+                let variants: Vec<String> = variants
+                    .iter()
+                    .map(|(variant, fields)| match fields.is_empty() {
+                        true => variant.lexeme().to_string(),
+                        false => {
+                            let fields: Vec<&str> = fields.iter().map(Token::lexeme).collect();
+
+                            format!("{}({})", variant.lexeme(), fields.join(", "))
+                        }
+                    })
+                    .collect();
+                let flat = format!("enum {} {{ {} }}", name.lexeme(), variants.join(", "));
+
+                if variants.is_empty() && comments.is_empty() {
+                    self.push(&format!("enum {} {{}}", name.lexeme()));
+                } else if comments.is_empty() && depth * INDENT.len() + flat.len() <= MAX_WIDTH {
+                    self.push(&flat);
+                } else {
+                    self.push(&format!("enum {} {{\n", name.lexeme()));
+
+                    for (index, variant) in variants.iter().enumerate() {
+                        self.comments(comments, index, count, depth + 1);
+                        self.line(depth + 1);
+                        self.push(&format!("{},\n", variant));
+                    }
+                    self.comments(comments, count, count, depth + 1);
+
+                    self.line(depth);
+                    self.push("}");
+                }
+            }
+        }
+    }
+
+    // The comments that come before the item at `index` of a list of `count`, like the arms of a
+    // match or the variants of an enum, laid out as `statements` lays them out between statements.
+    fn comments(
+        &mut self,
+        comments: &[(usize, Statement)],
+        index: usize,
+        count: usize,
+        depth: usize,
+    ) {
+        for (_, comment) in comments.iter().filter(|(before, _)| *before == index) {
+            match comment {
+                Statement::BlankLine { .. } => {
+                    if index > 0 && index < count {
+                        self.output.push('\n');
+                    }
+                }
+                Statement::Comment {
+                    token,
+                    trailing: true,
+                } if self.output.ends_with('\n') => {
+                    self.output.pop();
+                    self.output.push(' ');
+                    self.output.push_str(token.lexeme());
+                    self.output.push('\n');
+                }
+                _ => {
+                    self.line(depth);
+                    self.statement(comment, depth);
+                    self.output.push('\n');
+                }
+            }
+        }
+    }
+
+    fn block(&mut self, statements: &[Statement], depth: usize) {
+        if statements.is_empty() {
+            self.push("{}");
+            return;
+        }
+
+        self.push("{\n");
+        self.statements(statements, depth + 1);
+        self.line(depth);
+        self.push("}");
+    }
+
+    // The body of an `if` or `else`: blocks stay on the same line, anything else goes on its own
+    // line one level deeper.
+    fn branch(&mut self, statement: &Statement, depth: usize) {
+        if matches!(statement, Statement::Block { .. }) {
+            self.push(" ");
+            self.statement(statement, depth);
+        } else {
+            self.push("\n");
+            self.line(depth + 1);
+            self.statement(statement, depth + 1);
+        }
+    }
+
+    fn expression(&mut self, expression: &Expression, depth: usize, prefix: &str, suffix: &str) {
+        let flat = format!("{}{}{}", prefix, to_source(expression, depth), suffix);
+        // A function body spans several lines, only the first one has to fit.
+        let width = flat.find('\n').unwrap_or(flat.len());
+
+        if INDENT.len() * depth + width <= MAX_WIDTH {
+            self.push(&flat);
+            return;
+        }
+
+        let (first, rest) = operator_chain(expression);
+
+        self.push(prefix);
+        self.push(&to_source(first, depth));
+
+        for (operator, operand) in rest {
+            self.push("\n");
+            self.line(depth + 1);
+            self.push(&format!("{} {}", operator, to_source(operand, depth + 1)));
+        }
+
+        self.push(suffix);
+    }
+
+    fn line(&mut self, depth: usize) {
+        self.output.push_str(&INDENT.repeat(depth));
+    }
+
+    fn push(&mut self, text: &str) {
+        self.output.push_str(text);
+    }
+}
+
+// Splits `a + b - c` into `a` and `[("+", b), ("-", c)]` by walking down the left operands of the
+// outermost binary or logical expression, which is where a long line is broken.
+fn operator_chain(expression: &Expression) -> (&Expression, Vec<(&str, &Expression)>) {
+    match expression {
+        Expression::Binary {
+            left,
+            operator,
+            right,
+        }
+        | Expression::Logical {
+            left,
+            operator,
+            right,
+        } => {
+            let (first, mut rest) = operator_chain(left);
+            rest.push((operator.lexeme(), right));
+
+            (first, rest)
+        }
+        _ => (expression, vec![]),
+    }
+}
+
+// `depth` is the indentation of the line the expression starts on, which the body of a function
+// expression is indented from.
+fn to_source(expression: &Expression, depth: usize) -> String {
+    match expression {
+        Expression::Grouping { expression } => format!("({})", to_source(expression, depth)),
+        Expression::Binary {
+            left,
+            operator,
+            right,
+        }
+        | Expression::Logical {
+            left,
+            operator,
+            right,
+        } => format!(
+            "{} {} {}",
+            to_source(left, depth),
+            operator.lexeme(),
+            to_source(right, depth)
+        ),
+        Expression::Unary { operator, right } => {
+            let right = to_source(right, depth);
+
+            // `- -x` must not be glued into `--x`.
+            if right.starts_with(operator.lexeme()) {
+                format!("{} {}", operator.lexeme(), right)
+            } else {
+                format!("{}{}", operator.lexeme(), right)
+            }
+        }
+        Expression::Literal { literal_value, .. } => match literal_value {
+            Literal::Text(text) => format!("\"{}\"", text),
+            // A float literal keeps its decimal point, `2.0` printed as `2` would be an integer.
+            Literal::Number(value) if value.fract() == 0.0 => format!("{}.0", value),
+            _ => literal_value.to_string(),
+        },
+        Expression::Var { name } => name.lexeme().to_string(),
+        Expression::Assignment { name, value } => {
+            format!("{} = {}", name.lexeme(), to_source(value, depth))
+        }
+        Expression::Destructure { pattern, value, .. } => {
+            format!("{} = {}", pattern_source(pattern), to_source(value, depth))
+        }
+        Expression::CompoundAssignment {
+            name,
+            operator,
+            value,
+        } => format!(
+            "{} {} {}",
+            name.lexeme(),
+            operator.lexeme(),
+            to_source(value, depth)
+        ),
+        Expression::Increment {
+            name,
+            operator,
+            prefix: true,
+        } => format!("{}{}", operator.lexeme(), name.lexeme()),
+        Expression::Increment { name, operator, .. } => {
+            format!("{}{}", name.lexeme(), operator.lexeme())
+        }
+        Expression::Get {
+            object,
+            name,
+            optional,
+        } => format!(
+            "{}{}{}",
+            to_source(object, depth),
+            if *optional { "?." } else { "." },
+            name.lexeme()
+        ),
+        Expression::Conditional {
+            condition,
+            then_branch,
+            else_branch,
+            ..
+        } => format!(
+            "{} ? {} : {}",
+            to_source(condition, depth),
+            to_source(then_branch, depth),
+            to_source(else_branch, depth)
+        ),
+        Expression::Range {
+            start, end, step, ..
+        } => match step {
+            Some(step) => format!(
+                "{}..{} step {}",
+                to_source(start, depth),
+                to_source(end, depth),
+                to_source(step, depth)
+            ),
+            None => format!("{}..{}", to_source(start, depth), to_source(end, depth)),
+        },
+        Expression::Call {
+            callee,
+            arguments,
+            named,
+            ..
+        } => {
+            let arguments: Vec<String> =
+                arguments
+                    .iter()
+                    .map(|element| to_source(element, depth))
+                    .chain(named.iter().map(|(name, value)| {
+                        format!("{}: {}", name.lexeme(), to_source(value, depth))
+                    }))
+                    .collect();
+
+            format!("{}({})", to_source(callee, depth), arguments.join(", "))
+        }
+        Expression::List { elements, .. } => {
+            let elements: Vec<String> = elements
+                .iter()
+                .map(|element| to_source(element, depth))
+                .collect();
+
+            format!("[{}]", elements.join(", "))
+        }
+        Expression::Index { object, index, .. } => {
+            format!("{}[{}]", to_source(object, depth), to_source(index, depth))
+        }
+        Expression::Map { entries, .. } => {
+            let entries: Vec<String> = entries
+                .iter()
+                .map(|(key, value)| format!("{}: {}", key.lexeme(), to_source(value, depth)))
+                .collect();
+
+            format!("{{{}}}", entries.join(", "))
+        }
+        Expression::Function { declaration } => function_source(declaration, depth),
+        Expression::Match { subject, arms, .. } => {
+            let indent = INDENT.repeat(depth + 1);
+            let arms: Vec<String> = arms
+                .iter()
+                .map(|arm| {
+                    format!(
+                        "{}{}{}",
+                        indent,
+                        arm_head(arm, depth + 1),
+                        to_source(&arm.body, depth + 1)
+                    )
+                })
+                .collect();
+
+            format!(
+                "match ({}) {{\n{}\n{}}}",
+                to_source(subject, depth),
+                arms.join(",\n"),
+                INDENT.repeat(depth)
+            )
+        }
+    }
+}
+
+// `pattern if guard => `
+fn arm_head<T>(arm: &MatchArm<T>, depth: usize) -> String {
+    match &arm.guard {
+        Some(guard) => format!(
+            "{} if {} => ",
+            pattern_source(&arm.pattern),
+            to_source(guard, depth)
+        ),
+        None => format!("{} => ", pattern_source(&arm.pattern)),
+    }
+}
+
+fn pattern_source(pattern: &Pattern) -> String {
+    match pattern {
+        Pattern::Wildcard { .. } => "_".to_string(),
+        Pattern::Literal { value, token } => to_source(
+            &Expression::Literal {
+                literal_value: value.clone(),
+                line: token.get_line(),
+            },
+            0,
+        ),
+        Pattern::Binding { name } => name.lexeme().to_string(),
+        Pattern::Alternative { alternatives } => {
+            let alternatives: Vec<String> = alternatives.iter().map(pattern_source).collect();
+
+            alternatives.join(" | ")
+        }
+        Pattern::List { elements, rest, .. } => {
+            let mut elements: Vec<String> = elements.iter().map(pattern_source).collect();
+            if let Some(rest) = rest {
+                elements.push(format!("...{}", rest.lexeme()));
+            }
+
+            format!("[{}]", elements.join(", "))
+        }
+        Pattern::Map { entries, .. } => {
+            let entries: Vec<String> = entries
+                .iter()
+                .map(|(key, pattern)| match pattern {
+                    Pattern::Binding { name } if name == key => key.lexeme().to_string(),
+                    Pattern::Default {
+                        pattern, default, ..
+                    } if **pattern == Pattern::Binding { name: key.clone() } => {
+                        format!("{} = {}", key.lexeme(), to_source(default, 0))
+                    }
+                    _ => format!("{}: {}", key.lexeme(), pattern_source(pattern)),
+                })
+                .collect();
+
+            format!("{{{}}}", entries.join(", "))
+        }
+        Pattern::Variant {
+            enumeration,
+            name,
+            fields,
+        } => {
+            let mut source = match enumeration {
+                Some(enumeration) => format!("{}.{}", enumeration.lexeme(), name.lexeme()),
+                None => name.lexeme().to_string(),
+            };
+
+            if let Some(fields) = fields {
+                let fields: Vec<String> = fields.iter().map(pattern_source).collect();
+                source.push_str(&format!("({})", fields.join(", ")));
+            }
+
+            source
+        }
+        Pattern::Default {
+            pattern, default, ..
+        } => format!("{} = {}", pattern_source(pattern), to_source(default, 0)),
+    }
+}
+
+// `fun name(a, b) { ... }`, `fun (a, b) { ... }` or `(a, b) => a + b`.
+fn function_source(declaration: &FunctionDeclaration, depth: usize) -> String {
+    let parameters: Vec<String> = declaration
+        .parameters
+        .iter()
+        .map(|parameter| match (&parameter.default, parameter.rest) {
+            (_, true) => format!("...{}", parameter.name.lexeme()),
+            (Some(default), _) => format!(
+                "{} = {}",
+                parameter.name.lexeme(),
+                to_source(default, depth)
+            ),
+            (None, _) => parameter.name.lexeme().to_string(),
+        })
+        .collect();
+    let parameters = parameters.join(", ");
+
+    if let (
+        true,
+        [Statement::Return {
+            value: Some(value), ..
+        }],
+    ) = (declaration.arrow, declaration.body.as_slice())
+    {
+        return format!("({}) => {}", parameters, to_source(value, depth));
+    }
+
+    let mut body = Formatter::default();
+    body.block(&declaration.body, depth);
+
+    match &declaration.name {
+        Some(name) => format!("fun {}({}) {}", name.lexeme(), parameters, body.output),
+        None => format!("fun ({}) {}", parameters, body.output),
+    }
+}
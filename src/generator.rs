@@ -0,0 +1,504 @@
+use std::cell::RefCell;
+use std::fmt;
+use std::rc::Rc;
+
+use crate::{
+    error::RuntimeError, heap::Tracer, iteration::Iteration, Environment, Function, Interpreter,
+    Literal, Statement,
+};
+
+// A call of a function that contains `yield`, suspended at its last `yield`. The interpreter walks
+// the tree recursively and can't stop halfway through a statement, so a generator keeps its own
+// stack of the statements it is inside of. Only blocks and `if`, `for`, `match` and `try`
+// statements get a frame there, and only when a `yield` is somewhere inside them; everything else
+// runs in one go, as it would in any other function.
+pub struct Generator {
+    function: Function,
+    state: RefCell<State>,
+}
+
+struct State {
+    // The innermost scope the body was in when it last stopped.
+    environment: Environment,
+    // Empty once the body has run to its end, returned or failed.
+    frames: Vec<Frame>,
+    running: bool,
+}
+
+// Where the generator is inside of one statement. The statement itself is not stored: it is the
+// one the frame below has entered, found again from the body on every step (see `owner`).
+enum Frame {
+    // The next statement of a block, or of the body for the first frame.
+    Block {
+        next: usize,
+        scoped: bool,
+    },
+    // The branch of an `if` that was taken.
+    Branch {
+        then: bool,
+        entered: bool,
+    },
+    // The body of a `for` loop, in a scope holding the loop variables when `scoped`.
+    Loop {
+        iteration: Iteration,
+        scoped: bool,
+    },
+    // The body of the match arm that applied, in a scope holding its bindings.
+    Arm {
+        index: usize,
+        entered: bool,
+    },
+    // One part of a `try` statement, with the error to raise again once `finally` is done.
+    Try {
+        part: Part,
+        entered: bool,
+        pending: Option<RuntimeError>,
+    },
+}
+
+#[derive(PartialEq)]
+enum Part {
+    Body,
+    Catch,
+    Finally,
+}
+
+impl Generator {
+    // `environment` holds the parameters, already bound to the arguments of the call.
+    pub fn new(function: Function, environment: Environment) -> Self {
+        Self {
+            function,
+            state: RefCell::new(State {
+                environment,
+                frames: vec![Frame::Block {
+                    next: 0,
+                    scoped: false,
+                }],
+                running: false,
+            }),
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        self.function.name()
+    }
+
+    // While the generator runs, its frames are on the interpreter's stack, not here. Only loops
+    // hold values, what they walk.
+    pub(crate) fn trace(&self, tracer: &mut Tracer) {
+        self.function.closure.trace(tracer);
+
+        if let Ok(state) = self.state.try_borrow() {
+            state.environment.trace(tracer);
+
+            for frame in &state.frames {
+                if let Frame::Loop { iteration, .. } = frame {
+                    iteration.trace(tracer);
+                }
+            }
+        }
+    }
+
+    // Ends a generator the collector found unreachable, since its frames can hold on to it.
+    // The scopes a generator nobody refers to anymore holds, to be dropped without recursing, see
+    // `drop_scopes`.
+    pub(crate) fn into_environments(self) -> [Environment; 2] {
+        [self.function.closure, self.state.into_inner().environment]
+    }
+
+    pub(crate) fn discard(&self) {
+        let frames = match self.state.try_borrow_mut() {
+            Ok(mut state) => std::mem::take(&mut state.frames),
+            Err(_) => return,
+        };
+
+        drop(frames);
+    }
+}
+
+// Generators are only ever shared, never copied, so two of them are the same one or different.
+impl PartialEq for Generator {
+    fn eq(&self, other: &Self) -> bool {
+        std::ptr::eq(self, other)
+    }
+}
+
+impl fmt::Debug for Generator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "<generator {}>", self.name())
+    }
+}
+
+// The statement the top frame is inside of, or `None` when it is the frame of the body.
+fn owner<'a>(body: &'a [Statement], frames: &[Frame]) -> Option<&'a Statement> {
+    let mut owner = None;
+
+    for frame in &frames[..frames.len() - 1] {
+        owner = Some(child(body, owner, frame));
+    }
+
+    owner
+}
+
+// The statement `frame`, inside of `owner`, has entered.
+fn child<'a>(body: &'a [Statement], owner: Option<&'a Statement>, frame: &Frame) -> &'a Statement {
+    match (frame, owner) {
+        (Frame::Block { next, .. }, None) => &body[next - 1],
+        (Frame::Block { next, .. }, Some(Statement::Block { statements, .. })) => {
+            &statements[next - 1]
+        }
+        (
+            Frame::Branch { then: true, .. },
+            Some(Statement::If { then_statement, .. } | Statement::IfElse { then_statement, .. }),
+        ) => then_statement,
+        (Frame::Branch { then: false, .. }, Some(Statement::IfElse { else_stament, .. })) => {
+            else_stament
+        }
+        (Frame::Loop { .. }, Some(Statement::For { body, .. })) => body,
+        (Frame::Arm { index, .. }, Some(Statement::Match { arms, .. })) => &arms[*index].body,
+        (
+            Frame::Try { part, .. },
+            Some(Statement::Try {
+                try_statement,
+                catch_clause,
+                finally_statement,
+            }),
+        ) => match part {
+            Part::Body => try_statement,
+            Part::Catch => &catch_clause.as_ref().expect("A catch frame has a catch").1,
+            Part::Finally => finally_statement
+                .as_ref()
+                .expect("A finally frame has a finally"),
+        },
+        _ => unreachable!("A generator frame always matches the statement it is in"),
+    }
+}
+
+impl Interpreter {
+    // Runs the generator until its next `yield` and returns the value yielded, or `None` once the
+    // body has run to its end or returned. An error ends the generator too.
+    pub fn resume(
+        &mut self,
+        generator: &Generator,
+        line: u32,
+    ) -> Result<Option<Literal>, RuntimeError> {
+        let (environment, mut frames) = {
+            let mut state = generator.state.borrow_mut();
+
+            if state.running {
+                return Err(RuntimeError::new(
+                    format!("Generator {}() is already running.", generator.name()),
+                    line,
+                ));
+            }
+
+            if state.frames.is_empty() {
+                return Ok(None);
+            }
+
+            state.running = true;
+            (state.environment.clone(), std::mem::take(&mut state.frames))
+        };
+
+        let declaration = Rc::clone(&generator.function.declaration);
+        let (result, environment) =
+            self.with_frame(&generator.function, environment, line, |interpreter| {
+                interpreter.run_frames(&declaration.body, &mut frames)
+            });
+
+        let mut state = generator.state.borrow_mut();
+        state.running = false;
+        state.environment = environment;
+        state.frames = frames;
+
+        match result {
+            Err(RuntimeError::Return { .. }) => Ok(None),
+            result => result,
+        }
+    }
+
+    fn run_frames(
+        &mut self,
+        body: &[Statement],
+        frames: &mut Vec<Frame>,
+    ) -> Result<Option<Literal>, RuntimeError> {
+        while !frames.is_empty() {
+            match self.step_frame(body, frames) {
+                Ok(Some(value)) => return Ok(Some(value)),
+                Ok(None) => (),
+                Err(error) => self.recover_frames(body, frames, error)?,
+            }
+        }
+
+        Ok(None)
+    }
+
+    // Moves the top frame on by one statement.
+    fn step_frame(
+        &mut self,
+        body: &[Statement],
+        frames: &mut Vec<Frame>,
+    ) -> Result<Option<Literal>, RuntimeError> {
+        let owner = owner(body, frames);
+        let frame = frames.last_mut().expect("A running generator has a frame");
+
+        match frame {
+            Frame::Block { next, scoped } => {
+                let statements = match owner {
+                    Some(Statement::Block { statements, .. }) => statements,
+                    _ => body,
+                };
+
+                if *next < statements.len() {
+                    *next += 1;
+                    return self.start_statement(&statements[*next - 1], frames);
+                }
+
+                if *scoped {
+                    self.environment().pop_scope();
+                }
+                frames.pop();
+            }
+            Frame::Branch { entered, .. } | Frame::Arm { entered, .. } if !*entered => {
+                *entered = true;
+                return self.start_statement(child(body, owner, frame), frames);
+            }
+            Frame::Branch { .. } => {
+                frames.pop();
+            }
+            Frame::Arm { .. } => {
+                self.environment().pop_scope();
+                frames.pop();
+            }
+            Frame::Loop { iteration, scoped } => {
+                let Some(Statement::For {
+                    keyword,
+                    key,
+                    value,
+                    body: loop_body,
+                    ..
+                }) = owner
+                else {
+                    unreachable!("A loop frame is inside a for statement");
+                };
+
+                if *scoped {
+                    self.environment().pop_scope();
+                    *scoped = false;
+                }
+
+                let mut bindings = vec![];
+
+                match key {
+                    Some(key) => match iteration.next_entry(self, keyword.get_line())? {
+                        Some((key_value, value_value)) => {
+                            self.destructure(key, &key_value, &mut bindings)?;
+                            self.destructure(value, &value_value, &mut bindings)?;
+                        }
+                        None => {
+                            frames.pop();
+                            return Ok(None);
+                        }
+                    },
+                    None => match iteration.next_value(self, keyword.get_line())? {
+                        Some(value_value) => {
+                            self.destructure(value, &value_value, &mut bindings)?
+                        }
+                        None => {
+                            frames.pop();
+                            return Ok(None);
+                        }
+                    },
+                }
+
+                self.environment().push_scope();
+                for (name, value) in bindings {
+                    self.environment().define(name, value);
+                }
+                *scoped = true;
+
+                return self.start_statement(loop_body, frames);
+            }
+            Frame::Try { entered, .. } if !*entered => {
+                *entered = true;
+                return self.start_statement(child(body, owner, frame), frames);
+            }
+            Frame::Try {
+                part,
+                entered,
+                pending,
+            } => {
+                let Some(Statement::Try {
+                    finally_statement, ..
+                }) = owner
+                else {
+                    unreachable!("A try frame is inside a try statement");
+                };
+
+                if *part == Part::Catch {
+                    self.environment().pop_scope();
+                }
+
+                if *part != Part::Finally && finally_statement.is_some() {
+                    *part = Part::Finally;
+                    *entered = false;
+                } else {
+                    let pending = pending.take();
+                    frames.pop();
+
+                    if let Some(error) = pending {
+                        return Err(error);
+                    }
+                }
+            }
+        }
+
+        Ok(None)
+    }
+
+    // Starts running `statement`: in one go when it has no `yield` inside, or by pushing the frame
+    // that will walk it.
+    fn start_statement(
+        &mut self,
+        statement: &Statement,
+        frames: &mut Vec<Frame>,
+    ) -> Result<Option<Literal>, RuntimeError> {
+        if !statement.contains_yield() {
+            self.execute(statement)?;
+            return Ok(None);
+        }
+
+        match statement {
+            Statement::Yield { value, .. } => {
+                let value = match value {
+                    Some(value) => value.evaluate(self)?,
+                    None => Literal::Nil,
+                };
+
+                return Ok(Some(value));
+            }
+            Statement::Block { .. } => {
+                self.environment().push_scope();
+                frames.push(Frame::Block {
+                    next: 0,
+                    scoped: true,
+                });
+            }
+            Statement::If { condition, .. } | Statement::IfElse { condition, .. } => {
+                let then = condition.evaluate(self)? == Literal::Boolean(true);
+
+                if then || matches!(statement, Statement::IfElse { .. }) {
+                    frames.push(Frame::Branch {
+                        then,
+                        entered: false,
+                    });
+                }
+            }
+            Statement::For {
+                keyword, iterable, ..
+            } => {
+                let iterable = iterable.evaluate(self)?;
+                let iteration = Iteration::new(&iterable)
+                    .map_err(|message| RuntimeError::new(message, keyword.get_line()))?;
+
+                frames.push(Frame::Loop {
+                    iteration,
+                    scoped: false,
+                });
+            }
+            Statement::Match {
+                keyword,
+                subject,
+                arms,
+                ..
+            } => {
+                let value = subject.evaluate(self)?;
+
+                match self.select_arm(&value, arms)? {
+                    Some(index) => frames.push(Frame::Arm {
+                        index,
+                        entered: false,
+                    }),
+                    None => {
+                        return Err(RuntimeError::new(
+                            format!("No match arm for {}.", value),
+                            keyword.get_line(),
+                        ))
+                    }
+                }
+            }
+            Statement::Try { .. } => frames.push(Frame::Try {
+                part: Part::Body,
+                entered: false,
+                pending: None,
+            }),
+            _ => unreachable!("Only the statements above can have a yield inside"),
+        }
+
+        Ok(None)
+    }
+
+    // Leaves the frames `error` goes through, closing their scopes, up to a `try` that catches it
+    // or has a `finally` to run first. Without one, the error ends the generator.
+    fn recover_frames(
+        &mut self,
+        body: &[Statement],
+        frames: &mut Vec<Frame>,
+        error: RuntimeError,
+    ) -> Result<(), RuntimeError> {
+        while !frames.is_empty() {
+            let owner = owner(body, frames);
+
+            match frames.pop().expect("The loop checks for a frame") {
+                Frame::Block { scoped: true, .. } | Frame::Loop { scoped: true, .. } => {
+                    self.environment().pop_scope();
+                }
+                Frame::Arm { .. } => self.environment().pop_scope(),
+                Frame::Try { part, .. } => {
+                    let Some(Statement::Try {
+                        catch_clause,
+                        finally_statement,
+                        ..
+                    }) = owner
+                    else {
+                        unreachable!("A try frame is inside a try statement");
+                    };
+
+                    if part == Part::Catch {
+                        self.environment().pop_scope();
+                    }
+
+                    if let (Part::Body, Some((name, _)), RuntimeError::Exception { value, .. }) =
+                        (&part, catch_clause, &error)
+                    {
+                        self.environment().push_scope();
+                        self.environment().define(name.get_symbol(), value.clone());
+                        frames.push(Frame::Try {
+                            part: Part::Catch,
+                            entered: false,
+                            pending: None,
+                        });
+
+                        return Ok(());
+                    }
+
+                    if part != Part::Finally && finally_statement.is_some() {
+                        frames.push(Frame::Try {
+                            part: Part::Finally,
+                            entered: false,
+                            pending: Some(error),
+                        });
+
+                        return Ok(());
+                    }
+
+                    // Past a `finally`, an error raised inside it goes on in place of the
+                    // pending one, as it does outside generators.
+                }
+                _ => (),
+            }
+        }
+
+        Err(error)
+    }
+}
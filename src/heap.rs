@@ -0,0 +1,288 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::{Rc, Weak};
+
+use crate::{
+    environment::Scope, generator::Generator, Function, List, Literal, Map, Module, Variant,
+};
+
+// Scopes are the only values a script can change once they exist (a generator changes the scope
+// it is suspended in), so every reference cycle runs through one: a function stored in the scope
+// it closes over, a generator held by a variable of its own body. Reference counting never frees
+// those, so every scope is registered here, and a mark-and-sweep collection runs once enough memory
+// has been allocated since the last one, by new scopes and by the strings and collections the
+// interpreter accounts for.
+//
+// The roots, the global environment, the scopes of the calls in progress and the temporaries the
+// interpreter holds in Rust locals, all own plain `Rc`s and can't be listed. A collection finds them
+// instead: it traces every registered scope and the values reachable from them, and counts the
+// references each object gets from inside that graph. An object with more references than that is
+// held from outside, so it is a root. Whatever no root reaches is garbage, and its scopes are
+// emptied so that the cycles through them fall apart.
+thread_local! {
+    static HEAP: RefCell<Heap> = const {
+        RefCell::new(Heap {
+            scopes: Vec::new(),
+            allocated: 0,
+            threshold: MIN_THRESHOLD,
+            stress: false,
+        })
+    };
+}
+
+// A collection runs once this many bytes were allocated since the last one, or as many as that one
+// found held by the objects alive if that is more, so that a large live heap isn't traced over and
+// over while the garbage is still small next to it.
+const MIN_THRESHOLD: usize = 256 * 1024;
+
+struct Heap {
+    scopes: Vec<Weak<RefCell<Scope>>>,
+    allocated: usize,
+    threshold: usize,
+    stress: bool,
+}
+
+// Called by every new scope, which is already referenced by its creator, so collecting right away
+// can't free it.
+pub(crate) fn register(scope: &Rc<RefCell<Scope>>) {
+    let collect = HEAP.with_borrow_mut(|heap| {
+        heap.scopes.push(Rc::downgrade(scope));
+        heap.allocated += size_of::<RefCell<Scope>>();
+
+        heap.stress || heap.allocated >= heap.threshold
+    });
+
+    if collect {
+        self::collect();
+    }
+}
+
+// Called by the interpreter for the bytes of every string and collection it builds. The new value
+// is held by the caller, so collecting right away can't free it either.
+pub(crate) fn allocated(bytes: usize) {
+    let collect = HEAP.with_borrow_mut(|heap| {
+        heap.allocated = heap.allocated.saturating_add(bytes);
+
+        heap.allocated >= heap.threshold
+    });
+
+    if collect {
+        self::collect();
+    }
+}
+
+// With `stress`, every new scope starts a collection, so that a value the interpreter uses without
+// holding a reference to it is freed at once instead of once in a while. Applies to the current
+// thread, where the interpreter runs.
+pub fn set_stress(stress: bool) {
+    HEAP.with_borrow_mut(|heap| heap.stress = stress);
+}
+
+// Frees the scopes no longer reachable and returns how many are left.
+pub fn collect() -> usize {
+    let scopes: Vec<Rc<RefCell<Scope>>> = HEAP.with_borrow(|heap| {
+        heap.scopes
+            .iter()
+            .filter_map(|scope| scope.upgrade())
+            .collect()
+    });
+
+    let mut tracer = Tracer::default();
+    for scope in scopes {
+        tracer.add(Object::Scope(scope));
+    }
+    tracer.trace();
+
+    let marked = tracer.mark();
+    let live: usize = tracer
+        .objects
+        .iter()
+        .zip(&marked)
+        .filter(|(_, &marked)| marked)
+        .map(|(object, _)| object.allocated_bytes())
+        .sum();
+    let garbage = tracer.sweep(&marked);
+    drop(tracer);
+    drop(garbage);
+
+    HEAP.with_borrow_mut(|heap| {
+        heap.scopes.retain(|scope| scope.strong_count() > 0);
+        heap.allocated = 0;
+        heap.threshold = MIN_THRESHOLD.max(live);
+
+        heap.scopes.len()
+    })
+}
+
+// A value that holds references to others. Each is held by the tracer once, under the address of
+// its allocation.
+#[derive(Clone)]
+pub(crate) enum Object {
+    Scope(Rc<RefCell<Scope>>),
+    Function(Rc<Function>),
+    List(Rc<List>),
+    Map(Rc<Map>),
+    Variant(Rc<Variant>),
+    Generator(Rc<Generator>),
+    Module(Rc<Module>),
+}
+
+impl Object {
+    fn address(&self) -> usize {
+        match self {
+            Object::Scope(scope) => Rc::as_ptr(scope) as *const () as usize,
+            Object::Function(function) => Rc::as_ptr(function) as *const () as usize,
+            Object::List(list) => Rc::as_ptr(list) as *const () as usize,
+            Object::Map(map) => Rc::as_ptr(map) as *const () as usize,
+            Object::Variant(variant) => Rc::as_ptr(variant) as *const () as usize,
+            Object::Generator(generator) => Rc::as_ptr(generator) as *const () as usize,
+            Object::Module(module) => Rc::as_ptr(module) as *const () as usize,
+        }
+    }
+
+    fn strong_count(&self) -> usize {
+        match self {
+            Object::Scope(scope) => Rc::strong_count(scope),
+            Object::Function(function) => Rc::strong_count(function),
+            Object::List(list) => Rc::strong_count(list),
+            Object::Map(map) => Rc::strong_count(map),
+            Object::Variant(variant) => Rc::strong_count(variant),
+            Object::Generator(generator) => Rc::strong_count(generator),
+            Object::Module(module) => Rc::strong_count(module),
+        }
+    }
+
+    // An estimate of the memory the object holds, counted the way `allocated` counts it.
+    fn allocated_bytes(&self) -> usize {
+        match self {
+            Object::Scope(scope) => {
+                size_of::<RefCell<Scope>>() + scope.try_borrow().map_or(0, |s| s.allocated_bytes())
+            }
+            Object::Function(_) => size_of::<Function>(),
+            Object::List(list) => list.len() * size_of::<Literal>(),
+            Object::Map(map) => map
+                .iter()
+                .map(|(key, _)| size_of::<(String, Literal)>() + key.len())
+                .sum(),
+            Object::Variant(variant) => variant.values.len() * size_of::<Literal>(),
+            Object::Generator(_) => size_of::<Generator>(),
+            Object::Module(_) => size_of::<Module>(),
+        }
+    }
+
+    // Reports every reference this object holds, and only those: a reference reported but not
+    // held would make an object look unreachable while something still uses it.
+    fn trace(&self, tracer: &mut Tracer) {
+        match self {
+            Object::Scope(scope) => {
+                // A scope borrowed right now is being used, and what it refers to is treated as
+                // held from outside.
+                if let Ok(scope) = scope.try_borrow() {
+                    scope.trace(tracer);
+                }
+            }
+            Object::Function(function) => function.closure.trace(tracer),
+            Object::List(list) => list.iter().for_each(|value| tracer.literal(value)),
+            Object::Map(map) => map.iter().for_each(|(_, value)| tracer.literal(value)),
+            Object::Variant(variant) => variant.values.iter().for_each(|v| tracer.literal(v)),
+            Object::Generator(generator) => generator.trace(tracer),
+            Object::Module(module) => module.environment.trace(tracer),
+        }
+    }
+}
+
+#[derive(Default)]
+pub(crate) struct Tracer {
+    objects: Vec<Object>,
+    indices: HashMap<usize, usize>,
+    // How often each object is referenced from the others, and which ones it references.
+    references: Vec<usize>,
+    edges: Vec<Vec<usize>>,
+    current: usize,
+}
+
+impl Tracer {
+    fn add(&mut self, object: Object) -> usize {
+        let address = object.address();
+
+        if let Some(&index) = self.indices.get(&address) {
+            return index;
+        }
+
+        self.indices.insert(address, self.objects.len());
+        self.objects.push(object);
+        self.references.push(0);
+        self.edges.push(Vec::new());
+
+        self.objects.len() - 1
+    }
+
+    // A reference from the object being traced.
+    pub(crate) fn reference(&mut self, object: Object) {
+        let index = self.add(object);
+
+        self.references[index] += 1;
+        self.edges[self.current].push(index);
+    }
+
+    pub(crate) fn literal(&mut self, value: &Literal) {
+        match value {
+            Literal::Function(function) => self.reference(Object::Function(Rc::clone(function))),
+            Literal::List(list) => self.reference(Object::List(Rc::clone(list))),
+            Literal::Map(map) => self.reference(Object::Map(Rc::clone(map))),
+            Literal::Variant(variant) => self.reference(Object::Variant(Rc::clone(variant))),
+            Literal::Generator(generator) | Literal::GeneratorNext(generator) => {
+                self.reference(Object::Generator(Rc::clone(generator)))
+            }
+            Literal::Module(module) => self.reference(Object::Module(Rc::clone(module))),
+            _ => (),
+        }
+    }
+
+    // Traces every object once, including the ones found along the way.
+    fn trace(&mut self) {
+        while self.current < self.objects.len() {
+            let object = self.objects[self.current].clone();
+            object.trace(self);
+            self.current += 1;
+        }
+    }
+
+    // Whether the roots reach each object.
+    fn mark(&self) -> Vec<bool> {
+        let mut marked = vec![false; self.objects.len()];
+        // The tracer holds one reference to every object itself.
+        let mut pending: Vec<usize> = (0..self.objects.len())
+            .filter(|&index| self.objects[index].strong_count() - 1 > self.references[index])
+            .collect();
+
+        while let Some(index) = pending.pop() {
+            if !marked[index] {
+                marked[index] = true;
+                pending.extend(&self.edges[index]);
+            }
+        }
+
+        marked
+    }
+
+    // Empties the scopes and generators the roots don't reach. What the scopes held is returned, to
+    // be dropped once their borrows are over.
+    fn sweep(&self, marked: &[bool]) -> Vec<Scope> {
+        let mut garbage = Vec::new();
+
+        for (object, &marked) in self.objects.iter().zip(marked) {
+            match object {
+                Object::Scope(scope) if !marked => {
+                    if let Ok(mut scope) = scope.try_borrow_mut() {
+                        garbage.push(scope.take());
+                    }
+                }
+                Object::Generator(generator) if !marked => generator.discard(),
+                _ => (),
+            }
+        }
+
+        garbage
+    }
+}
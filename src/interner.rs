@@ -0,0 +1,61 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::{Mutex, OnceLock};
+
+// A global, append-only table mapping identifier names to compact symbol IDs. Names are interned
+// once at scan time so that environments can be keyed by a `u32` instead of hashing and cloning
+// the full name on every `define`, `get` and `assign`. Interned names are leaked on purpose: they
+// live for the whole program, which lets `Symbol::as_str` hand out `&'static str` without holding
+// the lock.
+static INTERNER: OnceLock<Mutex<Interner>> = OnceLock::new();
+
+fn get_interner() -> &'static Mutex<Interner> {
+    INTERNER.get_or_init(|| Mutex::new(Interner::default()))
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct Symbol(u32);
+
+#[derive(Default)]
+struct Interner {
+    symbols: HashMap<&'static str, Symbol>,
+    names: Vec<&'static str>,
+}
+
+impl Interner {
+    fn intern(&mut self, name: &str) -> Symbol {
+        if let Some(symbol) = self.symbols.get(name) {
+            return *symbol;
+        }
+
+        let name: &'static str = Box::leak(name.to_string().into_boxed_str());
+        let symbol = Symbol(self.names.len() as u32);
+
+        self.names.push(name);
+        self.symbols.insert(name, symbol);
+
+        symbol
+    }
+}
+
+pub fn intern(name: &str) -> Symbol {
+    get_interner().lock().unwrap().intern(name)
+}
+
+impl Symbol {
+    pub fn as_str(&self) -> &'static str {
+        get_interner().lock().unwrap().names[self.0 as usize]
+    }
+}
+
+impl From<&str> for Symbol {
+    fn from(name: &str) -> Self {
+        intern(name)
+    }
+}
+
+impl fmt::Display for Symbol {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
@@ -1,20 +1,66 @@
-use std::borrow::BorrowMut;
+use std::cell::Cell;
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::io::{self, BufRead, Read, Write};
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::Instant;
 
-use crate::{environment::*, Expression, Literal, Token};
+use crate::{
+    environment::*,
+    error::{Limit, RuntimeError, StackFrame},
+    generator::Generator,
+    heap,
+    interner::Symbol,
+    iteration::Iteration,
+    list::drop_nested,
+    optimizer,
+    pattern::{MatchArm, Pattern},
+    resolver, stdlib, Expression, List, Literal, Parser, Scanner, Token, TokenType,
+    MAX_NESTING_DEPTH,
+};
 
 pub enum Statement {
     Expression {
         expression: Expression,
     },
     Print {
+        keyword: Token,
         expression: Expression,
     },
     Var {
         token: Token,
         expression: Expression,
     },
+    // `const NAME = value;`, a variable that can't be assigned again.
+    Const {
+        token: Token,
+        expression: Expression,
+    },
+    // `var [a, b] = list;` or `var {name, age} = map;`, which defines every name the pattern binds.
+    // With `const` in place of `var`, the names are constants.
+    Destructure {
+        keyword: Token,
+        pattern: Pattern,
+        expression: Expression,
+    },
+    // `line` is the line of the `{`, which an empty block has no other way to report.
     Block {
         statements: Vec<Statement>,
+        line: u32,
+    },
+    // `for (key, value in iterable) body`; without a key, `value` gets the keys of a map and the
+    // elements of anything else.
+    For {
+        keyword: Token,
+        key: Option<Box<Pattern>>,
+        value: Box<Pattern>,
+        iterable: Expression,
+        body: Box<Statement>,
     },
     If {
         condition: Expression,
@@ -25,55 +71,993 @@ pub enum Statement {
         then_statement: Box<Statement>,
         else_stament: Box<Statement>,
     },
+    Comment {
+        token: Token,
+        trailing: bool,
+    },
+    // An empty line between two statements, only kept when parsing for the formatter.
+    BlankLine {
+        token: Token,
+    },
+    Throw {
+        keyword: Token,
+        expression: Expression,
+    },
+    Try {
+        try_statement: Box<Statement>,
+        catch_clause: Option<(Token, Box<Statement>)>,
+        finally_statement: Option<Box<Statement>>,
+    },
+    Import {
+        keyword: Token,
+        path: Token,
+        name: Token,
+    },
+    // `fun name(...) { ... }`, which binds the function to `name` in the current scope.
+    Function {
+        declaration: Rc<FunctionDeclaration>,
+    },
+    Return {
+        keyword: Token,
+        value: Option<Expression>,
+    },
+    // `yield value;`, which only a generator body can run (see `Generator`).
+    Yield {
+        keyword: Token,
+        value: Option<Expression>,
+    },
+    Match {
+        keyword: Token,
+        subject: Expression,
+        arms: Vec<MatchArm<Statement>>,
+        // The comments and blank lines between the arms, each with the index of the arm it comes
+        // before, only kept when parsing for the formatter.
+        comments: Vec<(usize, Statement)>,
+    },
+    // `enum Shape { Circle(radius), Empty }`: each variant with the names of its fields.
+    Enum {
+        keyword: Token,
+        name: Token,
+        variants: Vec<(Token, Vec<Token>)>,
+        // Like the comments of a `Match`, each with the index of the variant it comes before.
+        comments: Vec<(usize, Statement)>,
+    },
+}
+
+// The value an `import` binds: the global scope a module ran in. It stays shared with the
+// module's functions, so `d.count` sees what `d.inc()` did to it.
+pub struct Module {
+    pub path: String,
+    pub environment: Environment,
+}
+
+impl PartialEq for Module {
+    fn eq(&self, other: &Self) -> bool {
+        std::ptr::eq(self, other)
+    }
+}
+
+impl fmt::Debug for Module {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "<module {}>", self.path)
+    }
+}
+
+// The value an `enum` declaration binds. Its variants are reached as properties: a variant with
+// fields is a constructor, `Shape.Circle(1)`, one without is a value of its own, `Shape.Empty`.
+#[derive(PartialEq, Debug)]
+pub struct Enum {
+    pub name: String,
+    pub variants: Vec<(String, Vec<String>)>,
+}
+
+// A value built by a variant, which knows the enum it comes from. Two are equal when they come
+// from the same declaration and variant and their fields are equal.
+#[derive(PartialEq, Debug)]
+pub struct Variant {
+    pub enumeration: Rc<Enum>,
+    pub index: usize,
+    pub values: Vec<Literal>,
+}
+
+// Fields can hold lists and other variants, see `drop_nested`.
+impl Drop for Variant {
+    fn drop(&mut self) {
+        drop_nested(std::mem::take(&mut self.values));
+    }
+}
+
+impl Variant {
+    // `Shape.Circle`
+    pub fn name(&self) -> String {
+        format!(
+            "{}.{}",
+            self.enumeration.name, self.enumeration.variants[self.index].0
+        )
+    }
+
+    pub fn fields(&self) -> &[String] {
+        &self.enumeration.variants[self.index].1
+    }
+}
+
+// A function as written: `fun name(a, b) { ... }`, `fun (a, b) { ... }` or `(a, b) => a + b`.
+// `keyword` is the `fun` or `=>` token. The body of an arrow lambda is a single `return` of its
+// expression. A function whose body has a `yield` is a generator: calling it returns a
+// `Generator` instead of running the body.
+pub struct FunctionDeclaration {
+    pub keyword: Token,
+    pub name: Option<Token>,
+    pub parameters: Vec<Parameter>,
+    pub body: Vec<Statement>,
+    pub arrow: bool,
+    pub generator: bool,
+}
+
+// `name`, `name = default` or, last only, `...name`. A default is evaluated on every call that
+// leaves its parameter out, in the callee's scope with the parameters before it already bound.
+pub struct Parameter {
+    pub name: Token,
+    pub default: Option<Expression>,
+    pub rest: bool,
+}
+
+// Declarations are only ever shared, never copied, so two of them are the same one or different.
+impl PartialEq for FunctionDeclaration {
+    fn eq(&self, other: &Self) -> bool {
+        std::ptr::eq(self, other)
+    }
+}
+
+// The value of a function declaration or lambda: the declaration plus the scope it was evaluated
+// in, which the body keeps seeing for as long as the function lives. `file` is where it was
+// declared, for the frames its calls push.
+#[derive(Clone)]
+pub struct Function {
+    pub declaration: Rc<FunctionDeclaration>,
+    pub closure: Environment,
+    pub file: Option<String>,
+}
+
+impl Function {
+    pub fn name(&self) -> &str {
+        self.declaration
+            .name
+            .as_ref()
+            .map_or("lambda", |name| name.lexeme())
+    }
+}
+
+// Every evaluation of a declaration creates a new function, equal only to itself.
+impl PartialEq for Function {
+    fn eq(&self, other: &Self) -> bool {
+        std::ptr::eq(self, other)
+    }
+}
+
+impl fmt::Debug for Function {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "<fn {}>", self.name())
+    }
+}
+
+impl Statement {
+    // The line of the first token in the statement.
+    pub fn line(&self) -> u32 {
+        match self {
+            Statement::Expression { expression }
+            | Statement::If {
+                condition: expression,
+                ..
+            }
+            | Statement::IfElse {
+                condition: expression,
+                ..
+            } => expression.line(),
+            Statement::Var { token, .. }
+            | Statement::Const { token, .. }
+            | Statement::Comment { token, .. }
+            | Statement::BlankLine { token }
+            | Statement::Print { keyword: token, .. }
+            | Statement::Throw { keyword: token, .. }
+            | Statement::Import { keyword: token, .. }
+            | Statement::Return { keyword: token, .. }
+            | Statement::Yield { keyword: token, .. }
+            | Statement::Match { keyword: token, .. }
+            | Statement::Enum { keyword: token, .. }
+            | Statement::Destructure { keyword: token, .. }
+            | Statement::For { keyword: token, .. } => token.get_line(),
+            Statement::Function { declaration } => declaration.keyword.get_line(),
+            Statement::Block { line, .. } => *line,
+            Statement::Try { try_statement, .. } => try_statement.line(),
+        }
+    }
+
+    // Whether a `yield` runs as part of the statement, which makes a generator walk it instead of
+    // running it in one go. Functions declared inside don't count, they are generators of their
+    // own.
+    pub fn contains_yield(&self) -> bool {
+        match self {
+            Statement::Yield { .. } => true,
+            Statement::Block { statements, .. } => statements.iter().any(Statement::contains_yield),
+            Statement::If { then_statement, .. } => then_statement.contains_yield(),
+            Statement::IfElse {
+                then_statement,
+                else_stament,
+                ..
+            } => then_statement.contains_yield() || else_stament.contains_yield(),
+            Statement::For { body, .. } => body.contains_yield(),
+            Statement::Match { arms, .. } => arms.iter().any(|arm| arm.body.contains_yield()),
+            Statement::Try {
+                try_statement,
+                catch_clause,
+                finally_statement,
+            } => {
+                try_statement.contains_yield()
+                    || catch_clause
+                        .as_ref()
+                        .is_some_and(|(_, statement)| statement.contains_yield())
+                    || finally_statement
+                        .as_ref()
+                        .is_some_and(|statement| statement.contains_yield())
+            }
+            _ => false,
+        }
+    }
+}
+
+// How deep statements and expressions may nest inside one call, and how deep calls may go, before
+// evaluation stops with a "Stack overflow." error. The two are counted apart so that a recursive
+// function gets its thousand calls however many levels of nesting each of them takes.
+pub const DEFAULT_MAX_DEPTH: usize = 1000;
+pub const DEFAULT_MAX_CALL_DEPTH: usize = 1000;
+
+// The native stack one level of parser nesting or of interpreter depth takes at most, in
+// unoptimized builds, which need far more than release builds. A call is counted as the few levels
+// of nesting a typical function body adds on top of it.
+const STACK_BYTES_PER_LEVEL: usize = 32 * 1024;
+const LEVELS_PER_CALL: usize = 8;
+
+// Stacks are only reserved, but an unbounded reservation can still fail, so very high limits get
+// this much. Whatever they need beyond it is caught by `stack_exhausted`.
+const MAX_STACK_SIZE: usize = 1024 * 1024 * 1024;
+
+// How close to its end the stack may get before deeper code is refused, enough for the deepest
+// path from one check to the next.
+const STACK_RESERVE: usize = 2 * 1024 * 1024;
+
+thread_local! {
+    // The lowest address the stack of this thread may grow to, or 0 when it is not known.
+    static STACK_END: Cell<usize> = const { Cell::new(0) };
+}
+
+// Whether the current thread, if `run_on_stack` started it, has too little stack left for another
+// level of parsing or evaluation. The limits keep ordinary code far from that; this only stops
+// combinations like deep nesting in every one of a thousand calls from overflowing for real.
+pub(crate) fn stack_exhausted() -> bool {
+    let marker = 0u8;
+    let address = std::ptr::addr_of!(marker) as usize;
+
+    address < STACK_END.get()
+}
+
+// Runs `f`, which should do all the parsing and interpreting, on a thread with enough stack for
+// the given limits, plus an imported module parsed as deep as the parser allows. The default few
+// megabytes of the main thread overflow long before any limit is reached. A panic in `f`, like a
+// syntax error, goes on in the calling thread.
+pub fn run_on_stack<T: Send>(
+    max_depth: usize,
+    max_call_depth: usize,
+    f: impl FnOnce() -> T + Send,
+) -> T {
+    let stack_size = max_call_depth
+        .saturating_mul(LEVELS_PER_CALL)
+        .saturating_add(max_depth)
+        .saturating_add(MAX_NESTING_DEPTH)
+        .saturating_mul(STACK_BYTES_PER_LEVEL)
+        .min(MAX_STACK_SIZE);
+
+    thread::scope(|scope| {
+        thread::Builder::new()
+            .name("interpreter".to_string())
+            .stack_size(stack_size)
+            .spawn_scoped(scope, move || {
+                let marker = 0u8;
+                let start = std::ptr::addr_of!(marker) as usize;
+                STACK_END.set(start.saturating_sub(stack_size) + STACK_RESERVE);
+
+                f()
+            })
+            .expect("Could not start the interpreter thread")
+            .join()
+            .unwrap_or_else(|panic| std::panic::resume_unwind(panic))
+    })
+}
+
+// Bounds for running code that is not fully trusted. A step is one evaluated statement or
+// expression; allocated bytes add up every string, list and map the script creates.
+#[derive(Default)]
+pub struct Limits {
+    pub max_steps: Option<u64>,
+    pub deadline: Option<Instant>,
+    pub max_allocated_bytes: Option<usize>,
 }
 
+// Reading the clock on every step would dominate small expressions, so the deadline is only
+// checked once every this many steps.
+const DEADLINE_CHECK_INTERVAL: u64 = 1024;
+
 pub struct Interpreter {
     environment: Environment,
+    output: Box<dyn Write>,
+    input: Option<Box<dyn BufRead>>,
+    frames: Vec<StackFrame>,
+    depth: usize,
+    max_depth: usize,
+    max_call_depth: usize,
+    limits: Limits,
+    steps: u64,
+    allocated_bytes: usize,
+    cancelled: Arc<AtomicBool>,
+    modules: HashMap<PathBuf, Literal>,
+    optimize: bool,
+}
+
+// Stops the interpreter it was taken from at its next step. It can be sent to and triggered from
+// any thread. Only embedders use it, the command line relies on the other limits.
+#[derive(Clone)]
+pub struct CancelHandle(Arc<AtomicBool>);
+
+impl CancelHandle {
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
 }
 
 impl Interpreter {
     pub fn new(environment: Environment) -> Self {
-        Self { environment }
+        Self::with_output(environment, Box::new(io::stdout()))
+    }
+
+    // Lets an embedder (or a test) decide where `print` writes to instead of the process stdout.
+    pub fn with_output(environment: Environment, output: Box<dyn Write>) -> Self {
+        Self {
+            environment,
+            output,
+            input: None,
+            frames: vec![StackFrame {
+                function: "script".to_string(),
+                file: None,
+                line: 0,
+            }],
+            depth: 0,
+            max_depth: DEFAULT_MAX_DEPTH,
+            max_call_depth: DEFAULT_MAX_CALL_DEPTH,
+            limits: Limits::default(),
+            steps: 0,
+            allocated_bytes: 0,
+            cancelled: Arc::new(AtomicBool::new(false)),
+            modules: HashMap::new(),
+            optimize: true,
+        }
+    }
+
+    // Lets an embedder (or a test) feed the `io` natives that read stdin. Without it they read the
+    // process stdin, through its shared handle so the REPL can keep reading from it too.
+    pub fn set_input(&mut self, input: Box<dyn BufRead>) {
+        self.input = Some(input);
+    }
+
+    // One line without its terminator, or `None` at the end of the input.
+    pub fn read_line(&mut self) -> io::Result<Option<String>> {
+        let mut line = String::new();
+
+        let read = match &mut self.input {
+            Some(input) => input.read_line(&mut line)?,
+            None => io::stdin().read_line(&mut line)?,
+        };
+
+        if read == 0 {
+            return Ok(None);
+        }
+
+        if line.ends_with('\n') {
+            line.pop();
+            if line.ends_with('\r') {
+                line.pop();
+            }
+        }
+
+        Ok(Some(line))
+    }
+
+    pub fn read_all(&mut self) -> io::Result<String> {
+        let mut text = String::new();
+
+        match &mut self.input {
+            Some(input) => input.read_to_string(&mut text)?,
+            None => io::stdin().read_to_string(&mut text)?,
+        };
+
+        Ok(text)
+    }
+
+    // Whether imported modules go through the optimizer, like the script that imports them.
+    pub fn set_optimize(&mut self, optimize: bool) {
+        self.optimize = optimize;
+    }
+
+    pub fn set_limits(&mut self, limits: Limits) {
+        self.limits = limits;
+    }
+
+    pub fn cancel_handle(&self) -> CancelHandle {
+        CancelHandle(Arc::clone(&self.cancelled))
+    }
+
+    // Whether `bytes` more would still fit under the memory cap, for natives that want to check
+    // before building a large value. The bytes are accounted for later, by `allocate`.
+    pub fn can_allocate(&self, bytes: usize) -> bool {
+        match self.limits.max_allocated_bytes {
+            Some(max) => self.allocated_bytes.saturating_add(bytes) <= max,
+            None => true,
+        }
+    }
+
+    // Accounts for `bytes` of new string or collection data created by the script.
+    pub fn allocate(&mut self, bytes: usize, line: u32) -> Result<(), RuntimeError> {
+        self.allocated_bytes += bytes;
+        heap::allocated(bytes);
+
+        match self.limits.max_allocated_bytes {
+            Some(max) if self.allocated_bytes > max => {
+                Err(RuntimeError::limit(Limit::Memory, line))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn check_limits(&mut self) -> Option<Limit> {
+        self.steps += 1;
+
+        if self.cancelled.load(Ordering::Relaxed) {
+            return Some(Limit::Cancelled);
+        }
+
+        if self.limits.max_steps.is_some_and(|max| self.steps > max) {
+            return Some(Limit::Steps);
+        }
+
+        if self.steps.is_multiple_of(DEADLINE_CHECK_INTERVAL)
+            && self
+                .limits
+                .deadline
+                .is_some_and(|deadline| Instant::now() >= deadline)
+        {
+            return Some(Limit::Deadline);
+        }
+
+        None
+    }
+
+    pub fn set_max_depth(&mut self, max_depth: usize) {
+        self.max_depth = max_depth;
+    }
+
+    pub fn set_max_call_depth(&mut self, max_call_depth: usize) {
+        self.max_call_depth = max_call_depth;
+    }
+
+    pub fn environment(&mut self) -> &mut Environment {
+        &mut self.environment
+    }
+
+    // Called around every nested statement and expression. `line` is only evaluated when a limit
+    // is hit. A successful `enter` has to be matched by a `leave`.
+    pub fn enter(&mut self, line: impl FnOnce() -> u32) -> Result<(), RuntimeError> {
+        if let Some(limit) = self.check_limits() {
+            return Err(RuntimeError::limit(limit, line()));
+        }
+
+        if self.depth >= self.max_depth || stack_exhausted() {
+            return Err(RuntimeError::new("Stack overflow.", line()));
+        }
+
+        self.depth += 1;
+
+        Ok(())
+    }
+
+    pub fn leave(&mut self) {
+        self.depth -= 1;
+    }
+
+    // Names the file the top-level code comes from, so that traces can point at it.
+    pub fn set_script_path(&mut self, path: &str) {
+        self.frames[0].file = Some(path.to_string());
+    }
+
+    // Runs top-level code. Every frame an uncaught error leaves is added to its trace, and the
+    // top-level frame is the last one.
+    pub fn interpret(&mut self, statements: Vec<Statement>) -> Result<(), RuntimeError> {
+        self.execute_all(&statements)
+            .map_err(|error| error.unwind(&self.frames[0]))
     }
 
-    pub fn interpret(&mut self, statements: Vec<Statement>) {
+    fn execute_all(&mut self, statements: &[Statement]) -> Result<(), RuntimeError> {
         for statement in statements {
-            self.execute(statement);
+            self.execute(statement)?;
         }
+
+        Ok(())
+    }
+
+    pub fn execute(&mut self, statement: &Statement) -> Result<(), RuntimeError> {
+        self.enter(|| statement.line())?;
+        let result = self.execute_statement(statement);
+        self.leave();
+
+        result
+    }
+
+    // Binds the name a declaration statement introduces in the current scope.
+    fn declare(
+        &mut self,
+        name: &Token,
+        value: Literal,
+        constant: bool,
+    ) -> Result<(), RuntimeError> {
+        self.environment
+            .declare(name.get_symbol(), value, constant)
+            .map_err(|message| RuntimeError::new(message, name.get_line()))
+    }
+
+    // Closes `declaration` over the current scope. Storing the result in that scope, or in one it
+    // encloses, makes a cycle that only the collector in `heap` frees.
+    pub fn closure(&self, declaration: &Rc<FunctionDeclaration>) -> Literal {
+        Literal::Function(Rc::new(Function {
+            declaration: Rc::clone(declaration),
+            closure: self.environment.clone(),
+            file: self.frames.last().and_then(|frame| frame.file.clone()),
+        }))
+    }
+
+    // Runs the body in a new scope enclosed by the function's closure, with the parameters bound
+    // to the arguments. Positional arguments fill the parameters in order, with any left over
+    // going to the rest parameter; named ones then fill the parameters they name. Falling off the
+    // end of the body returns `nil`.
+    pub fn call_function(
+        &mut self,
+        function: &Function,
+        arguments: Vec<Literal>,
+        named: Vec<(Token, Literal)>,
+        line: u32,
+    ) -> Result<Literal, RuntimeError> {
+        let name = function.name();
+        let parameters = &function.declaration.parameters;
+        let positional = parameters
+            .iter()
+            .filter(|parameter| !parameter.rest)
+            .count();
+
+        let count = arguments.len();
+        let mut values: Vec<Option<Literal>> = vec![None; parameters.len()];
+        let mut arguments = arguments.into_iter();
+
+        for value in values.iter_mut().take(positional) {
+            *value = arguments.next();
+        }
+
+        let extra: Vec<Literal> = arguments.collect();
+        if positional < parameters.len() {
+            let rest = Literal::List(Rc::new(List::from(extra)));
+            self.allocate(rest.allocated_bytes(false), line)?;
+            values[positional] = Some(rest);
+        } else if !extra.is_empty() {
+            return Err(RuntimeError::new(
+                format!(
+                    "{}() expects at most {} arguments but got {}.",
+                    name, positional, count
+                ),
+                line,
+            ));
+        }
+
+        for (argument, value) in named {
+            let Some(index) = parameters.iter().position(|parameter| {
+                !parameter.rest && parameter.name.lexeme() == argument.lexeme()
+            }) else {
+                return Err(RuntimeError::new(
+                    format!("{}() has no parameter named '{}'.", name, argument.lexeme()),
+                    argument.get_line(),
+                ));
+            };
+
+            if values[index].is_some() {
+                return Err(RuntimeError::new(
+                    format!(
+                        "{}() got more than one value for parameter '{}'.",
+                        name,
+                        argument.lexeme()
+                    ),
+                    argument.get_line(),
+                ));
+            }
+
+            values[index] = Some(value);
+        }
+
+        if let Some(parameter) = parameters
+            .iter()
+            .zip(&values)
+            .find(|(parameter, value)| value.is_none() && parameter.default.is_none())
+            .map(|(parameter, _)| parameter)
+        {
+            return Err(RuntimeError::new(
+                format!(
+                    "{}() is missing an argument for parameter '{}'.",
+                    name,
+                    parameter.name.lexeme()
+                ),
+                line,
+            ));
+        }
+
+        let environment = Environment::new_with_enclosing(Some(function.closure.clone()));
+
+        if function.declaration.generator {
+            let (result, environment) =
+                self.with_frame(function, environment, line, |interpreter| {
+                    interpreter.bind_parameters(parameters, values)
+                });
+            result?;
+
+            return Ok(Literal::Generator(Rc::new(Generator::new(
+                function.clone(),
+                environment,
+            ))));
+        }
+
+        let (result, _) = self.with_frame(function, environment, line, |interpreter| {
+            interpreter
+                .bind_parameters(parameters, values)
+                .and_then(|()| interpreter.execute_all(&function.declaration.body))
+        });
+
+        match result {
+            Err(RuntimeError::Return { value, .. }) => Ok(value),
+            result => result.map(|()| Literal::Nil),
+        }
+    }
+
+    // Runs `body` in a new stack frame of `function`, called from `line`, with `environment` as
+    // the current scope. The scope `body` ends in is handed back, for generators to resume in.
+    pub fn with_frame<R>(
+        &mut self,
+        function: &Function,
+        environment: Environment,
+        line: u32,
+        body: impl FnOnce(&mut Self) -> Result<R, RuntimeError>,
+    ) -> (Result<R, RuntimeError>, Environment) {
+        // The frame of the script is always there, the others are calls.
+        if self.frames.len() > self.max_call_depth {
+            return (Err(RuntimeError::new("Stack overflow.", line)), environment);
+        }
+
+        if let Some(frame) = self.frames.last_mut() {
+            frame.line = line;
+        }
+        self.frames.push(StackFrame {
+            function: function.name().to_string(),
+            file: function.file.clone(),
+            line: 0,
+        });
+
+        // Nesting is counted from the start of every call.
+        let caller = std::mem::replace(&mut self.environment, environment);
+        let depth = std::mem::replace(&mut self.depth, 0);
+        let result = body(self);
+        self.depth = depth;
+        let environment = std::mem::replace(&mut self.environment, caller);
+
+        let frame = self
+            .frames
+            .pop()
+            .expect("The function frame is still on the stack");
+
+        (result.map_err(|error| error.unwind(&frame)), environment)
+    }
+
+    // Runs `body` for the first arm whose pattern matches `value` and whose guard holds, in a scope
+    // holding the names the pattern binds. It is an error for no arm to apply.
+    pub fn match_arms<T, R>(
+        &mut self,
+        value: &Literal,
+        arms: &[MatchArm<T>],
+        line: u32,
+        body: impl Fn(&mut Self, &T) -> Result<R, RuntimeError>,
+    ) -> Result<R, RuntimeError> {
+        let Some(index) = self.select_arm(value, arms)? else {
+            return Err(RuntimeError::new(
+                format!("No match arm for {}.", value),
+                line,
+            ));
+        };
+
+        let result = body(self, &arms[index].body);
+        self.environment.pop_scope();
+
+        result
+    }
+
+    // The index of the first arm that applies to `value`. Its bindings are left defined in a new
+    // scope, which the caller pops once the body has run.
+    pub fn select_arm<T>(
+        &mut self,
+        value: &Literal,
+        arms: &[MatchArm<T>],
+    ) -> Result<Option<usize>, RuntimeError> {
+        for (index, arm) in arms.iter().enumerate() {
+            let mut bindings = vec![];
+            if !arm.pattern.matches(value, &mut bindings) {
+                continue;
+            }
+
+            self.environment.push_scope();
+            for (name, value) in bindings {
+                self.environment.define(name, value);
+            }
+
+            let guard = match &arm.guard {
+                Some(guard) => guard
+                    .evaluate(self)
+                    .map(|condition| condition == Literal::Boolean(true)),
+                None => Ok(true),
+            };
+
+            match guard {
+                Ok(true) => return Ok(Some(index)),
+                Ok(false) => self.environment.pop_scope(),
+                Err(error) => {
+                    self.environment.pop_scope();
+                    return Err(error);
+                }
+            }
+        }
+
+        Ok(None)
+    }
+
+    // Pushes the names a destructuring pattern binds to `bindings`, with the matching parts of
+    // `value`. Unlike in a match arm, a value of the wrong shape is an error, and a missing part is
+    // only allowed when the pattern has a default for it.
+    pub fn destructure(
+        &mut self,
+        pattern: &Pattern,
+        value: &Literal,
+        bindings: &mut Vec<(Symbol, Literal)>,
+    ) -> Result<(), RuntimeError> {
+        match (pattern, value) {
+            (Pattern::Default { pattern, .. }, value) => self.destructure(pattern, value, bindings),
+            (
+                Pattern::List {
+                    bracket,
+                    elements,
+                    rest,
+                },
+                Literal::List(values),
+            ) => {
+                if rest.is_none() && values.len() > elements.len() {
+                    return Err(RuntimeError::new(
+                        format!(
+                            "Expected at most {} elements to destructure, got {}.",
+                            elements.len(),
+                            values.len()
+                        ),
+                        bracket.get_line(),
+                    ));
+                }
+
+                for (index, element) in elements.iter().enumerate() {
+                    match values.get(index) {
+                        Some(value) => self.destructure(element, value, bindings)?,
+                        None => self.destructure_missing(element, bindings, || {
+                            format!(
+                                "Expected at least {} elements to destructure, got {}.",
+                                index + 1,
+                                values.len()
+                            )
+                        })?,
+                    }
+                }
+
+                if let Some(rest) = rest.as_ref().filter(|rest| rest.lexeme() != "_") {
+                    let remaining: Vec<Literal> =
+                        values.iter().skip(elements.len()).cloned().collect();
+                    let remaining = Literal::List(Rc::new(List::from(remaining)));
+                    self.allocate(remaining.allocated_bytes(false), rest.get_line())?;
+                    bindings.push((rest.get_symbol(), remaining));
+                }
+
+                Ok(())
+            }
+            (Pattern::Map { entries, .. }, Literal::Map(values)) => {
+                for (key, pattern) in entries {
+                    let key = Expression::map_key(key);
+
+                    match values.get(&key) {
+                        Some(value) => self.destructure(pattern, value, bindings)?,
+                        None => self.destructure_missing(pattern, bindings, || {
+                            format!("Map has no key '{}' to destructure.", key)
+                        })?,
+                    }
+                }
+
+                Ok(())
+            }
+            (
+                Pattern::Variant {
+                    fields: Some(fields),
+                    ..
+                },
+                Literal::Variant(variant),
+            ) if pattern.names_variant(variant) && fields.len() == variant.values.len() => {
+                for (field, value) in fields.iter().zip(variant.values.iter()) {
+                    self.destructure(field, value, bindings)?;
+                }
+
+                Ok(())
+            }
+            (Pattern::List { bracket, .. }, _) => Err(RuntimeError::new(
+                format!("Can't destructure {} with a list pattern.", value),
+                bracket.get_line(),
+            )),
+            (Pattern::Map { brace, .. }, _) => Err(RuntimeError::new(
+                format!("Can't destructure {} with a map pattern.", value),
+                brace.get_line(),
+            )),
+            _ if pattern.matches(value, bindings) => Ok(()),
+            _ => Err(RuntimeError::new(
+                format!("{} doesn't match the destructuring pattern.", value),
+                pattern.line(),
+            )),
+        }
+    }
+
+    // A part of the pattern the destructured value lacks, which takes its default if it has one.
+    fn destructure_missing(
+        &mut self,
+        pattern: &Pattern,
+        bindings: &mut Vec<(Symbol, Literal)>,
+        message: impl FnOnce() -> String,
+    ) -> Result<(), RuntimeError> {
+        match pattern {
+            Pattern::Default {
+                pattern, default, ..
+            } => {
+                let value = default.evaluate(self)?;
+                self.destructure(pattern, &value, bindings)
+            }
+            _ => Err(RuntimeError::new(message(), pattern.line())),
+        }
+    }
+
+    // Defines the parameters in the current scope, in order, so that a default sees the
+    // parameters before it.
+    fn bind_parameters(
+        &mut self,
+        parameters: &[Parameter],
+        values: Vec<Option<Literal>>,
+    ) -> Result<(), RuntimeError> {
+        for (parameter, value) in parameters.iter().zip(values) {
+            let value = match (value, &parameter.default) {
+                (Some(value), _) => value,
+                (None, Some(default)) => default.evaluate(self)?,
+                // Missing arguments are reported before the call starts.
+                (None, None) => Literal::Nil,
+            };
+
+            self.environment.define(parameter.name.get_symbol(), value);
+        }
+
+        Ok(())
     }
 
-    fn execute(&mut self, statement: Statement) {
+    fn execute_statement(&mut self, statement: &Statement) -> Result<(), RuntimeError> {
         match statement {
-            Statement::Print { expression } => {
-                let value = expression.evaluate(&mut self.environment);
-                println!("{:#?}", value.to_string());
+            Statement::Comment { .. } | Statement::BlankLine { .. } => (),
+            Statement::Print { expression, .. } => {
+                let value = expression.evaluate(self)?;
+                writeln!(self.output, "{:#?}", value.to_string())
+                    .expect("Could not write the output");
             }
             Statement::Expression { expression } => {
-                expression.evaluate(&mut self.environment);
+                expression.evaluate(self)?;
             }
             Statement::Var { token, expression } => {
-                let value = expression.evaluate(&mut self.environment);
+                let value = expression.evaluate(self)?;
+
+                self.declare(token, value, false)?;
+            }
+            Statement::Const { token, expression } => {
+                let value = expression.evaluate(self)?;
 
-                self.environment.define(token.lexeme(), value);
+                self.declare(token, value, true)?;
             }
-            Statement::Block { statements } => {
-                let previous = self.environment.borrow_mut().clone();
+            Statement::Destructure {
+                keyword,
+                pattern,
+                expression,
+            } => {
+                let value = expression.evaluate(self)?;
+
+                let mut bindings = vec![];
+                self.destructure(pattern, &value, &mut bindings)?;
+
+                let constant = keyword.get_token_type() == &TokenType::Const;
+                for (name, value) in bindings {
+                    self.environment
+                        .declare(name, value, constant)
+                        .map_err(|message| RuntimeError::new(message, keyword.get_line()))?;
+                }
+            }
+            Statement::Block { statements, .. } => {
+                self.environment.push_scope();
+                let result = self.execute_all(statements);
+                self.environment.pop_scope();
+
+                return result;
+            }
+            Statement::For {
+                keyword,
+                key,
+                value,
+                iterable,
+                body,
+            } => {
+                let iterable = iterable.evaluate(self)?;
+                let mut iteration = Iteration::new(&iterable)
+                    .map_err(|message| RuntimeError::new(message, keyword.get_line()))?;
+
+                loop {
+                    let mut bindings = vec![];
+
+                    match key {
+                        Some(key) => match iteration.next_entry(self, keyword.get_line())? {
+                            Some((key_value, value_value)) => {
+                                self.destructure(key, &key_value, &mut bindings)?;
+                                self.destructure(value, &value_value, &mut bindings)?;
+                            }
+                            None => break,
+                        },
+                        None => match iteration.next_value(self, keyword.get_line())? {
+                            Some(value_value) => {
+                                self.destructure(value, &value_value, &mut bindings)?
+                            }
+                            None => break,
+                        },
+                    }
 
-                let current =
-                    Environment::new_with_enclosing(Some(Box::new(self.environment.clone())));
+                    // Every iteration gets its own scope, so closures created in the body keep
+                    // the values of that iteration.
+                    self.environment.push_scope();
+                    for (name, value) in bindings {
+                        self.environment.define(name, value);
+                    }
+                    let result = self.execute(body);
+                    self.environment.pop_scope();
 
-                self.environment = current;
-                self.interpret(statements);
-                self.environment = previous;
+                    result?;
+                }
             }
             Statement::If {
                 condition,
                 then_statement,
             } => {
-                let result = condition.evaluate(&mut self.environment);
+                let result = condition.evaluate(self)?;
 
                 if result == Literal::Boolean(true) {
-                    self.execute(*then_statement);
+                    return self.execute(then_statement);
                 }
             }
             Statement::IfElse {
@@ -81,15 +1065,213 @@ impl Interpreter {
                 then_statement,
                 else_stament,
             } => {
-                let result = condition.evaluate(&mut self.environment);
+                let result = condition.evaluate(self)?;
 
-                match result {
-                    Literal::Boolean(true) => self.execute(*then_statement),
-                    _ => {
-                        self.execute(*else_stament);
-                    }
+                return match result {
+                    Literal::Boolean(true) => self.execute(then_statement),
+                    _ => self.execute(else_stament),
+                };
+            }
+            Statement::Throw {
+                keyword,
+                expression,
+            } => {
+                let value = expression.evaluate(self)?;
+
+                return Err(RuntimeError::throw(value, keyword.get_line()));
+            }
+            Statement::Try {
+                try_statement,
+                catch_clause,
+                finally_statement,
+            } => {
+                let mut result = self.execute(try_statement);
+
+                if let (Err(RuntimeError::Exception { value, .. }), Some((name, catch_statement))) =
+                    (&result, catch_clause)
+                {
+                    let value = value.clone();
+
+                    self.environment.push_scope();
+                    self.environment.define(name.get_symbol(), value);
+                    result = self.execute(catch_statement);
+                    self.environment.pop_scope();
                 }
+
+                // `finally` always runs, even on the way out of a `return`; an error raised inside
+                // it replaces the pending one.
+                if let Some(finally_statement) = finally_statement {
+                    self.execute(finally_statement)?;
+                }
+
+                return result;
+            }
+            Statement::Import {
+                keyword,
+                path,
+                name,
+            } => {
+                let module = self.import(path, keyword.get_line())?;
+
+                self.declare(name, module, false)?;
+            }
+            Statement::Function { declaration } => {
+                let name = declaration
+                    .name
+                    .as_ref()
+                    .expect("A function statement is always named");
+                let function = self.closure(declaration);
+
+                self.declare(name, function, false)?;
+            }
+            Statement::Match {
+                keyword,
+                subject,
+                arms,
+                ..
+            } => {
+                let value = subject.evaluate(self)?;
+
+                self.match_arms(&value, arms, keyword.get_line(), |interpreter, body| {
+                    interpreter.execute(body)
+                })?;
+            }
+            Statement::Enum { name, variants, .. } => {
+                let enumeration = Enum {
+                    name: name.lexeme().to_string(),
+                    variants: variants
+                        .iter()
+                        .map(|(variant, fields)| {
+                            let fields = fields.iter().map(|f| f.lexeme().to_string()).collect();
+
+                            (variant.lexeme().to_string(), fields)
+                        })
+                        .collect(),
+                };
+
+                self.declare(name, Literal::Enum(Rc::new(enumeration)), false)?;
+            }
+            Statement::Return { keyword, value } => {
+                let value = match value {
+                    Some(value) => value.evaluate(self)?,
+                    None => Literal::Nil,
+                };
+
+                return Err(RuntimeError::Return {
+                    value,
+                    line: keyword.get_line(),
+                });
+            }
+            // The parser only accepts `yield` inside functions, which makes them generators, and
+            // generators run their `yield` statements themselves.
+            Statement::Yield { keyword, .. } => {
+                return Err(RuntimeError::new(
+                    "Can't yield outside of a generator.".to_string(),
+                    keyword.get_line(),
+                ));
             }
         }
+
+        Ok(())
+    }
+
+    // Paths are relative to the file doing the import. A module runs once, in a global scope of
+    // its own, and every later import of the same file gets the cached result. Modules that are
+    // still running are exactly the files on the frame stack, which is how cycles are found.
+    fn import(&mut self, path: &Token, line: u32) -> Result<Literal, RuntimeError> {
+        let relative = path.get_literal().to_string();
+
+        // Built-in modules are cached under their bare name, which can never collide with the
+        // canonical (absolute) path of a file.
+        if let Some(module) = self.modules.get(Path::new(&relative)) {
+            return Ok(module.clone());
+        }
+
+        if let Some(module) = stdlib::module(&relative) {
+            let module = Literal::Module(Rc::new(module));
+            self.modules
+                .insert(PathBuf::from(&relative), module.clone());
+
+            return Ok(module);
+        }
+
+        let resolved = match self.frames.last().and_then(|frame| frame.file.as_ref()) {
+            Some(file) => Path::new(file)
+                .parent()
+                .unwrap_or(Path::new(""))
+                .join(&relative),
+            None => PathBuf::from(&relative),
+        };
+        let display = resolved.display().to_string();
+
+        let canonical = fs::canonicalize(&resolved).map_err(|error| {
+            RuntimeError::new(format!("Could not import '{}': {}.", display, error), line)
+        })?;
+
+        if let Some(module) = self.modules.get(&canonical) {
+            return Ok(module.clone());
+        }
+
+        let chain: Vec<&String> = self
+            .frames
+            .iter()
+            .filter_map(|frame| frame.file.as_ref())
+            .collect();
+
+        if let Some(start) = chain
+            .iter()
+            .position(|file| fs::canonicalize(file).is_ok_and(|file| file == canonical))
+        {
+            let cycle: Vec<&str> = chain[start..].iter().map(|file| file.as_str()).collect();
+
+            return Err(RuntimeError::new(
+                format!("Import cycle: {} -> {}.", cycle.join(" -> "), display),
+                line,
+            ));
+        }
+
+        let source = fs::read_to_string(&canonical).map_err(|error| {
+            RuntimeError::new(format!("Could not import '{}': {}.", display, error), line)
+        })?;
+
+        let mut scanner = Scanner::new(&source);
+        let mut statements = Parser::new(scanner.scan_tokens().to_vec()).parse();
+
+        // Checked like the script itself: errors panic, warnings name the module they are in.
+        for warning in resolver::resolve(&statements) {
+            eprintln!("{} ({})", warning, display);
+        }
+
+        if self.optimize {
+            statements = optimizer::optimize(statements);
+        }
+
+        if let Some(frame) = self.frames.last_mut() {
+            frame.line = line;
+        }
+        self.frames.push(StackFrame {
+            function: "module".to_string(),
+            file: Some(display.clone()),
+            line: 0,
+        });
+
+        let globals = std::mem::take(&mut self.environment);
+        let result = self.execute_all(&statements);
+        let module_environment = std::mem::replace(&mut self.environment, globals);
+
+        let frame = self
+            .frames
+            .pop()
+            .expect("The module frame is still on the stack");
+        result.map_err(|error| error.unwind(&frame))?;
+
+        let module = Literal::Module(Rc::new(Module {
+            path: display,
+            environment: module_environment,
+        }));
+
+        self.modules.insert(canonical, module.clone());
+
+        Ok(module)
     }
 }
@@ -0,0 +1,177 @@
+use std::rc::Rc;
+
+use crate::{
+    error::RuntimeError,
+    generator::Generator,
+    heap::{Object, Tracer},
+    stdlib::Lines,
+    Interpreter, List, Literal, Map,
+};
+
+// Where a `for` loop is in the value it walks. Every step has a key and a value: the position and
+// the element for lists, strings, ranges and generators, the key and its value for maps. Lists and
+// maps are walked as they were when the loop started.
+pub enum Iteration {
+    List {
+        values: Rc<List>,
+        index: usize,
+    },
+    Map {
+        entries: Rc<Map>,
+        index: usize,
+    },
+    Characters {
+        characters: Vec<char>,
+        index: usize,
+    },
+    Range {
+        next: Option<i64>,
+        end: i64,
+        step: i64,
+        index: i64,
+    },
+    // Resumed once per step, until its body ends.
+    Generator {
+        generator: Rc<Generator>,
+        index: i64,
+    },
+    // Read once per step, until the end of the file.
+    Lines {
+        lines: Rc<Lines>,
+        index: i64,
+    },
+}
+
+impl Iteration {
+    pub fn new(value: &Literal) -> Result<Self, String> {
+        match value {
+            Literal::List(values) => Ok(Iteration::List {
+                values: Rc::clone(values),
+                index: 0,
+            }),
+            Literal::Map(entries) => Ok(Iteration::Map {
+                entries: Rc::clone(entries),
+                index: 0,
+            }),
+            Literal::Text(text) => Ok(Iteration::Characters {
+                characters: text.chars().collect(),
+                index: 0,
+            }),
+            Literal::Range { start, end, step } => Ok(Iteration::Range {
+                next: Some(*start),
+                end: *end,
+                step: *step,
+                index: 0,
+            }),
+            Literal::Generator(generator) => Ok(Iteration::Generator {
+                generator: Rc::clone(generator),
+                index: 0,
+            }),
+            Literal::Lines(lines) => Ok(Iteration::Lines {
+                lines: Rc::clone(lines),
+                index: 0,
+            }),
+            _ => Err(format!("Can't iterate over {}.", value)),
+        }
+    }
+
+    // The next value of a loop with one variable, which is the key for maps. `line` is the line of
+    // the loop, for the frame of a generator it resumes.
+    pub fn next_value(
+        &mut self,
+        interpreter: &mut Interpreter,
+        line: u32,
+    ) -> Result<Option<Literal>, RuntimeError> {
+        let is_map = matches!(self, Iteration::Map { .. });
+
+        Ok(self
+            .next_entry(interpreter, line)?
+            .map(|(key, value)| if is_map { key } else { value }))
+    }
+
+    pub fn next_entry(
+        &mut self,
+        interpreter: &mut Interpreter,
+        line: u32,
+    ) -> Result<Option<(Literal, Literal)>, RuntimeError> {
+        if let Iteration::Generator { generator, index } = self {
+            let Some(value) = interpreter.resume(generator, line)? else {
+                return Ok(None);
+            };
+            *index += 1;
+
+            return Ok(Some((Literal::Int(*index - 1), value)));
+        }
+
+        if let Iteration::Lines { lines, index } = self {
+            let Some(value) = lines
+                .next_line()
+                .map_err(|message| RuntimeError::new(message, line))?
+            else {
+                return Ok(None);
+            };
+            *index += 1;
+
+            return Ok(Some((Literal::Int(*index - 1), Literal::Text(value))));
+        }
+
+        Ok(self.next_element())
+    }
+
+    fn next_element(&mut self) -> Option<(Literal, Literal)> {
+        match self {
+            Iteration::List { values, index } => {
+                let value = values.get(*index)?.clone();
+                *index += 1;
+
+                Some((Literal::Int(*index as i64 - 1), value))
+            }
+            Iteration::Map { entries, index } => {
+                let (key, value) = entries.entry(*index)?.clone();
+                *index += 1;
+
+                Some((Literal::Text(key), value))
+            }
+            Iteration::Characters { characters, index } => {
+                let character = *characters.get(*index)?;
+                *index += 1;
+
+                Some((
+                    Literal::Int(*index as i64 - 1),
+                    Literal::Text(character.to_string()),
+                ))
+            }
+            Iteration::Range {
+                next,
+                end,
+                step,
+                index,
+            } => {
+                let value = next.filter(|value| match *step > 0 {
+                    true => value < end,
+                    false => value > end,
+                })?;
+
+                // A step past the largest integer ends the range instead of overflowing.
+                *next = value.checked_add(*step);
+                *index += 1;
+
+                Some((Literal::Int(*index - 1), Literal::Int(value)))
+            }
+            Iteration::Generator { .. } | Iteration::Lines { .. } => {
+                unreachable!("Generators and files are read by next_entry")
+            }
+        }
+    }
+
+    pub(crate) fn trace(&self, tracer: &mut Tracer) {
+        match self {
+            Iteration::List { values, .. } => tracer.reference(Object::List(Rc::clone(values))),
+            Iteration::Map { entries, .. } => tracer.reference(Object::Map(Rc::clone(entries))),
+            Iteration::Generator { generator, .. } => {
+                tracer.reference(Object::Generator(Rc::clone(generator)))
+            }
+            Iteration::Characters { .. } | Iteration::Range { .. } | Iteration::Lines { .. } => (),
+        }
+    }
+}
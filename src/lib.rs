@@ -0,0 +1,27 @@
+// The interpreter as a library, for embedding it in another program. The `rlox` binary is built on
+// the same API: scan and parse a source into statements, then hand them to an `Interpreter`,
+// configured with limits, an output and an input, and stopped from another thread through its
+// `CancelHandle`.
+pub mod scanner;
+pub use crate::scanner::*;
+pub mod parser;
+pub use crate::parser::*;
+pub mod interpreter;
+pub mod iteration;
+pub use crate::interpreter::*;
+pub mod environment;
+pub use crate::environment::*;
+pub mod dump;
+pub mod error;
+pub mod formatter;
+pub mod generator;
+pub mod heap;
+pub mod interner;
+pub mod list;
+pub use crate::list::List;
+pub mod map;
+pub use crate::map::Map;
+pub mod optimizer;
+pub mod pattern;
+pub mod resolver;
+pub mod stdlib;
@@ -0,0 +1,86 @@
+use std::ops::Deref;
+use std::rc::Rc;
+
+use crate::{Environment, Literal};
+
+// The elements of a list value. Lists are never changed once built, so this only wraps the `Vec`
+// to control how it is dropped.
+#[derive(PartialEq, Debug)]
+pub struct List(Vec<Literal>);
+
+impl From<Vec<Literal>> for List {
+    fn from(elements: Vec<Literal>) -> Self {
+        Self(elements)
+    }
+}
+
+impl Deref for List {
+    type Target = Vec<Literal>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl Drop for List {
+    fn drop(&mut self) {
+        drop_nested(std::mem::take(&mut self.0));
+    }
+}
+
+// Dropping a list that holds the last reference to another list, which holds the last reference
+// to another, and so on, would recurse once per level and overflow the native stack on values
+// built in a loop like `xs = [xs, i];`. The elements of such nested lists, maps and variants are
+// moved out onto a work list instead, so that every level is dropped from this one loop.
+pub fn drop_nested(pending: Vec<Literal>) {
+    drop_scopes(pending, vec![]);
+}
+
+// Closures chain the same way through the scopes they hold, `f = fun () { return g; }` with `g`
+// the previous `f`, so the scopes of functions and generators that go away with these values, and
+// the values in those scopes, go on work lists too.
+pub(crate) fn drop_scopes(mut pending: Vec<Literal>, mut environments: Vec<Environment>) {
+    loop {
+        if let Some(environment) = environments.pop() {
+            if let Some((mut values, enclosing)) = environment.take_unshared() {
+                pending.append(&mut values);
+                environments.extend(enclosing);
+            }
+
+            continue;
+        }
+
+        let Some(value) = pending.pop() else {
+            break;
+        };
+
+        match value {
+            Literal::List(list) => {
+                if let Ok(mut list) = Rc::try_unwrap(list) {
+                    pending.append(&mut list.0);
+                }
+            }
+            Literal::Map(map) => {
+                if let Ok(mut map) = Rc::try_unwrap(map) {
+                    pending.extend(map.take_values());
+                }
+            }
+            Literal::Variant(variant) => {
+                if let Ok(mut variant) = Rc::try_unwrap(variant) {
+                    pending.append(&mut variant.values);
+                }
+            }
+            Literal::Function(function) => {
+                if let Ok(function) = Rc::try_unwrap(function) {
+                    environments.push(function.closure);
+                }
+            }
+            Literal::Generator(generator) | Literal::GeneratorNext(generator) => {
+                if let Ok(generator) = Rc::try_unwrap(generator) {
+                    environments.extend(generator.into_environments());
+                }
+            }
+            _ => (),
+        }
+    }
+}
@@ -1,54 +1,206 @@
-mod scanner;
-use crate::scanner::*;
-mod parser;
-use crate::parser::*;
-mod interpreter;
-use crate::interpreter::*;
-mod environment;
-use crate::environment::*;
+use lox_in_rust::{
+    dump, error, formatter, heap, optimizer, resolver, run_on_stack, Environment, Interpreter,
+    Limits, Parser, Scanner, Statement, DEFAULT_MAX_CALL_DEPTH, DEFAULT_MAX_DEPTH,
+};
 
 use std::env;
 use std::fs;
 use std::io;
 use std::process;
+use std::time::{Duration, Instant};
+
+pub struct Options {
+    optimize: bool,
+    dump_tokens: bool,
+    dump_ast: Option<dump::Format>,
+    max_depth: usize,
+    max_call_depth: usize,
+    max_steps: Option<u64>,
+    timeout: Option<Duration>,
+    max_allocated_bytes: Option<usize>,
+    gc_stress: bool,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            optimize: true,
+            dump_tokens: false,
+            dump_ast: None,
+            max_depth: DEFAULT_MAX_DEPTH,
+            max_call_depth: DEFAULT_MAX_CALL_DEPTH,
+            max_steps: None,
+            timeout: None,
+            max_allocated_bytes: None,
+            gc_stress: false,
+        }
+    }
+}
 
 fn main() {
-    const TOTAL_ARGS_EXPECTED: usize = 2;
+    let args: Vec<String> = env::args().skip(1).collect();
+
+    if args.first().is_some_and(|command| command == "fmt") {
+        run_on_stack(0, 0, || run_fmt(&args[1..]));
+        return;
+    }
 
-    let args: Vec<String> = env::args().collect();
+    let mut options = Options::default();
+    let mut script: Option<String> = None;
 
-    match args.len().cmp(&TOTAL_ARGS_EXPECTED) {
-        std::cmp::Ordering::Greater => {
-            println!("Usage: rlox [script]!");
-            process::exit(64);
+    for arg in args {
+        match arg.as_str() {
+            "-O0" => options.optimize = false,
+            "--dump-tokens" => options.dump_tokens = true,
+            "--gc-stress" => options.gc_stress = true,
+            _ if arg.starts_with("--dump-ast=") => match dump::Format::from_name(&arg[11..]) {
+                Some(format) => options.dump_ast = Some(format),
+                None => usage(),
+            },
+            _ if arg.starts_with("--max-depth=") => match arg[12..].parse() {
+                Ok(max_depth) => options.max_depth = max_depth,
+                Err(_) => usage(),
+            },
+            _ if arg.starts_with("--max-call-depth=") => match arg[17..].parse() {
+                Ok(max_call_depth) => options.max_call_depth = max_call_depth,
+                Err(_) => usage(),
+            },
+            _ if arg.starts_with("--max-steps=") => match arg[12..].parse() {
+                Ok(max_steps) => options.max_steps = Some(max_steps),
+                Err(_) => usage(),
+            },
+            _ if arg.starts_with("--timeout-ms=") => match arg[13..].parse() {
+                Ok(timeout) => options.timeout = Some(Duration::from_millis(timeout)),
+                Err(_) => usage(),
+            },
+            _ if arg.starts_with("--max-memory=") => match arg[13..].parse() {
+                Ok(max_bytes) => options.max_allocated_bytes = Some(max_bytes),
+                Err(_) => usage(),
+            },
+            _ if arg.starts_with('-') || script.is_some() => usage(),
+            _ => script = Some(arg),
         }
-        std::cmp::Ordering::Equal => {
-            run_file(&args[1]);
+    }
+
+    run_on_stack(options.max_depth, options.max_call_depth, || match script {
+        Some(path) => run_file(&path, &options),
+        None => run_prompt(&options),
+    });
+}
+
+fn usage() -> ! {
+    println!(
+        "Usage: rlox [-O0] [--max-depth=N] [--max-call-depth=N] [--max-steps=N] [--timeout-ms=N]"
+    );
+    println!("            [--max-memory=BYTES] [--gc-stress] [--dump-tokens]");
+    println!("            [--dump-ast=json|sexpr|dot] [script]");
+    println!("       rlox fmt [--check] <script>...");
+    process::exit(64);
+}
+
+// Rewrites every given script in the canonical layout. With `--check` nothing is written: the
+// scripts that are not formatted are listed and the process exits with 1, for use in CI.
+fn run_fmt(args: &[String]) {
+    let check = args.iter().any(|arg| arg == "--check");
+    let paths: Vec<&String> = args.iter().filter(|arg| *arg != "--check").collect();
+
+    if paths.is_empty() || paths.iter().any(|path| path.starts_with('-')) {
+        usage();
+    }
+
+    let mut unformatted = false;
+
+    for path in paths {
+        let source = fs::read_to_string(path).expect("Could not read the file");
+        let formatted = formatter::format(&source);
+
+        if formatted == source {
+            continue;
         }
-        std::cmp::Ordering::Less => {
-            run_prompt();
+
+        if check {
+            println!("{} is not formatted", path);
+            unformatted = true;
+        } else {
+            fs::write(path, formatted).expect("Could not write the file");
         }
     }
+
+    if unformatted {
+        process::exit(1);
+    }
 }
 
-fn run_file(s: &str) {
+fn run_file(s: &str, options: &Options) {
     let file_content = fs::read_to_string(s).expect("Could not read the file");
 
-    run(&file_content);
+    if options.dump_tokens || options.dump_ast.is_some() {
+        dump(&file_content, options);
+        return;
+    }
+
+    let mut interpreter = Interpreter::new(Environment::new());
+    configure_interpreter(&mut interpreter, options);
+    interpreter.set_script_path(s);
+
+    if let Err(error) = interpreter.interpret(parse(&file_content, options)) {
+        eprintln!("{}", error);
+        process::exit(70);
+    }
+}
+
+// Prints the requested views of the front end instead of executing the script.
+fn dump(s: &str, options: &Options) {
+    let mut scanner = Scanner::new(s);
+    let tokens = scanner.scan_tokens();
+
+    if options.dump_tokens {
+        print!("{}", dump::dump_tokens(tokens));
+    }
+
+    if let Some(format) = &options.dump_ast {
+        let mut parser = Parser::new(tokens.to_vec());
+        print!("{}", dump::dump_ast(&parser.parse(), format));
+    }
+}
+
+fn configure_interpreter(interpreter: &mut Interpreter, options: &Options) {
+    interpreter.set_optimize(options.optimize);
+    interpreter.set_max_depth(options.max_depth);
+    interpreter.set_max_call_depth(options.max_call_depth);
+    heap::set_stress(options.gc_stress);
+    interpreter.set_limits(Limits {
+        max_steps: options.max_steps,
+        deadline: options.timeout.map(|timeout| Instant::now() + timeout),
+        max_allocated_bytes: options.max_allocated_bytes,
+    });
 }
 
-fn run(s: &str) {
+fn run(s: &str, options: &Options) -> Result<(), error::RuntimeError> {
+    let mut interpreter = Interpreter::new(Environment::new());
+    configure_interpreter(&mut interpreter, options);
+    interpreter.interpret(parse(s, options))
+}
+
+fn parse(s: &str, options: &Options) -> Vec<Statement> {
     let mut scanner = Scanner::new(s);
     let tokens = scanner.scan_tokens();
 
     let mut parser = Parser::new(tokens.to_vec());
     let statements = parser.parse();
 
-    let mut interpreter = Interpreter::new(Environment::new());
-    interpreter.interpret(statements);
+    for warning in resolver::resolve(&statements) {
+        eprintln!("{}", warning);
+    }
+
+    if options.optimize {
+        return optimizer::optimize(statements);
+    }
+
+    statements
 }
 
-fn run_prompt() {
+fn run_prompt(options: &Options) {
     loop {
         println!("> ");
 
@@ -64,7 +216,9 @@ fn run_prompt() {
             break;
         }
 
-        run(line);
+        if let Err(error) = run(line, options) {
+            eprintln!("{}", error);
+        }
     }
 }
 
@@ -72,9 +226,58 @@ fn run_prompt() {
 mod tests {
     use super::*;
 
+    use lox_in_rust::{interner, Expression, Literal, Token, TokenType};
+
+    use std::cell::RefCell;
+    use std::io::Write;
+    use std::rc::Rc;
+
+    struct SharedOutput(Rc<RefCell<Vec<u8>>>);
+
+    impl Write for SharedOutput {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.borrow_mut().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn run_to_string(source: &str, options: &Options) -> String {
+        let (output, result) = try_run_to_string(source, options);
+        result.unwrap();
+
+        output
+    }
+
+    fn try_run_to_string(
+        source: &str,
+        options: &Options,
+    ) -> (String, Result<(), error::RuntimeError>) {
+        let output = Rc::new(RefCell::new(Vec::new()));
+        let mut interpreter = Interpreter::with_output(
+            Environment::new(),
+            Box::new(SharedOutput(Rc::clone(&output))),
+        );
+        configure_interpreter(&mut interpreter, options);
+
+        let result = interpreter.interpret(parse(source, options));
+
+        let output = output.borrow();
+        (String::from_utf8(output.clone()).unwrap(), result)
+    }
+
+    fn parse_and_optimize(source: &str) -> Expression {
+        let mut scanner = Scanner::new(source);
+        let mut parser = Parser::new(scanner.scan_tokens().to_vec());
+
+        optimizer::optimize_expression(parser.parse_expression())
+    }
+
     #[test]
     fn it_parses_add() {
-        use crate::scanner::{Literal, Token, TokenType};
+        use lox_in_rust::scanner::{Literal, Token, TokenType};
 
         let tokens = vec![
             Token::new(TokenType::Number, "2".to_string(), Literal::Number(2.0), 1),
@@ -91,7 +294,7 @@ mod tests {
 
     #[test]
     fn it_parses_with_precedence() {
-        use crate::scanner::{Literal, Token, TokenType};
+        use lox_in_rust::scanner::{Literal, Token, TokenType};
 
         let tokens = vec![
             Token::new(TokenType::Number, "1".to_string(), Literal::Number(1.0), 1),
@@ -110,7 +313,7 @@ mod tests {
 
     #[test]
     fn it_parses_with_grouping() {
-        use crate::scanner::{Literal, Token, TokenType};
+        use lox_in_rust::scanner::{Literal, Token, TokenType};
 
         let tokens = vec![
             Token::new(TokenType::LeftParen, "(".to_string(), Literal::Nil, 1),
@@ -132,7 +335,7 @@ mod tests {
     #[test]
     #[should_panic(expected = "Expect ')' after expression.")]
     fn it_should_panic_missing_paren() {
-        use crate::scanner::{Literal, Token, TokenType};
+        use lox_in_rust::scanner::{Literal, Token, TokenType};
 
         let tokens = vec![
             Token::new(TokenType::LeftParen, "(".to_string(), Literal::Nil, 1),
@@ -149,19 +352,28 @@ mod tests {
     }
 
     #[test]
-    #[should_panic]
+    #[should_panic(expected = "Unrecognized token at line 1")]
     fn it_should_panic_accentuation() {
-        let mut scanner = Scanner::new("print \"é\"");
+        let mut scanner = Scanner::new("var é = 1;");
         scanner.scan_tokens();
     }
 
+    #[test]
+    fn it_should_tokenize_accented_strings() {
+        let mut scanner = Scanner::new("print \"é\" + \"ü\";");
+        let tokens = scanner.scan_tokens().to_vec();
+
+        assert_eq!(tokens[1].get_literal(), &Literal::Text("é".to_string()));
+        assert_eq!(tokens[3].get_literal(), &Literal::Text("ü".to_string()));
+    }
+
     #[test]
     fn it_should_tokenize() {
         let mut scanner = Scanner::new("print \"e\"");
         let tokens = scanner.scan_tokens().to_vec();
 
-        assert_eq!(tokens[0].type_equals_to(&TokenType::Print), true);
-        assert_eq!(tokens[1].type_equals_to(&TokenType::String), true);
+        assert!(tokens[0].type_equals_to(&TokenType::Print));
+        assert!(tokens[1].type_equals_to(&TokenType::String));
     }
 
     #[test]
@@ -174,9 +386,12 @@ mod tests {
         ];
 
         let mut parser = Parser::new(tokens);
-        let mut environment = Environment::new();
+        let mut interpreter = Interpreter::new(Environment::new());
 
-        let literal = parser.parse_expression().evaluate(&mut environment);
+        let literal = parser
+            .parse_expression()
+            .evaluate(&mut interpreter)
+            .unwrap();
 
         assert_eq!(literal.to_string(), "2");
     }
@@ -187,18 +402,1649 @@ mod tests {
         current_environment.define("i", Literal::Number(1.0));
         current_environment.define("j", Literal::Number(2.0));
 
-        let mut inner = Environment::new_with_enclosing(Some(Box::from(current_environment)));
+        let mut inner = Environment::new_with_enclosing(Some(current_environment));
         inner.define("i", Literal::Number(2.0));
 
-        let mut inner_most = Environment::new_with_enclosing(Some(Box::from(inner)));
+        let mut inner_most = Environment::new_with_enclosing(Some(inner));
         inner_most.define("k", Literal::Text("John".to_string()));
 
         let variable_i = inner_most.get("i");
         let variable_j = inner_most.get("j");
         let variable_k = inner_most.get("k");
 
-        assert_eq!(variable_i.unwrap(), &Literal::Number(2.0));
-        assert_eq!(variable_j.unwrap(), &Literal::Number(2.0));
-        assert_eq!(variable_k.unwrap(), &Literal::Text("John".to_string()));
+        assert_eq!(variable_i.unwrap(), Literal::Number(2.0));
+        assert_eq!(variable_j.unwrap(), Literal::Number(2.0));
+        assert_eq!(variable_k.unwrap(), Literal::Text("John".to_string()));
+    }
+
+    #[test]
+    fn it_interns_identifiers() {
+        let mut scanner = Scanner::new("var count = count;");
+        let tokens = scanner.scan_tokens().to_vec();
+
+        assert_eq!(tokens[1].get_symbol(), tokens[3].get_symbol());
+        assert_eq!(tokens[1].get_symbol(), interner::intern("count"));
+        assert_eq!(tokens[1].get_symbol().to_string(), "count");
+        assert_ne!(tokens[1].get_symbol(), interner::intern("counter"));
+    }
+
+    #[test]
+    fn it_folds_constants() {
+        assert_eq!(parse_and_optimize("2 * 60 * 60").to_custom_string(), "7200");
+        assert_eq!(parse_and_optimize("\"a\" + \"b\"").to_custom_string(), "ab");
+        assert_eq!(
+            parse_and_optimize("-(1 + 2) < 0").to_custom_string(),
+            "true"
+        );
+        assert_eq!(
+            parse_and_optimize("false and x").to_custom_string(),
+            "false"
+        );
+    }
+
+    #[test]
+    fn it_keeps_failing_operations_unfolded() {
+        assert_eq!(parse_and_optimize("1 / 0").to_custom_string(), "(/ 1 0)");
+        assert_eq!(
+            parse_and_optimize("\"a\" + 1").to_custom_string(),
+            "(+ a 1)"
+        );
+    }
+
+    #[test]
+    fn it_simplifies_identities() {
+        assert_eq!(
+            parse_and_optimize("(a - b) * 1").to_custom_string(),
+            "(group (- (var a) (var b)))"
+        );
+        assert_eq!(
+            parse_and_optimize("a * 1").to_custom_string(),
+            "(* (var a) 1)"
+        );
+    }
+
+    #[test]
+    fn it_prints_the_same_with_and_without_optimizer() {
+        let source = "var a = 2 * 60 * 60;
+            if (false) { print 1 / 0; }
+            if (1 < 2) print a; else print \"no\";
+            var b = (a - 1) * 1;
+            print b;
+            print \"x\" + \"y\";";
+
+        let optimized = run_to_string(source, &Options::default());
+        let unoptimized = run_to_string(
+            source,
+            &Options {
+                optimize: false,
+                ..Options::default()
+            },
+        );
+
+        assert_eq!(optimized, "\"7200\"\n\"7199\"\n\"xy\"\n");
+        assert_eq!(optimized, unoptimized);
+    }
+
+    #[test]
+    fn it_formats_source() {
+        let source = "// totals\nvar a=1+2;   var b;\nif(a>2){print a;// big\n}else if (a < 0) print -a; else {}\n{ a = a*(2-1); }";
+
+        let expected = "// totals
+var a = 1 + 2;
+var b;
+if (a > 2) {
+    print a; // big
+} else if (a < 0)
+    print -a;
+else {}
+{
+    a = a * (2 - 1);
+}
+";
+
+        assert_eq!(formatter::format(source), expected);
+        assert_eq!(formatter::format(expected), expected);
+    }
+
+    #[test]
+    fn it_keeps_comments_inside_statements() {
+        let source = "var a = 1 + // one\n 2;\nprint a;";
+
+        assert_eq!(
+            formatter::format(source),
+            "var a = 1 + 2;\n// one\nprint a;\n"
+        );
+    }
+
+    #[test]
+    fn it_keeps_comments_in_their_scope() {
+        let source = "if (a) {\n    print 1;\n} // after then\nelse {\n    print 2;\n}\n\
+                      fun f(x, // the x\n y) {\n    return x;\n}\n";
+        let expected = "if (a) {\n    print 1;\n} else {\n    print 2;\n}\n// after then\n\
+                        fun f(x, y) {\n    return x;\n}\n// the x\n";
+
+        assert_eq!(formatter::format(source), expected);
+        assert_eq!(formatter::format(expected), expected);
+    }
+
+    #[test]
+    fn it_keeps_single_blank_lines_between_statements() {
+        let source = "var a = 1;\n\n\n// section two\nfun f() {\n\n    print a;\n\n\n    print [1,\n\n 2];\n\n}\n\n";
+        let expected =
+            "var a = 1;\n\n// section two\nfun f() {\n    print a;\n\n    print [1, 2];\n}\n";
+
+        assert_eq!(formatter::format(source), expected);
+        assert_eq!(formatter::format(expected), expected);
+    }
+
+    #[test]
+    fn it_wraps_long_lines() {
+        let operand = "a".repeat(30);
+        let source = format!("print {0} + {0} + {0} + {0};", operand);
+
+        assert_eq!(
+            formatter::format(&source),
+            format!("print {0}\n    + {0}\n    + {0}\n    + {0};\n", operand)
+        );
+    }
+
+    #[test]
+    fn it_dumps_tokens() {
+        let mut scanner = Scanner::new("print \"hi\";");
+
+        assert_eq!(
+            dump::dump_tokens(scanner.scan_tokens()),
+            r#"[
+  {"node": "Token", "type": "Print", "lexeme": "print", "literal": null, "line": 1},
+  {"node": "Token", "type": "String", "lexeme": "\"hi\"", "literal": "hi", "line": 1},
+  {"node": "Token", "type": "Semicolon", "lexeme": ";", "literal": null, "line": 1},
+  {"node": "Token", "type": "Eof", "lexeme": "", "literal": null, "line": 1}
+]
+"#
+        );
+    }
+
+    #[test]
+    fn it_dumps_floats_apart_from_integers() {
+        let mut scanner = Scanner::new("print 2.0 ** 2;");
+        let mut parser = Parser::new(scanner.scan_tokens().to_vec());
+
+        assert_eq!(
+            dump::dump_ast(&parser.parse(), &dump::Format::Sexpr),
+            "(Program :statements ((Print :line 1 :expression (Binary :operator \"**\" :line 1 \
+             :left (Literal :value 2.0 :line 1) :right (Literal :value 2 :line 1)))))\n"
+        );
+    }
+
+    #[test]
+    fn it_dumps_the_ast() {
+        let statements = parse(
+            "var a = -1;\nif (a < 2) print a;\n{\n  print nil;\n}",
+            &Options {
+                optimize: false,
+                ..Options::default()
+            },
+        );
+
+        assert_eq!(
+            dump::dump_ast(&statements, &dump::Format::Sexpr),
+            "(Program :statements ((Var :name \"a\" :line 1 :initializer (Unary :operator \"-\" :line 1 :right (Literal :value 1 :line 1))) \
+             (If :condition (Binary :operator \"<\" :line 2 :left (Variable :name \"a\" :line 2) :right (Literal :value 2 :line 2)) \
+             :then (Print :line 2 :expression (Variable :name \"a\" :line 2))) \
+             (Block :line 3 :statements ((Print :line 4 :expression (Literal :value nil :line 4))))))\n"
+        );
+
+        let json = dump::dump_ast(&statements, &dump::Format::Json);
+        assert!(
+            json.starts_with(r#"{"node": "Program", "statements": [{"node": "Var", "name": "a""#)
+        );
+
+        let dot = dump::dump_ast(&statements, &dump::Format::Dot);
+        assert!(dot.starts_with("digraph ast {"));
+        assert!(dot.contains("n0 -> n1 [label=\"statements[0]\"];"));
+    }
+
+    #[test]
+    fn it_catches_runtime_errors() {
+        let source = "try {
+                print 1 / 0;
+            } catch (e) {
+                print e.message;
+                print e.line;
+            } finally {
+                print \"done\";
+            }
+            try { throw \"bad record\"; } catch (e) { print e; }";
+
+        assert_eq!(
+            run_to_string(source, &Options::default()),
+            "\"Can't divide by zero\"\n\"2\"\n\"done\"\n\"bad record\"\n"
+        );
+    }
+
+    #[test]
+    fn it_restores_scopes_when_unwinding() {
+        let source = "var a = 1;
+            try {
+                var a = 2;
+                { var b = 3; throw a + b; }
+            } catch (e) {
+                print a;
+                print e;
+            }
+            { a = 4; }
+            print a;";
+
+        assert_eq!(
+            run_to_string(source, &Options::default()),
+            "\"1\"\n\"5\"\n\"4\"\n"
+        );
+    }
+
+    #[test]
+    fn it_reports_uncaught_errors() {
+        let (output, result) = try_run_to_string(
+            "try { print y; } finally { print \"cleanup\"; }",
+            &Options::default(),
+        );
+
+        assert_eq!(output, "\"cleanup\"\n");
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "Undefined variable 'y'.\n[line 1] in script"
+        );
+    }
+
+    #[test]
+    fn it_traces_uncaught_errors() {
+        let mut interpreter = Interpreter::new(Environment::new());
+        interpreter.set_script_path("area.lox");
+
+        let error = interpreter
+            .interpret(parse("var w = 2;\nprint w * \"h\";", &Options::default()))
+            .unwrap_err();
+
+        assert_eq!(
+            error.to_string(),
+            "Unsupported binary operation: 2 * h\n[line 2] in script (area.lox)"
+        );
+    }
+
+    #[test]
+    fn it_raises_a_catchable_stack_overflow() {
+        let source = format!(
+            "try {{ print {}1{}; }} catch (e) {{ print e.message; }}\nprint (1);",
+            "(".repeat(20),
+            ")".repeat(20)
+        );
+        let options = Options {
+            optimize: false,
+            max_depth: 10,
+            ..Options::default()
+        };
+
+        assert_eq!(
+            run_to_string(&source, &options),
+            "\"Stack overflow.\"\n\"1\"\n"
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Too much nesting at line 1.")]
+    fn it_should_panic_too_much_nesting() {
+        let source = format!("print 1{};", " + 1".repeat(5000));
+
+        parse(&source, &Options::default());
+    }
+
+    #[test]
+    fn it_stays_within_the_native_stack_at_the_default_limits() {
+        let nested = |open: &str, close: &str| {
+            let depth = 990;
+            format!("print {}1{};", open.repeat(depth), close.repeat(depth))
+        };
+        let recursion = "fun rec(n) { return rec(n + 1); }
+            try { rec(0); } catch (e) { print e.message; }";
+        let generators = "fun g(n) { if (n > 0) { for (x in g(n - 1)) yield x; } else yield 0; }
+            try { for (x in g(100000)) print x; } catch (e) { print e.message; }";
+
+        let output = run_on_stack(DEFAULT_MAX_DEPTH, DEFAULT_MAX_CALL_DEPTH, || {
+            [
+                nested("(", ")"),
+                nested("[", "]"),
+                recursion.to_string(),
+                generators.to_string(),
+            ]
+            .map(|source| run_to_string(&source, &Options::default()))
+        });
+
+        assert_eq!(output[0], "\"1\"\n");
+        assert_eq!(output[1].matches('[').count(), 990);
+        assert_eq!(output[2], "\"Stack overflow.\"\n");
+        assert_eq!(output[3], "\"Stack overflow.\"\n");
+    }
+
+    #[test]
+    fn it_counts_calls_apart_from_nesting() {
+        let source = "fun down(n) { if (n == 0) { return 0; } else { return 1 + down(n - 1); } }
+            print down(998);
+            try { down(1000); } catch (e) { print e.message; }";
+
+        let output = run_on_stack(DEFAULT_MAX_DEPTH, DEFAULT_MAX_CALL_DEPTH, || {
+            run_to_string(source, &Options::default())
+        });
+
+        assert_eq!(output, "\"998\"\n\"Stack overflow.\"\n");
+    }
+
+    #[test]
+    fn it_stays_within_the_native_stack_past_the_reserved_size() {
+        // Far more stack than can be reserved: the guard stops the recursion instead.
+        let source = "fun rec(n) { return rec(n + 1); }
+            try { rec(0); } catch (e) { print e.message; }";
+        let options = Options {
+            max_depth: 100_000_000_000,
+            max_call_depth: 100_000_000_000,
+            ..Options::default()
+        };
+
+        let output = run_on_stack(options.max_depth, options.max_call_depth, || {
+            run_to_string(source, &options)
+        });
+
+        assert_eq!(output, "\"Stack overflow.\"\n");
+    }
+
+    #[test]
+    fn it_stops_when_the_instruction_budget_runs_out() {
+        let options = Options {
+            max_steps: Some(6),
+            ..Options::default()
+        };
+
+        let (output, result) = try_run_to_string(
+            "var a = 1; try { print a; print a + 1; } catch (e) { print e; }",
+            &options,
+        );
+
+        assert_eq!(output, "\"1\"\n");
+        assert_eq!(
+            result.unwrap_err(),
+            error::RuntimeError::LimitExceeded {
+                limit: error::Limit::Steps,
+                line: 1,
+                trace: vec![error::StackFrame {
+                    function: "script".to_string(),
+                    file: None,
+                    line: 1
+                }]
+            }
+        );
+    }
+
+    #[test]
+    fn it_stops_at_the_deadline() {
+        let options = Options {
+            timeout: Some(Duration::ZERO),
+            ..Options::default()
+        };
+
+        let (_, result) = try_run_to_string(&"print 1;".repeat(1000), &options);
+
+        assert!(matches!(
+            result,
+            Err(error::RuntimeError::LimitExceeded {
+                limit: error::Limit::Deadline,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn it_caps_allocated_memory() {
+        let options = Options {
+            max_allocated_bytes: Some(10),
+            ..Options::default()
+        };
+
+        let (_, result) = try_run_to_string("var a = \"abcdef\";\nvar b = a + a;", &options);
+
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "Execution stopped: memory cap exceeded.\n[line 2] in script"
+        );
+
+        let options = Options {
+            max_allocated_bytes: Some(1000),
+            ..Options::default()
+        };
+
+        for source in [
+            "var xs = [];\nfor (i in 0..100000) {\n xs = [xs, i, i, i]; }",
+            "var m = {};\nfor (i in 0..100000) {\n m = {inner: m, i: i}; }",
+            "fun f(...rest) { return rest; }\nfor (i in 0..100000) {\n f(1, 2, 3, 4); }",
+            "import \"string\" as string;\nfor (i in 0..100000) {\n string.split(\"a,b,c,d\", \",\"); }",
+        ] {
+            let (_, result) = try_run_to_string(source, &options);
+
+            assert_eq!(
+                result.unwrap_err().to_string(),
+                "Execution stopped: memory cap exceeded.\n[line 3] in script"
+            );
+        }
+    }
+
+    #[test]
+    fn it_collapses_repeated_frames_in_traces() {
+        let (_, result) = try_run_to_string(
+            "fun down(n) {\n  if (n == 0) throw \"bottom\";\n  down(n - 1);\n}\ndown(3);",
+            &Options::default(),
+        );
+
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "Uncaught exception: bottom\n[line 2] in down\n[line 3] in down\n\
+             ... previous frame repeated 2 more times\n[line 5] in script"
+        );
+    }
+
+    #[test]
+    fn it_traces_where_a_limit_stopped_the_script() {
+        let options = Options {
+            max_steps: Some(1000),
+            ..Options::default()
+        };
+
+        let (_, result) = try_run_to_string(
+            "fun spin() {\n  for (i in 0..9223372036854775807) {}\n}\nspin();",
+            &options,
+        );
+
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "Execution stopped: instruction budget exhausted.\n[line 2] in spin\n[line 4] in script"
+        );
+    }
+
+    #[test]
+    fn it_can_be_cancelled_from_another_thread() {
+        let mut interpreter = Interpreter::new(Environment::new());
+        let handle = interpreter.cancel_handle();
+
+        // The script would run for hours on its own, so it can only stop because the other thread
+        // cancels it while it runs.
+        let canceller = std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(50));
+            handle.cancel();
+        });
+
+        let result = interpreter.interpret(parse(
+            "var n = 0;\nfor (i in 0..1000000000000) { n = n + i; }",
+            &Options::default(),
+        ));
+        canceller.join().unwrap();
+
+        assert!(matches!(
+            result,
+            Err(error::RuntimeError::LimitExceeded {
+                limit: error::Limit::Cancelled,
+                line: 2,
+                ..
+            })
+        ));
+    }
+
+    fn module_dir(name: &str, files: &[(&str, &str)]) -> std::path::PathBuf {
+        let dir = env::temp_dir().join(format!("rlox-{}-{}", name, process::id()));
+
+        for (path, source) in files {
+            let path = dir.join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, source).unwrap();
+        }
+
+        dir
+    }
+
+    fn run_script(path: &std::path::Path) -> (String, Result<(), error::RuntimeError>) {
+        let output = Rc::new(RefCell::new(Vec::new()));
+        let mut interpreter = Interpreter::with_output(
+            Environment::new(),
+            Box::new(SharedOutput(Rc::clone(&output))),
+        );
+        interpreter.set_script_path(path.to_str().unwrap());
+
+        let source = fs::read_to_string(path).unwrap();
+        let result = interpreter.interpret(parse(&source, &Options::default()));
+
+        let output = output.borrow();
+        (String::from_utf8(output.clone()).unwrap(), result)
+    }
+
+    #[test]
+    fn it_imports_modules_once() {
+        let dir = module_dir(
+            "imports",
+            &[
+                (
+                    "main.lox",
+                    "import \"lib/helpers.lox\" as helpers;\n\
+                     import \"lib/helpers.lox\" as again;\n\
+                     print helpers.greeting + \" \" + again.name;",
+                ),
+                (
+                    "lib/helpers.lox",
+                    "import \"names.lox\" as names;\n\
+                     print \"loading\";\n\
+                     var greeting = \"hello\";\n\
+                     var name = names.first;",
+                ),
+                ("lib/names.lox", "var first = \"ada\";"),
+            ],
+        );
+
+        let (output, result) = run_script(&dir.join("main.lox"));
+        fs::remove_dir_all(&dir).unwrap();
+
+        result.unwrap();
+        assert_eq!(output, "\"loading\"\n\"hello ada\"\n");
+    }
+
+    #[test]
+    fn it_sees_module_variables_change() {
+        let dir = module_dir(
+            "live",
+            &[
+                (
+                    "main.lox",
+                    "import \"lib/d.lox\" as d;\n\
+                     d.inc();\n\
+                     d.inc();\n\
+                     print d.count;\n\
+                     print d.get();",
+                ),
+                (
+                    "lib/d.lox",
+                    "var count = 0;\n\
+                     fun inc() { count = count + 1; }\n\
+                     fun get() { return count; }",
+                ),
+            ],
+        );
+
+        let (output, result) = run_script(&dir.join("main.lox"));
+        fs::remove_dir_all(&dir).unwrap();
+
+        result.unwrap();
+        assert_eq!(output, "\"2\"\n\"2\"\n");
+    }
+
+    #[test]
+    fn it_reports_import_cycles() {
+        let dir = module_dir(
+            "cycle",
+            &[
+                ("a.lox", "import \"b.lox\" as b;"),
+                ("b.lox", "var x = 1;\nimport \"a.lox\" as a;"),
+            ],
+        );
+        let a = dir.join("a.lox").display().to_string();
+        let b = dir.join("b.lox").display().to_string();
+
+        let (_, result) = run_script(&dir.join("a.lox"));
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            format!(
+                "Import cycle: {a} -> {b} -> {a}.\n[line 2] in module ({b})\n[line 1] in script ({a})"
+            )
+        );
+    }
+
+    #[test]
+    fn it_provides_a_math_module() {
+        let source = "import \"math\" as math;\n\
+                      print math.sqrt(16) + math.pow(2, 10);\n\
+                      print math.max(math.floor(2.7), math.round(-2.5));\n\
+                      print math.format(math.PI, 3);\n\
+                      print math.atan2(0, -1) == math.PI;";
+
+        assert_eq!(
+            run_to_string(source, &Options::default()),
+            "\"1028\"\n\"2\"\n\"3.142\"\n\"true\"\n"
+        );
+    }
+
+    #[test]
+    fn it_keeps_integers_in_math_functions() {
+        let source = "import \"math\" as math;\n\
+                      print [math.abs(-3), math.floor(7), math.ceil(7), math.round(7)];\n\
+                      print [math.min(9007199254740993, 9007199254740995), math.max(2, 3)];\n\
+                      print [math.abs(-3.5), math.min(1, 2.5), math.floor(2.5)];\n\
+                      print 9007199254740993 == 9007199254740992.0;\n\
+                      print 9007199254740992 == 9007199254740992.0;\n\
+                      print 9223372036854775807 == 9223372036854775807.0;\n\
+                      try { math.abs(-9223372036854775807 - 1); } catch (e) { print e.message; }";
+
+        assert_eq!(
+            run_to_string(source, &Options::default()),
+            "\"[3, 7, 7, 7]\"\n\"[9007199254740993, 3]\"\n\"[3.5, 1, 2]\"\n\
+             \"false\"\n\"true\"\n\"false\"\n\
+             \"Integer overflow: math.abs(-9223372036854775808)\"\n"
+        );
+    }
+
+    #[test]
+    fn it_follows_ieee_rules_for_nan_and_infinity() {
+        let source = "import \"math\" as math;\n\
+                      var nan = math.sqrt(-1);\n\
+                      print nan;\n\
+                      print nan == nan;\n\
+                      print nan != nan;\n\
+                      print math.min(nan, 1);\n\
+                      print math.log(0);\n\
+                      print math.format(math.INFINITY, 2);";
+
+        assert_eq!(
+            run_to_string(source, &Options::default()),
+            "\"NaN\"\n\"false\"\n\"true\"\n\"NaN\"\n\"-Infinity\"\n\"Infinity\"\n"
+        );
+    }
+
+    #[test]
+    fn it_checks_native_arguments() {
+        let source = "import \"math\" as math;\n\
+                      try { math.sqrt(1, 2); } catch (e) { print e.message; }\n\
+                      try { math.abs(\"x\"); } catch (e) { print e.message; }\n\
+                      try { math.PI(); } catch (e) { print e.message; }";
+
+        assert_eq!(
+            run_to_string(source, &Options::default()),
+            "\"sqrt() expects 1 arguments but got 2.\"\n\
+             \"math.abs expects a number, got x.\"\n\
+             \"Can only call functions, got 3.141592653589793.\"\n"
+        );
+    }
+
+    #[test]
+    fn it_provides_a_string_module() {
+        let source = "import \"string\" as string;\n\
+                      var s = \"  Héllo, World  \";\n\
+                      var t = string.trim(s);\n\
+                      print string.length(t) + string.find(t, \"W\");\n\
+                      print t[1] + string.slice(t, 7) + string.upper(string.slice(t, 0, 2));\n\
+                      print string.join(string.split(\"a,b,c\", \",\"), \"-\");\n\
+                      print string.replace(string.repeat(\"ab\", 3), \"b\", \"!\");\n\
+                      print string.num(\" 3.5 \") + string.length(string.str(10));\n\
+                      print string.startsWith(t, \"Hé\") == string.endsWith(t, \"!\");\n\
+                      print \"apple\" < \"banana\";";
+
+        assert_eq!(
+            run_to_string(source, &Options::default()),
+            "\"19\"\n\"éWorldHÉ\"\n\"a-b-c\"\n\"a!a!a!\"\n\"5.5\"\n\"false\"\n\"true\"\n"
+        );
+    }
+
+    #[test]
+    fn it_compares_and_prints_deeply_nested_values() {
+        let source = "var xs = [];\n\
+                      var ys = [];\n\
+                      for (i in 0..100000) { xs = [xs]; ys = [ys]; }\n\
+                      print xs == ys;\n\
+                      print [xs, 1] == [ys, 2];\n\
+                      print xs;";
+
+        let output = run_to_string(source, &Options::default());
+        let lines: Vec<&str> = output.lines().collect();
+
+        assert_eq!(lines[..2], ["\"true\"", "\"false\""]);
+        assert_eq!(lines[2].len(), 2 * 100001 + 2);
+    }
+
+    #[test]
+    fn it_fails_to_repeat_strings_too_large_for_memory() {
+        let source = "import \"string\" as string;\n\
+                      try { string.repeat(\"ab\", 1000000000000); } catch (e) { print e.message; }\n\
+                      print string.repeat(\"\", 1000000000000);";
+
+        assert_eq!(
+            run_to_string(source, &Options::default()),
+            "\"string.repeat result is too large.\"\n\"\"\n"
+        );
+    }
+
+    #[test]
+    fn it_drops_deeply_nested_values() {
+        let source = "var xs = [];\n\
+                      var m = {};\n\
+                      var f = nil;\n\
+                      for (i in 0..100000) { xs = [xs, i]; m = {inner: m}; var g = f; f = fun () { return g; }; }\n\
+                      print xs[1];\n\
+                      xs = nil;\n\
+                      m = nil;\n\
+                      f = nil;\n\
+                      print \"dropped\";";
+
+        assert_eq!(
+            run_to_string(source, &Options::default()),
+            "\"99999\"\n\"dropped\"\n"
+        );
+    }
+
+    #[test]
+    fn it_collects_closures_stored_in_their_own_scope() {
+        let source = "for (i in 0..5000) { var big = [i, i, i]; fun f() { return big; } }\n\
+                      fun counter() { var n = 0; fun next() { n = n + 1; return n; } return next; }\n\
+                      var next = counter();\n\
+                      next();\n\
+                      print next();";
+
+        assert_eq!(run_to_string(source, &Options::default()), "\"2\"\n");
+        // The global scope holds `counter`, which closes over it, so even it is only freed here.
+        assert_eq!(heap::collect(), 0);
+    }
+
+    #[test]
+    fn it_collects_lambdas_that_refer_to_themselves() {
+        let source = "var calls = 0;\n\
+                      for (i in 0..3000) {\n\
+                        var handlers = {};\n\
+                        handlers = {click: () => handlers, hover: fun () { return i; }};\n\
+                        var again = (n) => n > 0 ? again(n - 1) : n;\n\
+                        calls = calls + again(2) + handlers[\"hover\"]();\n\
+                      }\n\
+                      print calls;";
+
+        assert_eq!(run_to_string(source, &Options::default()), "\"4498500\"\n");
+        assert_eq!(heap::collect(), 0);
+    }
+
+    #[test]
+    fn it_keeps_reachable_values_alive_under_gc_stress() {
+        let source = "fun adder(n) { return (x) => x + n; }\n\
+                      var adders = [adder(1), adder(2)];\n\
+                      fun count(n) { for (i in 0..n) { var j = i * 10; yield () => j; } }\n\
+                      var total = 0;\n\
+                      for (f in count(3)) { total = total + f(); }\n\
+                      var values = {self: nil};\n\
+                      fun keep() { return values; }\n\
+                      print adders[0](10) + adders[1](10);\n\
+                      print total;\n\
+                      print keep() == values;";
+        let options = Options {
+            gc_stress: true,
+            ..Options::default()
+        };
+
+        assert_eq!(
+            run_to_string(source, &options),
+            "\"23\"\n\"30\"\n\"true\"\n"
+        );
+    }
+
+    #[test]
+    fn it_indexes_lists_and_strings() {
+        let source = "var list = [1, \"two\", [3]];\n\
+                      print list;\n\
+                      print list[2][0] + 1;\n\
+                      print list == [1, \"two\", [3]];\n\
+                      try { print \"abc\"[3]; } catch (e) { print e.message; }\n\
+                      try { string.num(\"x\"); } catch (e) { print e.message; }";
+
+        assert_eq!(
+            run_to_string(source, &Options::default()),
+            "\"[1, \\\"two\\\", [3]]\"\n\"4\"\n\"true\"\n\
+             \"Index 3 is out of range for a length of 3.\"\n\
+             \"Undefined variable 'string'.\"\n"
+        );
+    }
+
+    #[test]
+    fn it_parses_calls_and_indexes() {
+        let statements = parse("print f(a, 1)[0].b;", &Options::default());
+
+        assert_eq!(
+            formatter::format("print f(a,1)[ 0 ].b;"),
+            "print f(a, 1)[0].b;\n"
+        );
+        assert_eq!(statements.len(), 1);
+    }
+
+    #[test]
+    fn it_reads_and_writes_files() {
+        let dir = module_dir("io", &[("in.txt", "a\r\nb\n")]);
+        let source = format!(
+            "import \"io\" as io;\n\
+             var dir = \"{}/\";\n\
+             print io.readLines(dir + \"in.txt\");\n\
+             io.writeFile(dir + \"out.txt\", \"x\");\n\
+             io.appendFile(dir + \"out.txt\", \"y\");\n\
+             print io.readFile(dir + \"out.txt\");\n\
+             print io.listDir(dir);\n\
+             print io.exists(dir + \"missing.txt\");\n\
+             try {{ io.readFile(dir + \"missing.txt\"); }} catch (e) {{ print e.message; }}",
+            dir.display()
+        );
+
+        let output = run_to_string(&source, &Options::default());
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(
+            output,
+            format!(
+                "\"[\\\"a\\\", \\\"b\\\"]\"\n\"xy\"\n\"[\\\"in.txt\\\", \\\"out.txt\\\"]\"\n\"false\"\n\
+                 \"Could not read '{}/missing.txt': No such file or directory (os error 2).\"\n",
+                dir.display()
+            )
+        );
+    }
+
+    #[test]
+    fn it_streams_the_lines_of_a_file() {
+        let dir = module_dir("lines", &[("in.txt", "a\r\nb\n\nc")]);
+        let source = format!(
+            "import \"io\" as io;\n\
+             var lines = io.lines(\"{}/in.txt\");\n\
+             print lines;\n\
+             fun two() {{ for (i, line in lines) {{ print [i, line]; if (i == 1) return; }} }}\n\
+             two();\n\
+             for (line in lines) print line;\n\
+             for (line in lines) print line;\n\
+             try {{ io.lines(\"{}/missing.txt\"); }} catch (e) {{ print e.line; }}",
+            dir.display(),
+            dir.display()
+        );
+
+        let output = run_to_string(&source, &Options::default());
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(
+            output,
+            format!(
+                "\"<lines {}/in.txt>\"\n\"[0, \\\"a\\\"]\"\n\"[1, \\\"b\\\"]\"\n\"\"\n\"c\"\n\"8\"\n",
+                dir.display()
+            )
+        );
+    }
+
+    #[test]
+    fn it_reads_lines_from_stdin() {
+        let output = Rc::new(RefCell::new(Vec::new()));
+        let mut interpreter = Interpreter::with_output(
+            Environment::new(),
+            Box::new(SharedOutput(Rc::clone(&output))),
+        );
+        interpreter.set_input(Box::new(io::Cursor::new("first\nsecond\nrest\n")));
+
+        let source = "import \"io\" as io;\n\
+                      print io.readLine();\n\
+                      var line = io.readLine();\n\
+                      print io.readAll();\n\
+                      print io.readLine() == nil;";
+        interpreter
+            .interpret(parse(source, &Options::default()))
+            .unwrap();
+
+        assert_eq!(
+            String::from_utf8(output.borrow().clone()).unwrap(),
+            "\"first\"\n\"rest\\n\"\n\"true\"\n"
+        );
+    }
+
+    #[test]
+    fn it_builds_and_indexes_maps() {
+        let source = "var user = {name: \"ada\", \"born in\": 1815, tags: [\"math\"]};\n\
+                      print user[\"name\"] + \" \" + user[\"tags\"][0];\n\
+                      print user[\"missing\"];\n\
+                      print {a: 1, b: 2} == {b: 2, a: 1};\n\
+                      print {a: 1, a: 2};";
+
+        assert_eq!(
+            run_to_string(source, &Options::default()),
+            "\"ada math\"\n\"nil\"\n\"true\"\n\"{\\\"a\\\": 2}\"\n"
+        );
+    }
+
+    #[test]
+    fn it_parses_large_json_objects() {
+        let keys: Vec<String> = (0..20000).map(|i| format!("\"k{}\": {}", i, i)).collect();
+        let dir = module_dir(
+            "large-json",
+            &[
+                ("large.json", &format!("{{{}}}", keys.join(", "))),
+                ("duplicate.json", "{\"a\": 1, \"b\": 2, \"a\": 3}"),
+            ],
+        );
+        let source = format!(
+            "import \"io\" as io;\n\
+             import \"json\" as json;\n\
+             var dir = \"{}/\";\n\
+             var large = json.parse(io.readFile(dir + \"large.json\"));\n\
+             print [large[\"k0\"], large[\"k19999\"], large[\"k20000\"]];\n\
+             print json.parse(io.readFile(dir + \"duplicate.json\"));",
+            dir.display()
+        );
+
+        let output = run_to_string(&source, &Options::default());
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(
+            output,
+            "\"[0, 19999, nil]\"\n\"{\\\"a\\\": 3, \\\"b\\\": 2}\"\n"
+        );
+    }
+
+    #[test]
+    fn it_converts_json() {
+        let dir = module_dir(
+            "json",
+            &[
+                (
+                    "data.json",
+                    "{\"id\": 7, \"tags\": [\"a\\u00e9\", null, true], \"n\": -1.5e2}",
+                ),
+                ("bad-array.json", "[1,\n 2 3]"),
+                ("bad-object.json", "{\"a\" 1}"),
+            ],
+        );
+        let source = format!(
+            "import \"io\" as io;\n\
+             import \"json\" as json;\n\
+             import \"math\" as math;\n\
+             var dir = \"{}/\";\n\
+             var data = json.parse(io.readFile(dir + \"data.json\"));\n\
+             print data[\"tags\"][0] + \" \" + json.stringify(data[\"n\"]);\n\
+             print json.stringify(data);\n\
+             print json.stringify({{list: [1, {{}}], empty: []}}, 2);\n\
+             print json.parse(json.stringify(data)) == data;\n\
+             try {{ json.parse(io.readFile(dir + \"bad-array.json\")); }} catch (e) {{ print e.message; }}\n\
+             try {{ json.parse(io.readFile(dir + \"bad-object.json\")); }} catch (e) {{ print e.message; }}\n\
+             try {{ json.stringify([math.NAN]); }} catch (e) {{ print e.message; }}",
+            dir.display()
+        );
+
+        let output = run_to_string(&source, &Options::default());
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(
+            output,
+            "\"aé -150\"\n\
+             \"{\\\"id\\\":7,\\\"tags\\\":[\\\"aé\\\",null,true],\\\"n\\\":-150}\"\n\
+             \"{\\n  \\\"list\\\": [\\n    1,\\n    {}\\n  ],\\n  \\\"empty\\\": []\\n}\"\n\
+             \"true\"\n\
+             \"json.parse: Expected ',' or ']' at line 2, column 4.\"\n\
+             \"json.parse: Expected ':' at line 1, column 6.\"\n\
+             \"json.stringify can't convert NaN to JSON.\"\n"
+        );
+    }
+
+    #[test]
+    fn it_keeps_integers_exact() {
+        let source = "var id = 9007199254740993;\n\
+                      print id + 2;\n\
+                      print 10 / 4;\n\
+                      print 10 ~/ 3;\n\
+                      print -7 % 3;\n\
+                      print 7.5 % 2;\n\
+                      print 2 * 1.5;\n\
+                      print 2 == 2.0;\n\
+                      print 4 / 2;\n\
+                      try { print 9223372036854775807 + 1; } catch (e) { print e.message; }\n\
+                      try { print 1 ~/ 0; } catch (e) { print e.message; }";
+
+        for optimize in [true, false] {
+            let options = Options {
+                optimize,
+                ..Options::default()
+            };
+
+            assert_eq!(
+                run_to_string(source, &options),
+                "\"9007199254740995\"\n\"2.5\"\n\"3\"\n\"-1\"\n\"1.5\"\n\"3\"\n\"true\"\n\"2\"\n\
+                 \"Integer overflow: 9223372036854775807 + 1\"\n\"Can't divide by zero\"\n"
+            );
+        }
+    }
+
+    #[test]
+    fn it_formats_float_literals_with_a_decimal_point() {
+        assert_eq!(
+            formatter::format("var x = 2.0 * 3 % 2;"),
+            "var x = 2.0 * 3 % 2;\n"
+        );
+    }
+
+    #[test]
+    fn it_parses_extended_operators_with_precedence() {
+        let cases = [
+            ("1 | 2 ^ 3 & 4 << 5 + 6", "(| 1 (^ 2 (& 3 (<< 4 (+ 5 6)))))"),
+            ("a < b | c", "(< (var a) (| (var b) (var c)))"),
+            ("2 ** 3 ** 2", "(** 2 (** 3 2))"),
+            ("-2 ** 2", "(- (** 2 2))"),
+            ("2 ** -x++", "(** 2 (- (x ++)))"),
+            (
+                "a * b % c ~/ d",
+                "(~/ (% (* (var a) (var b)) (var c)) (var d))",
+            ),
+            ("~a >> 1", "(>> (~ (var a)) 1)"),
+            ("x += y -= 2", "(+= x (-= y 2))"),
+            ("--x - 1", "(- (-- x) 1)"),
+        ];
+
+        for (source, expected) in cases {
+            let mut scanner = Scanner::new(source);
+            let mut parser = Parser::new(scanner.scan_tokens().to_vec());
+
+            assert_eq!(parser.parse_expression().to_custom_string(), expected);
+        }
+    }
+
+    #[test]
+    fn it_evaluates_extended_operators() {
+        let source = "var x = 5;\n\
+                      print x++ + ++x;\n\
+                      print x;\n\
+                      x *= 2 ** 3;\n\
+                      x %= 5;\n\
+                      print x--;\n\
+                      var s = \"a\";\n\
+                      s += \"b\";\n\
+                      print s;\n\
+                      print (6 & 3) + (6 | 3) + (6 ^ 3) + (1 << 4) + (-16 >> 2) + ~0;\n\
+                      print 2 ** -1;\n\
+                      try { print 1.5 & 1; } catch (e) { print e.message; }\n\
+                      try { print 1 << 64; } catch (e) { print e.message; }\n\
+                      try { print 2 ** 63; } catch (e) { print e.message; }";
+
+        assert_eq!(
+            run_to_string(source, &Options::default()),
+            "\"12\"\n\"7\"\n\"1\"\n\"ab\"\n\"25\"\n\"0.5\"\n\
+             \"Unsupported binary operation: 1.5 & 1\"\n\
+             \"Shift amount out of range: 64\"\n\
+             \"Integer overflow: 2 ** 63\"\n"
+        );
+    }
+
+    #[test]
+    fn it_formats_extended_operators() {
+        assert_eq!(
+            formatter::format("x+=2**-y;\nprint - --x+y++;"),
+            "x += 2 ** -y;\nprint - --x + y++;\n"
+        );
+    }
+
+    #[test]
+    fn it_parses_conditional_and_coalescing_expressions() {
+        let cases = [
+            (
+                "a ? b : c ? d : e",
+                "(?: (var a) (var b) (?: (var c) (var d) (var e)))",
+            ),
+            (
+                "a ?? b or c ? 1 : 2",
+                "(?: (?? (var a) (or (var b) (var c))) 1 2)",
+            ),
+            ("x = a?.b.c", "x=(. (?. (var a) b) c)"),
+        ];
+
+        for (source, expected) in cases {
+            let mut scanner = Scanner::new(source);
+            let mut parser = Parser::new(scanner.scan_tokens().to_vec());
+
+            assert_eq!(parser.parse_expression().to_custom_string(), expected);
+        }
+    }
+
+    #[test]
+    fn it_short_circuits_nil_checks() {
+        let source = "import \"math\" as math;\n\
+                      var none = nil;\n\
+                      var calls = 0;\n\
+                      print 1 < 2 ? \"yes\" : \"no\";\n\
+                      print none ?? \"default\";\n\
+                      print false ?? (calls += 1);\n\
+                      print none?.sqrt(calls += 1);\n\
+                      print math?.sqrt(16);\n\
+                      print calls;\n\
+                      try { print none.sqrt; } catch (e) { print e.message; }";
+
+        for optimize in [true, false] {
+            let options = Options {
+                optimize,
+                ..Options::default()
+            };
+
+            assert_eq!(
+                run_to_string(source, &options),
+                "\"yes\"\n\"default\"\n\"false\"\n\"nil\"\n\"4\"\n\"0\"\n\
+                 \"Only errors, modules, enums, variants and generators have properties, got nil.\"\n"
+            );
+        }
+    }
+
+    #[test]
+    fn it_calls_closures_and_lambdas() {
+        let source = "fun makeCounter() {\n\
+                          var count = 0;\n\
+                          return fun () { count += 1; return count; };\n\
+                      }\n\
+                      var counter = makeCounter();\n\
+                      counter();\n\
+                      print counter();\n\
+                      var add = (a, b) => a + b;\n\
+                      print add(2, 3);\n\
+                      print (() => \"none\")();\n\
+                      fun fib(n) { if (n < 2) return n; return fib(n - 1) + fib(n - 2); }\n\
+                      print fib(10);\n\
+                      fun early() { try { return 1; } finally { print \"finally\"; } }\n\
+                      print early();\n\
+                      print fun () {}();\n\
+                      try { add(1); } catch (e) { print e.message; }\n\
+                      print add;";
+
+        for optimize in [true, false] {
+            let options = Options {
+                optimize,
+                ..Options::default()
+            };
+
+            assert_eq!(
+                run_to_string(source, &options),
+                "\"2\"\n\"5\"\n\"none\"\n\"55\"\n\"finally\"\n\"1\"\n\"nil\"\n\
+                 \"lambda() is missing an argument for parameter 'b'.\"\n\"<fn lambda>\"\n"
+            );
+        }
+    }
+
+    #[test]
+    fn it_traces_errors_through_functions() {
+        let mut interpreter = Interpreter::new(Environment::new());
+        interpreter.set_script_path("calls.lox");
+
+        let source = "fun inner() {\nthrow \"boom\";\n}\nvar outer = () => inner();\nouter();";
+        let error = interpreter
+            .interpret(parse(source, &Options::default()))
+            .unwrap_err();
+
+        assert_eq!(
+            error.to_string(),
+            "Uncaught exception: boom\n[line 2] in inner (calls.lox)\n\
+             [line 4] in lambda (calls.lox)\n[line 5] in script (calls.lox)"
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Can't return from top-level code at line 1.")]
+    fn it_should_panic_top_level_return() {
+        parse("if (true) return 1;", &Options::default());
+    }
+
+    #[test]
+    fn it_formats_functions() {
+        assert_eq!(
+            formatter::format(
+                "fun add(a,b=1,...c){return a+b;}\nvar f=fun(x){\nreturn;};\nprint map((x)=>x*2,by:1);"
+            ),
+            "fun add(a, b = 1, ...c) {\n    return a + b;\n}\nvar f = fun (x) {\n    return;\n};\n\
+             print map((x) => x * 2, by: 1);\n"
+        );
+    }
+
+    #[test]
+    fn it_binds_default_rest_and_named_parameters() {
+        let source = "fun greet(name, greeting = \"hi\", ...rest) {\n\
+                          return greeting + \" \" + name + \" \" + string.str(rest);\n\
+                      }\n\
+                      import \"string\" as string;\n\
+                      print greet(\"a\");\n\
+                      print greet(greeting: \"yo\", name: \"b\");\n\
+                      print greet(\"c\", \"hey\", 1, 2);\n\
+                      var scale = (x, factor = x) => x * factor;\n\
+                      print scale(3);\n\
+                      var errors = [\n\
+                          fun () { greet(); },\n\
+                          fun () { scale(1, 2, 3); },\n\
+                          fun () { greet(\"d\", nam: 1); },\n\
+                          fun () { greet(\"d\", name: 1); },\n\
+                          fun () { string.upper(text: \"x\"); }\n\
+                      ];\n\
+                      fun check(i) { try { errors[i](); } catch (e) { print e.message; } }\n\
+                      check(0); check(1); check(2); check(3); check(4);";
+
+        assert_eq!(
+            run_to_string(source, &Options::default()),
+            "\"hi a []\"\n\"yo b []\"\n\"hey c [1, 2]\"\n\"9\"\n\
+             \"greet() is missing an argument for parameter 'name'.\"\n\
+             \"lambda() expects at most 2 arguments but got 3.\"\n\
+             \"greet() has no parameter named 'nam'.\"\n\
+             \"greet() got more than one value for parameter 'name'.\"\n\
+             \"upper() doesn't take named arguments.\"\n"
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "The rest parameter must be the last one at line 1.")]
+    fn it_should_panic_rest_parameter_not_last() {
+        parse("fun f(...a, b) {}", &Options::default());
+    }
+
+    #[test]
+    fn it_matches_patterns() {
+        let source = "fun describe(value) {\n\
+                          return match (value) {\n\
+                              0 => \"zero\",\n\
+                              -1 | \"a\" | nil => \"special\",\n\
+                              [x, y] => \"pair\",\n\
+                              [_, ...rest] => \"list and \" + string.str(rest),\n\
+                              {name: n, age} if age > 17 => n + \" adult\",\n\
+                              {name} => name,\n\
+                              _ => \"other\"\n\
+                          };\n\
+                      }\n\
+                      import \"string\" as string;\n\
+                      print describe(0);\n\
+                      print describe(-1);\n\
+                      print describe(nil);\n\
+                      print describe([1, 2]);\n\
+                      print describe([1, 2, 3]);\n\
+                      print describe({name: \"ann\", age: 30});\n\
+                      print describe({name: \"bo\", age: 3});\n\
+                      print describe(0.5);\n\
+                      match (3) {\n\
+                          1 => print \"one\";\n\
+                          n if (n > 2) => { print n; }\n\
+                      }\n\
+                      try { match (1) { 2 => print 2; } } catch (e) { print e.message; }";
+
+        for optimize in [true, false] {
+            let options = Options {
+                optimize,
+                ..Options::default()
+            };
+
+            assert_eq!(
+                run_to_string(source, &options),
+                "\"zero\"\n\"special\"\n\"special\"\n\"pair\"\n\"list and [2, 3]\"\n\
+                 \"ann adult\"\n\"bo\"\n\"other\"\n\"3\"\n\"No match arm for 1.\"\n"
+            );
+        }
+    }
+
+    #[test]
+    fn it_warns_about_matches_without_a_catch_all() {
+        let statements = parse(
+            "match (1) { 1 => print 1; }\n\
+             print match (2) { 2 => 2, n if n > 2 => n };\n\
+             print match (3) { 3 => 3, n => n };",
+            &Options::default(),
+        );
+
+        let warnings: Vec<String> = resolver::resolve(&statements)
+            .iter()
+            .map(|warning| warning.to_string())
+            .collect();
+
+        assert_eq!(
+            warnings,
+            [
+                "[line 1] Warning: match is not exhaustive and has no '_' arm.",
+                "[line 2] Warning: match is not exhaustive and has no '_' arm."
+            ]
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Duplicate binding 'x' in pattern at line 1.")]
+    fn it_should_panic_duplicate_pattern_binding() {
+        parse("match (1) { [x, {x}] => print x; }", &Options::default());
+    }
+
+    #[test]
+    fn it_formats_matches() {
+        assert_eq!(
+            formatter::format(
+                "match(x){-1|\"a\"=>print 1;[a,...b] if a>1=>{print b;}}\n\
+                 var y=match(x){{name,age:0.0}=>1,_=>2};"
+            ),
+            "match (x) {\n    -1 | \"a\" => print 1;\n    [a, ...b] if a > 1 => {\n        print b;\n    }\n}\n\
+             var y = match (x) {\n    {name, age: 0.0} => 1,\n    _ => 2\n};\n"
+        );
+    }
+
+    #[test]
+    fn it_keeps_comments_between_match_arms() {
+        let source = "match (x) {\n\
+                      // small\n\
+                      0 | 1 => print 1; // one\n\
+                      \n\
+                      // the rest\n\
+                      _ => print 2;\n\
+                      // done\n\
+                      }\n";
+        let expected =
+            "match (x) {\n    // small\n    0 | 1 => print 1; // one\n\n    // the rest\n    \
+                        _ => print 2;\n    // done\n}\n";
+
+        assert_eq!(formatter::format(source), expected);
+        assert_eq!(formatter::format(expected), expected);
+    }
+
+    #[test]
+    fn it_builds_and_matches_enums() {
+        let source = "enum Shape { Circle(radius), Rect(w, h), Empty }\n\
+                      fun area(shape) {\n\
+                          return match (shape) {\n\
+                              Shape.Circle(r) => 3 * r * r,\n\
+                              Rect(w, h) => w * h,\n\
+                              Shape.Empty => 0\n\
+                          };\n\
+                      }\n\
+                      var c = Shape.Circle(2);\n\
+                      print c;\n\
+                      print Shape.Empty;\n\
+                      print Shape.Rect;\n\
+                      print c.radius;\n\
+                      print area(c) + area(Shape.Rect(2, 3)) + area(Shape.Empty);\n\
+                      print c == Shape.Circle(2);\n\
+                      print c == Shape.Circle(3);\n\
+                      print c is Shape;\n\
+                      print c is Shape.Rect;\n\
+                      print Shape.Empty is Shape.Empty;\n\
+                      print 1 is Shape;\n\
+                      try { Shape.Rect(1); } catch (e) { print e.message; }\n\
+                      try { print Shape.Square; } catch (e) { print e.message; }";
+
+        for optimize in [true, false] {
+            let options = Options {
+                optimize,
+                ..Options::default()
+            };
+
+            assert_eq!(
+                run_to_string(source, &options),
+                "\"Shape.Circle(2)\"\n\"Shape.Empty\"\n\"<constructor Shape.Rect>\"\n\"2\"\n\
+                 \"18\"\n\"true\"\n\"false\"\n\"true\"\n\"false\"\n\"true\"\n\"false\"\n\
+                 \"Shape.Rect() expects 2 arguments but got 1.\"\n\
+                 \"Enum Shape has no variant 'Square'.\"\n"
+            );
+        }
+    }
+
+    #[test]
+    fn it_checks_enum_matches() {
+        let statements = parse(
+            "enum Shape { Circle(radius), Empty }\n\
+             match (Shape.Empty) { Shape.Circle(_) => print 1; Shape.Empty => print 2; }\n\
+             match (Shape.Empty) { Shape.Circle(0) => print 1; Shape.Empty => print 2; }\n\
+             match (Shape.Empty) { Shape.Square => print 1; Shape.Circle(a, b) => print 2; _ => print 3; }",
+            &Options::default(),
+        );
+
+        let warnings: Vec<String> = resolver::resolve(&statements)
+            .iter()
+            .map(|warning| warning.to_string())
+            .collect();
+
+        assert_eq!(
+            warnings,
+            [
+                "[line 3] Warning: match is not exhaustive and has no '_' arm.",
+                "[line 4] Warning: Enum Shape has no variant 'Square'.",
+                "[line 4] Warning: Shape.Circle has 1 fields but the pattern has 2."
+            ]
+        );
+    }
+
+    #[test]
+    fn it_formats_enums() {
+        assert_eq!(
+            formatter::format(
+                "enum Shape{Circle(radius),Rect(w,h),Empty}\n\
+                 enum Never{}\n\
+                 match(s){Shape.Circle(r)=>print r;Empty=>print 0;}"
+            ),
+            "enum Shape { Circle(radius), Rect(w, h), Empty }\n\
+             enum Never {}\n\
+             match (s) {\n    Shape.Circle(r) => print r;\n    Empty => print 0;\n}\n"
+        );
+    }
+
+    #[test]
+    fn it_keeps_comments_between_enum_variants() {
+        let source = "enum Shape {\n // a circle\n Circle(radius), Empty }\n\
+                      enum Size { Small, // tiny\n Large // huge\n }\n";
+        let expected = "enum Shape {\n    // a circle\n    Circle(radius),\n    Empty,\n}\n\
+                        enum Size {\n    Small, // tiny\n    Large, // huge\n}\n";
+
+        assert_eq!(formatter::format(source), expected);
+        assert_eq!(formatter::format(expected), expected);
+    }
+
+    #[test]
+    fn it_destructures_lists_and_maps() {
+        let source = "var [a, b, ...rest] = [1, 2, 3, 4];\n\
+                      var {name, age = 18, address: {city}} = {name: \"ann\", address: {city: \"Oslo\"}};\n\
+                      [a, b] = [b, a];\n\
+                      print [a, b, rest];\n\
+                      print name + \" \" + city;\n\
+                      print age;\n\
+                      var [x, [y, z] = [5, 6]] = [0];\n\
+                      print x + y + z;\n\
+                      print ({name} = {name: \"bo\"});\n\
+                      print name;\n\
+                      try { var [p] = [1, 2]; } catch (e) { print e.message; }\n\
+                      try { var [p, q] = [1]; } catch (e) { print e.message; }\n\
+                      try { var {k} = {}; } catch (e) { print e.message; }\n\
+                      try { var [p] = 3; } catch (e) { print e.message; }";
+
+        for optimize in [true, false] {
+            let options = Options {
+                optimize,
+                ..Options::default()
+            };
+
+            assert_eq!(
+                run_to_string(source, &options),
+                "\"[2, 1, [3, 4]]\"\n\"ann Oslo\"\n\"18\"\n\"11\"\n\"{\\\"name\\\": \\\"bo\\\"}\"\n\"bo\"\n\
+                 \"Expected at most 1 elements to destructure, got 2.\"\n\
+                 \"Expected at least 2 elements to destructure, got 1.\"\n\
+                 \"Map has no key 'k' to destructure.\"\n\
+                 \"Can't destructure 3 with a list pattern.\"\n"
+            );
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "Default values are only allowed when destructuring at line 1.")]
+    fn it_should_panic_default_in_match_pattern() {
+        parse("match (1) { [x = 1] => print x; }", &Options::default());
+    }
+
+    #[test]
+    fn it_formats_destructuring() {
+        assert_eq!(
+            formatter::format("var[a,b=1,...c]=l;\nvar{name,age=2,x:[y]}=m;\n[a,b]=[b,a];"),
+            "var [a, b = 1, ...c] = l;\nvar {name, age = 2, x: [y]} = m;\n[a, b] = [b, a];\n"
+        );
+    }
+
+    #[test]
+    fn it_iterates_with_for_in() {
+        let source = "var f;\n\
+                      var g;\n\
+                      for (i in 0..2) { if (i == 0) f = () => i; else g = () => i; }\n\
+                      print [f(), g()];\n\
+                      for (i in 10..0 step -4) print i;\n\
+                      for (c in \"hé\") print c;\n\
+                      for (k in {a: 1, b: 2}) print k;\n\
+                      for (k, v in {a: 1}) print [k, v];\n\
+                      for (i, [a, b] in [[1, 2], [3, 4]]) print i + a + b;\n\
+                      print 0..10 step 2;\n\
+                      try { for (x in 5) print x; } catch (e) { print e.message; }\n\
+                      try { print 0..1 step 0; } catch (e) { print e.message; }";
+
+        for optimize in [true, false] {
+            let options = Options {
+                optimize,
+                ..Options::default()
+            };
+
+            assert_eq!(
+                run_to_string(source, &options),
+                "\"[0, 1]\"\n\"10\"\n\"6\"\n\"2\"\n\"h\"\n\"é\"\n\"a\"\n\"b\"\n\"[\\\"a\\\", 1]\"\n\
+                 \"3\"\n\"8\"\n\"0..10 step 2\"\n\"Can't iterate over 5.\"\n\"Range step can't be 0.\"\n"
+            );
+        }
+    }
+
+    #[test]
+    fn it_formats_for_in_loops() {
+        assert_eq!(
+            formatter::format("for(i in 0..n+1 step 2)print i;\nfor(k,{a} in m){print k;}"),
+            "for (i in 0..n + 1 step 2)\n    print i;\nfor (k, {a} in m) {\n    print k;\n}\n"
+        );
+    }
+
+    #[test]
+    fn it_runs_generators() {
+        let source = "fun count(n) {\n\
+                      for (i in 0..n) { if (i == 1) yield \"one\"; else yield i; }\n\
+                      try { throw \"boom\"; } catch (e) { yield e; } finally { print \"done\"; }\n\
+                      }\n\
+                      for (x in count(2)) print x;\n\
+                      var g = count(1);\n\
+                      print g;\n\
+                      print g.next();\n\
+                      print g.next();\n\
+                      print g.next();\n\
+                      print g.next();\n\
+                      fun me() { yield self.next(); }\n\
+                      var self = me();\n\
+                      try { self.next(); } catch (e) { print e.message; }";
+
+        for optimize in [true, false] {
+            let options = Options {
+                optimize,
+                ..Options::default()
+            };
+
+            assert_eq!(
+                run_to_string(source, &options),
+                "\"0\"\n\"one\"\n\"boom\"\n\"done\"\n\"<generator count>\"\n\
+                 \"{\\\"value\\\": 0, \\\"done\\\": false}\"\n\
+                 \"{\\\"value\\\": \\\"boom\\\", \\\"done\\\": false}\"\n\"done\"\n\
+                 \"{\\\"value\\\": nil, \\\"done\\\": true}\"\n\
+                 \"{\\\"value\\\": nil, \\\"done\\\": true}\"\n\
+                 \"Generator me() is already running.\"\n"
+            );
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "Can't yield from top-level code at line 1.")]
+    fn it_should_panic_top_level_yield() {
+        parse("yield 1;", &Options::default());
+    }
+
+    #[test]
+    fn it_formats_yield() {
+        assert_eq!(
+            formatter::format("fun g(){yield;yield 1+2;}"),
+            "fun g() {\n    yield;\n    yield 1 + 2;\n}\n"
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Can't assign to constant MAX at line 3.")]
+    fn it_should_panic_assignment_to_constant() {
+        parse(
+            "const MAX = 10;\n\
+             { var MAX = 1; MAX = 2; }\n\
+             fun bump() { MAX += 1; }",
+            &Options::default(),
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Can't assign to constant b at line 2.")]
+    fn it_should_panic_assignment_to_destructured_constant() {
+        parse("const [a, {b}] = [1, {b: 2}];\nb++;", &Options::default());
+    }
+
+    #[test]
+    #[should_panic(expected = "Constant Z is already declared in this scope at line 1.")]
+    fn it_should_panic_redeclared_constant() {
+        parse(
+            "const Z = 1; var Z = 2; Z = 3; print Z;",
+            &Options::default(),
+        );
+    }
+
+    #[test]
+    fn it_checks_constants_in_imported_modules() {
+        let dir = module_dir(
+            "constants",
+            &[
+                ("main.lox", "import \"config.lox\" as config;"),
+                ("config.lox", "const LIMIT = 10;\nLIMIT = 20;"),
+            ],
+        );
+
+        let result = std::panic::catch_unwind(|| run_script(&dir.join("main.lox")));
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(
+            result.unwrap_err().downcast_ref::<String>().unwrap(),
+            "Can't assign to constant LIMIT at line 2."
+        );
+    }
+
+    #[test]
+    fn it_rejects_assignments_to_constants_at_runtime() {
+        let source = "fun later() { LATER = 2; }\n\
+                      const LATER = 1;\n\
+                      try { later(); } catch (e) { print e.message; }\n\
+                      fun shadow(LATER) { LATER = 3; return LATER; }\n\
+                      print [shadow(0), LATER];";
+        // The resolver would reject this one before it runs.
+        let redeclared = "const Z = 1;\nvar Z = 2;";
+
+        for optimize in [true, false] {
+            let options = Options {
+                optimize,
+                ..Options::default()
+            };
+
+            assert_eq!(
+                run_to_string(source, &options),
+                "\"Can't assign to constant LATER.\"\n\"[3, 1]\"\n"
+            );
+        }
+
+        let mut scanner = Scanner::new(redeclared);
+        let statements = Parser::new(scanner.scan_tokens().to_vec()).parse();
+        let error = Interpreter::new(Environment::new())
+            .interpret(statements)
+            .unwrap_err();
+
+        assert_eq!(
+            error.to_string(),
+            "Constant Z is already declared in this scope.\n[line 2] in script"
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Constant X must be initialized at line 1.")]
+    fn it_should_panic_const_without_initializer() {
+        parse("const X;", &Options::default());
+    }
+
+    #[test]
+    fn it_formats_constants() {
+        assert_eq!(
+            formatter::format("const X=1+2;\nconst[a,b]=l;"),
+            "const X = 1 + 2;\nconst [a, b] = l;\n"
+        );
     }
 }
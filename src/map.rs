@@ -0,0 +1,92 @@
+use std::collections::HashMap;
+use std::slice;
+
+use crate::{list::drop_nested, Literal};
+
+// The entries of a map value, in insertion order, with an index from each key to its position so
+// that lookups and inserts don't scan the entries. Keeping the order makes printing and
+// `json.stringify` deterministic.
+#[derive(Default, Debug)]
+pub struct Map {
+    entries: Vec<(String, Literal)>,
+    positions: HashMap<String, usize>,
+}
+
+impl Map {
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            entries: Vec::with_capacity(capacity),
+            positions: HashMap::with_capacity(capacity),
+        }
+    }
+
+    // A key that is already there gets the new value but keeps its original position, the same
+    // rule JSON objects with duplicate keys follow.
+    pub fn insert(&mut self, key: String, value: Literal) {
+        match self.positions.get(&key) {
+            Some(&position) => self.entries[position].1 = value,
+            None => {
+                self.positions.insert(key.clone(), self.entries.len());
+                self.entries.push((key, value));
+            }
+        }
+    }
+
+    pub fn get(&self, key: &str) -> Option<&Literal> {
+        self.positions
+            .get(key)
+            .map(|&position| &self.entries[position].1)
+    }
+
+    // The entry at `position` in insertion order.
+    pub fn entry(&self, position: usize) -> Option<&(String, Literal)> {
+        self.entries.get(position)
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn iter(&self) -> slice::Iter<'_, (String, Literal)> {
+        self.entries.iter()
+    }
+
+    // Empties the map, handing out its values.
+    pub fn take_values(&mut self) -> Vec<Literal> {
+        self.positions.clear();
+
+        std::mem::take(&mut self.entries)
+            .into_iter()
+            .map(|(_, value)| value)
+            .collect()
+    }
+}
+
+impl Drop for Map {
+    fn drop(&mut self) {
+        drop_nested(self.take_values());
+    }
+}
+
+impl FromIterator<(String, Literal)> for Map {
+    fn from_iter<I: IntoIterator<Item = (String, Literal)>>(entries: I) -> Self {
+        let mut map = Map::default();
+
+        for (key, value) in entries {
+            map.insert(key, value);
+        }
+
+        map
+    }
+}
+
+// The same entries in the same order, like the `Vec` maps used to be.
+impl PartialEq for Map {
+    fn eq(&self, other: &Self) -> bool {
+        self.entries == other.entries
+    }
+}
@@ -0,0 +1,486 @@
+use std::rc::Rc;
+
+use crate::{
+    pattern::{MatchArm, Pattern},
+    Expression, FunctionDeclaration, Literal, Parameter, Statement, Token, TokenType,
+};
+
+// A single pass over the tree that runs between parsing and interpretation. It only rewrites
+// what is statically known: operations on literals are folded, branches guarded by a literal
+// condition are dropped, and a few identities are simplified. Nothing that could fail at runtime
+// is folded, so an error still surfaces only if (and where) the original code would have raised it.
+pub fn optimize(statements: Vec<Statement>) -> Vec<Statement> {
+    statements
+        .into_iter()
+        .map(optimize_statement)
+        .filter(|statement| !is_empty_block(statement))
+        .collect()
+}
+
+fn optimize_statement(statement: Statement) -> Statement {
+    match statement {
+        Statement::Expression { expression } => Statement::Expression {
+            expression: optimize_expression(expression),
+        },
+        Statement::Print {
+            keyword,
+            expression,
+        } => Statement::Print {
+            keyword,
+            expression: optimize_expression(expression),
+        },
+        Statement::Var { token, expression } => Statement::Var {
+            token,
+            expression: optimize_expression(expression),
+        },
+        Statement::Const { token, expression } => Statement::Const {
+            token,
+            expression: optimize_expression(expression),
+        },
+        Statement::Destructure {
+            keyword,
+            pattern,
+            expression,
+        } => Statement::Destructure {
+            keyword,
+            pattern: optimize_pattern(pattern),
+            expression: optimize_expression(expression),
+        },
+        Statement::For {
+            keyword,
+            key,
+            value,
+            iterable,
+            body,
+        } => Statement::For {
+            keyword,
+            key: key.map(|key| Box::from(optimize_pattern(*key))),
+            value: Box::from(optimize_pattern(*value)),
+            iterable: optimize_expression(iterable),
+            body: Box::from(optimize_statement(*body)),
+        },
+        Statement::Block { statements, line } => Statement::Block {
+            statements: optimize(statements),
+            line,
+        },
+        statement @ (Statement::Comment { .. }
+        | Statement::BlankLine { .. }
+        | Statement::Import { .. }
+        | Statement::Enum { .. }) => statement,
+        Statement::Function { declaration } => Statement::Function {
+            declaration: optimize_function(declaration),
+        },
+        Statement::Return { keyword, value } => Statement::Return {
+            keyword,
+            value: value.map(optimize_expression),
+        },
+        Statement::Yield { keyword, value } => Statement::Yield {
+            keyword,
+            value: value.map(optimize_expression),
+        },
+        Statement::Match {
+            keyword,
+            subject,
+            arms,
+            comments,
+        } => Statement::Match {
+            keyword,
+            subject: optimize_expression(subject),
+            arms: arms
+                .into_iter()
+                .map(|arm| optimize_arm(arm, optimize_statement))
+                .collect(),
+            comments,
+        },
+        Statement::Throw {
+            keyword,
+            expression,
+        } => Statement::Throw {
+            keyword,
+            expression: optimize_expression(expression),
+        },
+        Statement::Try {
+            try_statement,
+            catch_clause,
+            finally_statement,
+        } => Statement::Try {
+            try_statement: Box::from(optimize_statement(*try_statement)),
+            catch_clause: catch_clause
+                .map(|(name, statement)| (name, Box::from(optimize_statement(*statement)))),
+            finally_statement: finally_statement
+                .map(|statement| Box::from(optimize_statement(*statement))),
+        },
+        Statement::If {
+            condition,
+            then_statement,
+        } => match optimize_expression(condition) {
+            Expression::Literal { literal_value, .. } => {
+                if literal_value == Literal::Boolean(true) {
+                    optimize_statement(*then_statement)
+                } else {
+                    empty_block(then_statement.line())
+                }
+            }
+            condition => Statement::If {
+                condition,
+                then_statement: Box::from(optimize_statement(*then_statement)),
+            },
+        },
+        Statement::IfElse {
+            condition,
+            then_statement,
+            else_stament,
+        } => match optimize_expression(condition) {
+            Expression::Literal { literal_value, .. } => {
+                if literal_value == Literal::Boolean(true) {
+                    optimize_statement(*then_statement)
+                } else {
+                    optimize_statement(*else_stament)
+                }
+            }
+            condition => Statement::IfElse {
+                condition,
+                then_statement: Box::from(optimize_statement(*then_statement)),
+                else_stament: Box::from(optimize_statement(*else_stament)),
+            },
+        },
+    }
+}
+
+pub fn optimize_expression(expression: Expression) -> Expression {
+    match expression {
+        Expression::Grouping { expression } => match optimize_expression(*expression) {
+            literal @ Expression::Literal { .. } => literal,
+            expression => Expression::Grouping {
+                expression: Box::from(expression),
+            },
+        },
+        Expression::Binary {
+            left,
+            operator,
+            right,
+        } => {
+            let left = optimize_expression(*left);
+            let right = optimize_expression(*right);
+
+            if let (
+                Expression::Literal {
+                    literal_value: l, ..
+                },
+                Expression::Literal {
+                    literal_value: r, ..
+                },
+            ) = (&left, &right)
+            {
+                if let Ok(literal_value) =
+                    Expression::binary_operation(l.clone(), &operator, r.clone())
+                {
+                    return Expression::Literal {
+                        literal_value,
+                        line: operator.get_line(),
+                    };
+                }
+            }
+
+            simplify_binary(left, operator, right)
+        }
+        Expression::Unary { operator, right } => match optimize_expression(*right) {
+            Expression::Literal {
+                literal_value: Literal::Number(value),
+                ..
+            } if operator.type_equals_to(&TokenType::Minus) => Expression::Literal {
+                literal_value: Literal::Number(-value),
+                line: operator.get_line(),
+            },
+            Expression::Literal {
+                literal_value: Literal::Int(value),
+                ..
+            } if operator.type_equals_to(&TokenType::Minus) && value != i64::MIN => {
+                Expression::Literal {
+                    literal_value: Literal::Int(-value),
+                    line: operator.get_line(),
+                }
+            }
+            right => Expression::Unary {
+                operator,
+                right: Box::from(right),
+            },
+        },
+        Expression::Assignment { name, value } => Expression::Assignment {
+            name,
+            value: Box::from(optimize_expression(*value)),
+        },
+        Expression::Destructure {
+            pattern,
+            equals,
+            value,
+        } => Expression::Destructure {
+            pattern: optimize_pattern(pattern),
+            equals,
+            value: Box::from(optimize_expression(*value)),
+        },
+        Expression::CompoundAssignment {
+            name,
+            operator,
+            value,
+        } => Expression::CompoundAssignment {
+            name,
+            operator,
+            value: Box::from(optimize_expression(*value)),
+        },
+        Expression::Logical {
+            left,
+            operator,
+            right,
+        } => {
+            let left = optimize_expression(*left);
+            let right = optimize_expression(*right);
+
+            // Mirrors the short-circuit rules in `Expression::evaluate`: a literal left operand has
+            // no side effects, so either it is the result or the right operand is.
+            if let Expression::Literal { literal_value, .. } = &left {
+                let short_circuits = match operator.get_token_type() {
+                    TokenType::Or => *literal_value == Literal::Boolean(true),
+                    TokenType::And => *literal_value == Literal::Boolean(false),
+                    TokenType::QuestionQuestion => *literal_value != Literal::Nil,
+                    _ => false,
+                };
+
+                return if short_circuits { left } else { right };
+            }
+
+            Expression::Logical {
+                left: Box::from(left),
+                operator,
+                right: Box::from(right),
+            }
+        }
+        Expression::Get {
+            object,
+            name,
+            optional,
+        } => Expression::Get {
+            object: Box::from(optimize_expression(*object)),
+            name,
+            optional,
+        },
+        Expression::Conditional {
+            condition,
+            question,
+            then_branch,
+            else_branch,
+        } => match optimize_expression(*condition) {
+            Expression::Literal { literal_value, .. } => {
+                if literal_value == Literal::Boolean(true) {
+                    optimize_expression(*then_branch)
+                } else {
+                    optimize_expression(*else_branch)
+                }
+            }
+            condition => Expression::Conditional {
+                condition: Box::from(condition),
+                question,
+                then_branch: Box::from(optimize_expression(*then_branch)),
+                else_branch: Box::from(optimize_expression(*else_branch)),
+            },
+        },
+        Expression::Range {
+            start,
+            operator,
+            end,
+            step,
+        } => Expression::Range {
+            start: Box::from(optimize_expression(*start)),
+            operator,
+            end: Box::from(optimize_expression(*end)),
+            step: step.map(|step| Box::from(optimize_expression(*step))),
+        },
+        Expression::Call {
+            callee,
+            paren,
+            arguments,
+            named,
+        } => Expression::Call {
+            callee: Box::from(optimize_expression(*callee)),
+            paren,
+            arguments: arguments.into_iter().map(optimize_expression).collect(),
+            named: named
+                .into_iter()
+                .map(|(name, value)| (name, optimize_expression(value)))
+                .collect(),
+        },
+        Expression::List { bracket, elements } => Expression::List {
+            bracket,
+            elements: elements.into_iter().map(optimize_expression).collect(),
+        },
+        Expression::Index {
+            object,
+            bracket,
+            index,
+        } => Expression::Index {
+            object: Box::from(optimize_expression(*object)),
+            bracket,
+            index: Box::from(optimize_expression(*index)),
+        },
+        Expression::Map { brace, entries } => Expression::Map {
+            brace,
+            entries: entries
+                .into_iter()
+                .map(|(key, value)| (key, optimize_expression(value)))
+                .collect(),
+        },
+        Expression::Function { declaration } => Expression::Function {
+            declaration: optimize_function(declaration),
+        },
+        Expression::Match {
+            keyword,
+            subject,
+            arms,
+        } => Expression::Match {
+            keyword,
+            subject: Box::from(optimize_expression(*subject)),
+            arms: arms
+                .into_iter()
+                .map(|arm| optimize_arm(arm, optimize_expression))
+                .collect(),
+        },
+        expression @ (Expression::Literal { .. }
+        | Expression::Var { .. }
+        | Expression::Increment { .. }) => expression,
+    }
+}
+
+fn optimize_arm<T>(arm: MatchArm<T>, optimize_body: fn(T) -> T) -> MatchArm<T> {
+    MatchArm {
+        guard: arm.guard.map(optimize_expression),
+        body: optimize_body(arm.body),
+        ..arm
+    }
+}
+
+// Only the defaults of destructuring patterns hold expressions.
+fn optimize_pattern(pattern: Pattern) -> Pattern {
+    match pattern {
+        Pattern::Default {
+            pattern,
+            equals,
+            default,
+        } => Pattern::Default {
+            pattern: Box::from(optimize_pattern(*pattern)),
+            equals,
+            default: Box::from(optimize_expression(*default)),
+        },
+        Pattern::List {
+            bracket,
+            elements,
+            rest,
+        } => Pattern::List {
+            bracket,
+            elements: elements.into_iter().map(optimize_pattern).collect(),
+            rest,
+        },
+        Pattern::Map { brace, entries } => Pattern::Map {
+            brace,
+            entries: entries
+                .into_iter()
+                .map(|(key, pattern)| (key, optimize_pattern(pattern)))
+                .collect(),
+        },
+        Pattern::Variant {
+            enumeration,
+            name,
+            fields,
+        } => Pattern::Variant {
+            enumeration,
+            name,
+            fields: fields.map(|fields| fields.into_iter().map(optimize_pattern).collect()),
+        },
+        pattern => pattern,
+    }
+}
+
+// A declaration straight out of the parser is not shared yet; one that is gets left as it is.
+fn optimize_function(declaration: Rc<FunctionDeclaration>) -> Rc<FunctionDeclaration> {
+    match Rc::try_unwrap(declaration) {
+        Ok(declaration) => Rc::new(FunctionDeclaration {
+            parameters: declaration
+                .parameters
+                .into_iter()
+                .map(|parameter| Parameter {
+                    default: parameter.default.map(optimize_expression),
+                    ..parameter
+                })
+                .collect(),
+            body: optimize(declaration.body),
+            ..declaration
+        }),
+        Err(declaration) => declaration,
+    }
+}
+
+// `x * 1`, `1 * x` and `x - 0` are reduced to `x`, but only when `x` is known to produce a number:
+// on any other type the original operation is a runtime error and has to stay one. Only the
+// integer literals count, since `x * 1.0` turns an integer `x` into a float. `x / 1` does too, as
+// `/` always divides as floats, and `x + 0` is left alone because `-0.0 + 0` is `0` and would print
+// differently.
+fn simplify_binary(left: Expression, operator: Token, right: Expression) -> Expression {
+    let is_one = |expression: &Expression| is_int(expression, 1);
+    let is_zero = |expression: &Expression| is_int(expression, 0);
+
+    match operator.get_token_type() {
+        TokenType::Star if is_one(&right) && is_numeric(&left) => left,
+        TokenType::Star if is_one(&left) && is_numeric(&right) => right,
+        TokenType::Minus if is_zero(&right) && is_numeric(&left) => left,
+        _ => Expression::Binary {
+            left: Box::from(left),
+            operator,
+            right: Box::from(right),
+        },
+    }
+}
+
+fn is_int(expression: &Expression, expected: i64) -> bool {
+    matches!(
+        expression,
+        Expression::Literal { literal_value: Literal::Int(value), .. } if *value == expected
+    )
+}
+
+fn is_numeric(expression: &Expression) -> bool {
+    match expression {
+        Expression::Literal { literal_value, .. } => {
+            matches!(literal_value, Literal::Number(_) | Literal::Int(_))
+        }
+        Expression::Grouping { expression } => is_numeric(expression),
+        Expression::Unary { operator, .. } => matches!(
+            operator.get_token_type(),
+            TokenType::Minus | TokenType::Tilde
+        ),
+        Expression::Binary { operator, .. } => matches!(
+            operator.get_token_type(),
+            TokenType::Minus
+                | TokenType::Star
+                | TokenType::Slash
+                | TokenType::Percent
+                | TokenType::TildeSlash
+                | TokenType::StarStar
+                | TokenType::Ampersand
+                | TokenType::Pipe
+                | TokenType::Caret
+                | TokenType::LessLess
+                | TokenType::GreaterGreater
+        ),
+        _ => false,
+    }
+}
+
+fn empty_block(line: u32) -> Statement {
+    Statement::Block {
+        statements: vec![],
+        line,
+    }
+}
+
+fn is_empty_block(statement: &Statement) -> bool {
+    matches!(statement, Statement::Block { statements, .. } if statements.is_empty())
+}
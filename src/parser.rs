@@ -1,6 +1,13 @@
 use std::fmt;
+use std::rc::Rc;
 
-use crate::{scanner::*, Environment, Statement};
+use crate::{
+    error::RuntimeError,
+    interpreter::stack_exhausted,
+    pattern::{MatchArm, Pattern},
+    scanner::*,
+    FunctionDeclaration, Interpreter, List, Map, Parameter, Statement, Variant,
+};
 
 #[derive(PartialEq, Clone)]
 pub enum Expression {
@@ -18,6 +25,7 @@ pub enum Expression {
     },
     Literal {
         literal_value: Literal,
+        line: u32,
     },
     Var {
         name: Token,
@@ -26,26 +34,193 @@ pub enum Expression {
         name: Token,
         value: Box<Expression>,
     },
+    // `[a, b] = [b, a]`: assigns every name the pattern binds to the matching part of the value.
+    Destructure {
+        pattern: Pattern,
+        equals: Token,
+        value: Box<Expression>,
+    },
+    // `x += 1`; `operator` is the compound token, see `compound_operator`.
+    CompoundAssignment {
+        name: Token,
+        operator: Token,
+        value: Box<Expression>,
+    },
+    // `++x`, `x--`: the prefix form evaluates to the new value, the postfix form to the old one.
+    Increment {
+        name: Token,
+        operator: Token,
+        prefix: bool,
+    },
     Logical {
         left: Box<Expression>,
         operator: Token,
         right: Box<Expression>,
     },
+    // `object.name`, or `object?.name` when `optional`, which is `nil` for a `nil` object.
+    Get {
+        object: Box<Expression>,
+        name: Token,
+        optional: bool,
+    },
+    Conditional {
+        condition: Box<Expression>,
+        question: Token,
+        then_branch: Box<Expression>,
+        else_branch: Box<Expression>,
+    },
+    // `start..end` or `start..end step step`, which evaluates to a `Literal::Range`.
+    Range {
+        start: Box<Expression>,
+        operator: Token,
+        end: Box<Expression>,
+        step: Option<Box<Expression>>,
+    },
+    // `f(1, 2, name: 3)`: named arguments always come after the positional ones.
+    Call {
+        callee: Box<Expression>,
+        paren: Token,
+        arguments: Vec<Expression>,
+        named: Vec<(Token, Expression)>,
+    },
+    List {
+        bracket: Token,
+        elements: Vec<Expression>,
+    },
+    Index {
+        object: Box<Expression>,
+        bracket: Token,
+        index: Box<Expression>,
+    },
+    // Keys are identifier or string tokens, `{name: 1, "full name": 2}`.
+    Map {
+        brace: Token,
+        entries: Vec<(Token, Expression)>,
+    },
+    // `fun (a, b) { ... }` or `(a, b) => a + b`, which evaluate to a closure.
+    Function {
+        declaration: Rc<FunctionDeclaration>,
+    },
+    // `match (subject) { pattern => expression, ... }`, the value of the first arm that applies.
+    Match {
+        keyword: Token,
+        subject: Box<Expression>,
+        arms: Vec<MatchArm<Expression>>,
+    },
+}
+
+// A part of a value still to be written: the value itself, or text between the values nested in
+// it. Nested strings get their quotes back so `["1", 1]` does not print as `[1, 1]`.
+enum Piece<'a> {
+    Value { value: &'a Literal, nested: bool },
+    Text(&'a str),
+    Key(&'a str),
+}
+
+// The parts of a nested value are pushed in reverse, so that they come off in order.
+fn push_values<'a>(pending: &mut Vec<Piece<'a>>, values: &'a [Literal]) {
+    for (index, value) in values.iter().enumerate().rev() {
+        pending.push(Piece::Value {
+            value,
+            nested: true,
+        });
+        if index > 0 {
+            pending.push(Piece::Text(", "));
+        }
+    }
 }
 
 impl fmt::Display for Literal {
+    // Lists, maps and variants nest as deep as a script builds them, so the values inside them are
+    // written from a work list instead of recursively, like they are dropped (see `drop_nested`).
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        match self {
-            Literal::Number(value) => write!(f, "{}", value),
-            Literal::Boolean(value) => write!(f, "{}", value),
-            Literal::Text(value) => write!(f, "{}", value),
-            Literal::Nil => write!(f, "nil"),
+        let mut pending = vec![Piece::Value {
+            value: self,
+            nested: false,
+        }];
+
+        while let Some(piece) = pending.pop() {
+            let value = match piece {
+                Piece::Text(text) => {
+                    f.write_str(text)?;
+                    continue;
+                }
+                Piece::Key(key) => {
+                    write!(f, "\"{}\": ", key)?;
+                    continue;
+                }
+                Piece::Value { value, nested } => match value {
+                    Literal::Text(text) if nested => {
+                        write!(f, "\"{}\"", text)?;
+                        continue;
+                    }
+                    value => value,
+                },
+            };
+
+            match value {
+                Literal::Number(value) => write!(f, "{}", format_number(*value))?,
+                Literal::Int(value) => write!(f, "{}", value)?,
+                Literal::Boolean(value) => write!(f, "{}", value)?,
+                Literal::Text(value) => write!(f, "{}", value)?,
+                Literal::Nil => write!(f, "nil")?,
+                Literal::Error { message, .. } => write!(f, "Error: {}", message)?,
+                Literal::Module(module) => write!(f, "<module {}>", module.path)?,
+                Literal::Native(native) => write!(f, "<native fn {}>", native.name)?,
+                Literal::Function(function) => write!(f, "<fn {}>", function.name())?,
+                Literal::Enum(enumeration) => write!(f, "<enum {}>", enumeration.name)?,
+                Literal::Constructor(enumeration, index) => write!(
+                    f,
+                    "<constructor {}.{}>",
+                    enumeration.name, enumeration.variants[*index].0
+                )?,
+                Literal::Variant(variant) if variant.values.is_empty() => {
+                    write!(f, "{}", variant.name())?
+                }
+                Literal::Variant(variant) => {
+                    write!(f, "{}(", variant.name())?;
+                    pending.push(Piece::Text(")"));
+                    push_values(&mut pending, &variant.values);
+                }
+                Literal::List(elements) => {
+                    f.write_str("[")?;
+                    pending.push(Piece::Text("]"));
+                    push_values(&mut pending, elements);
+                }
+                Literal::Map(entries) => {
+                    f.write_str("{")?;
+                    pending.push(Piece::Text("}"));
+
+                    for (index, (key, value)) in entries.iter().enumerate().rev() {
+                        pending.push(Piece::Value {
+                            value,
+                            nested: true,
+                        });
+                        pending.push(Piece::Key(key));
+                        if index > 0 {
+                            pending.push(Piece::Text(", "));
+                        }
+                    }
+                }
+                Literal::Generator(generator) => write!(f, "<generator {}>", generator.name())?,
+                Literal::GeneratorNext(_) => write!(f, "<fn next>")?,
+                Literal::Lines(lines) => write!(f, "<lines {}>", lines.path)?,
+                Literal::Range {
+                    start,
+                    end,
+                    step: 1,
+                } => write!(f, "{}..{}", start, end)?,
+                Literal::Range { start, end, step } => {
+                    write!(f, "{}..{} step {}", start, end, step)?
+                }
+            }
         }
+
+        Ok(())
     }
 }
 
 impl Expression {
-    #[cfg(test)]
     pub fn to_custom_string(&self) -> String {
         match self {
             Expression::Grouping { expression } => {
@@ -66,15 +241,39 @@ impl Expression {
             Expression::Unary { operator, right } => {
                 format!("({} {})", operator.lexeme(), right.to_custom_string())
             }
-            Expression::Literal { literal_value } => {
-                format!("{}", literal_value.to_custom_string())
-            }
+            Expression::Literal { literal_value, .. } => literal_value.to_custom_string(),
             Expression::Var { name } => {
                 format!("(var {})", name.lexeme())
             }
             Expression::Assignment { name, value } => {
                 format!("{}={}", name.lexeme(), value.to_custom_string())
             }
+            Expression::Destructure { value, .. } => {
+                format!("(destructure {})", value.to_custom_string())
+            }
+            Expression::CompoundAssignment {
+                name,
+                operator,
+                value,
+            } => {
+                format!(
+                    "({} {} {})",
+                    operator.lexeme(),
+                    name.lexeme(),
+                    value.to_custom_string()
+                )
+            }
+            Expression::Increment {
+                name,
+                operator,
+                prefix,
+            } => {
+                if *prefix {
+                    format!("({} {})", operator.lexeme(), name.lexeme())
+                } else {
+                    format!("({} {})", name.lexeme(), operator.lexeme())
+                }
+            }
             Expression::Logical {
                 left,
                 operator,
@@ -87,225 +286,1496 @@ impl Expression {
                     right.to_custom_string()
                 )
             }
+            Expression::Get {
+                object,
+                name,
+                optional,
+            } => {
+                format!(
+                    "({} {} {})",
+                    if *optional { "?." } else { "." },
+                    object.to_custom_string(),
+                    name.lexeme()
+                )
+            }
+            Expression::Conditional {
+                condition,
+                then_branch,
+                else_branch,
+                ..
+            } => {
+                format!(
+                    "(?: {} {} {})",
+                    condition.to_custom_string(),
+                    then_branch.to_custom_string(),
+                    else_branch.to_custom_string()
+                )
+            }
+            Expression::Range {
+                start, end, step, ..
+            } => match step {
+                Some(step) => format!(
+                    "(.. {} {} {})",
+                    start.to_custom_string(),
+                    end.to_custom_string(),
+                    step.to_custom_string()
+                ),
+                None => format!(
+                    "(.. {} {})",
+                    start.to_custom_string(),
+                    end.to_custom_string()
+                ),
+            },
+            Expression::Call {
+                callee,
+                arguments,
+                named,
+                ..
+            } => {
+                let arguments: Vec<String> =
+                    arguments
+                        .iter()
+                        .map(|a| a.to_custom_string())
+                        .chain(named.iter().map(|(name, a)| {
+                            format!("{}: {}", name.lexeme(), a.to_custom_string())
+                        }))
+                        .collect();
+
+                format!(
+                    "(call {} {})",
+                    callee.to_custom_string(),
+                    arguments.join(" ")
+                )
+            }
+            Expression::List { elements, .. } => {
+                let elements: Vec<String> = elements.iter().map(|e| e.to_custom_string()).collect();
+
+                format!("(list {})", elements.join(" "))
+            }
+            Expression::Index { object, index, .. } => {
+                format!(
+                    "([] {} {})",
+                    object.to_custom_string(),
+                    index.to_custom_string()
+                )
+            }
+            Expression::Map { entries, .. } => {
+                let entries: Vec<String> = entries
+                    .iter()
+                    .map(|(key, value)| format!("{} {}", key.lexeme(), value.to_custom_string()))
+                    .collect();
+
+                format!("(map {})", entries.join(" "))
+            }
+            Expression::Function { declaration } => {
+                let parameters: Vec<String> = declaration
+                    .parameters
+                    .iter()
+                    .map(|parameter| match (&parameter.default, parameter.rest) {
+                        (_, true) => format!("...{}", parameter.name.lexeme()),
+                        (Some(default), _) => {
+                            format!("{}={}", parameter.name.lexeme(), default.to_custom_string())
+                        }
+                        (None, _) => parameter.name.lexeme().to_string(),
+                    })
+                    .collect();
+
+                format!("(fun {})", parameters.join(" "))
+            }
+            Expression::Match { subject, arms, .. } => {
+                let arms: Vec<String> = arms
+                    .iter()
+                    .map(|arm| match &arm.guard {
+                        Some(guard) => format!(
+                            "(if {} {})",
+                            guard.to_custom_string(),
+                            arm.body.to_custom_string()
+                        ),
+                        None => arm.body.to_custom_string(),
+                    })
+                    .collect();
+
+                format!("(match {} {})", subject.to_custom_string(), arms.join(" "))
+            }
         }
     }
 
-    #[allow(dead_code)]
-    pub fn evaluate(&self, environment: &mut Environment) -> Literal {
+    // Every nested evaluation goes through here, so this is where the nesting depth is checked.
+    pub fn evaluate(&self, interpreter: &mut Interpreter) -> Result<Literal, RuntimeError> {
+        interpreter.enter(|| self.line())?;
+        let result = self.evaluate_expression(interpreter);
+        interpreter.leave();
+
+        result
+    }
+
+    fn evaluate_expression(&self, interpreter: &mut Interpreter) -> Result<Literal, RuntimeError> {
         match self {
-            Expression::Grouping { expression } => expression.evaluate(environment),
+            Expression::Grouping { expression } => expression.evaluate(interpreter),
             Expression::Binary {
                 left,
                 operator,
                 right,
-            } => self.evaluate_binary(left, operator, right, environment),
+            } => self.evaluate_binary(left, operator, right, interpreter),
             Expression::Unary { operator, right } => {
-                self.evaluate_unary(operator, right, environment)
+                self.evaluate_unary(operator, right, interpreter)
             }
-            Expression::Literal { literal_value } => literal_value.clone(),
-            Expression::Var { name } => match environment.get(name.lexeme()) {
-                Some(value) => value.clone(),
-                _ => Literal::Nil,
-            },
+            Expression::Literal { literal_value, .. } => Ok(literal_value.clone()),
+            Expression::Function { declaration } => Ok(interpreter.closure(declaration)),
+            Expression::Match {
+                keyword,
+                subject,
+                arms,
+            } => {
+                let value = subject.evaluate(interpreter)?;
+
+                interpreter.match_arms(&value, arms, keyword.get_line(), |interpreter, body| {
+                    body.evaluate(interpreter)
+                })
+            }
+            Expression::Var { name } => Expression::variable_value(name, interpreter),
             Expression::Assignment { name, value } => {
-                let new_value = value.evaluate(environment);
+                let new_value = value.evaluate(interpreter)?;
 
-                let result = environment.assign(name.lexeme(), new_value.clone());
+                interpreter
+                    .environment()
+                    .assign(name.get_symbol(), new_value.clone())
+                    .map_err(|message| RuntimeError::new(message, name.get_line()))?;
 
-                if result {
-                    return new_value;
+                Ok(new_value)
+            }
+            Expression::Destructure {
+                pattern,
+                equals,
+                value,
+            } => {
+                let new_value = value.evaluate(interpreter)?;
+
+                let mut bindings = vec![];
+                interpreter.destructure(pattern, &new_value, &mut bindings)?;
+
+                for (name, value) in bindings {
+                    interpreter
+                        .environment()
+                        .assign(name, value)
+                        .map_err(|message| RuntimeError::new(message, equals.get_line()))?;
                 }
 
-                panic!("Variable {} has not been defined.", name.lexeme());
+                Ok(new_value)
+            }
+            Expression::CompoundAssignment {
+                name,
+                operator,
+                value,
+            } => {
+                let current = Expression::variable_value(name, interpreter)?;
+                let right = value.evaluate(interpreter)?;
+
+                self.update_variable(
+                    name,
+                    current,
+                    &Expression::compound_operator(operator),
+                    right,
+                    interpreter,
+                )
+            }
+            Expression::Increment {
+                name,
+                operator,
+                prefix,
+            } => {
+                let current = Expression::variable_value(name, interpreter)?;
+
+                let step = match operator.get_token_type() {
+                    TokenType::PlusPlus => TokenType::Plus,
+                    _ => TokenType::Minus,
+                };
+                let step = Token::new(
+                    step,
+                    operator.lexeme()[..1].to_string(),
+                    Literal::Nil,
+                    operator.get_line(),
+                );
+
+                let updated = self.update_variable(
+                    name,
+                    current.clone(),
+                    &step,
+                    Literal::Int(1),
+                    interpreter,
+                )?;
+
+                Ok(if *prefix { updated } else { current })
             }
             Expression::Logical {
                 left,
                 operator,
                 right,
             } => {
-                let left_value = left.evaluate(environment);
+                let left_value = left.evaluate(interpreter)?;
 
                 match operator.get_token_type() {
-                    TokenType::Or => {
-                        if left_value == Literal::Boolean(true) {
-                            return left_value;
-                        }
+                    TokenType::Or if left_value == Literal::Boolean(true) => {
+                        return Ok(left_value);
                     }
-                    TokenType::And => {
-                        if left_value == Literal::Boolean(false) {
-                            return left_value;
-                        }
+                    TokenType::And if left_value == Literal::Boolean(false) => {
+                        return Ok(left_value);
+                    }
+                    TokenType::QuestionQuestion if left_value != Literal::Nil => {
+                        return Ok(left_value);
                     }
                     _ => (),
                 }
 
-                right.evaluate(environment)
+                right.evaluate(interpreter)
             }
-        }
-    }
+            Expression::Get {
+                object,
+                name,
+                optional,
+            } => {
+                let object_value = object.evaluate(interpreter)?;
 
-    fn evaluate_binary(
-        &self,
-        left: &Expression,
-        token: &Token,
-        right: &Expression,
-        environment: &mut Environment,
-    ) -> Literal {
-        let left_expression = left.evaluate(environment);
-        let right_expression = right.evaluate(environment);
-
-        match (&left_expression, token.get_token_type(), &right_expression) {
-            (Literal::Number(l), TokenType::Minus, Literal::Number(r)) => Literal::Number(l - r),
-            (Literal::Number(l), TokenType::Plus, Literal::Number(r)) => Literal::Number(l + r),
-            (Literal::Number(l), TokenType::Star, Literal::Number(r)) => Literal::Number(l * r),
-            (Literal::Number(l), TokenType::Slash, Literal::Number(r)) => {
-                if *r == 0.0 {
-                    panic!("Can't divide by zero");
-                }
-                Literal::Number(l / r)
-            }
-            (Literal::Number(l), TokenType::Greater, Literal::Number(r)) => Literal::Boolean(l > r),
-            (Literal::Number(l), TokenType::GreaterEqual, Literal::Number(r)) => {
-                Literal::Boolean(l >= r)
+                if *optional && object_value == Literal::Nil {
+                    return Ok(Literal::Nil);
+                }
+
+                Expression::get_property(object_value, name)
             }
-            (Literal::Number(l), TokenType::Less, Literal::Number(r)) => Literal::Boolean(l < r),
-            (Literal::Number(l), TokenType::LessEqual, Literal::Number(r)) => {
-                Literal::Boolean(l <= r)
+            Expression::Conditional {
+                condition,
+                then_branch,
+                else_branch,
+                ..
+            } => {
+                if condition.evaluate(interpreter)? == Literal::Boolean(true) {
+                    then_branch.evaluate(interpreter)
+                } else {
+                    else_branch.evaluate(interpreter)
+                }
             }
-            (Literal::Text(l), TokenType::Plus, Literal::Text(r)) => {
-                Literal::Text(format!("{}{}", l, r))
+            Expression::Range {
+                start,
+                operator,
+                end,
+                step,
+            } => {
+                let mut bound = |expression: &Expression| {
+                    let value = expression.evaluate(interpreter)?;
+
+                    value.as_whole_number().ok_or_else(|| {
+                        RuntimeError::new(
+                            format!("Range bounds must be whole numbers, got {}.", value),
+                            operator.get_line(),
+                        )
+                    })
+                };
+
+                let start = bound(start)?;
+                let end = bound(end)?;
+                let step = match step {
+                    Some(step) => bound(step)?,
+                    None => 1,
+                };
+
+                if step == 0 {
+                    return Err(RuntimeError::new(
+                        "Range step can't be 0.".to_string(),
+                        operator.get_line(),
+                    ));
+                }
+
+                Ok(Literal::Range { start, end, step })
             }
-            (_, TokenType::EqualEqual, _) => {
-                Literal::Boolean(self.is_equal(left_expression, right_expression))
+            Expression::Call {
+                callee,
+                paren,
+                arguments,
+                named,
+            } => self.evaluate_call(callee, paren, arguments, named, interpreter),
+            Expression::List { bracket, elements } => {
+                let mut values = Vec::with_capacity(elements.len());
+                for element in elements {
+                    values.push(element.evaluate(interpreter)?);
+                }
+
+                let list = Literal::List(Rc::new(List::from(values)));
+                interpreter.allocate(list.allocated_bytes(false), bracket.get_line())?;
+
+                Ok(list)
             }
-            (_, TokenType::BangEqual, _) => {
-                Literal::Boolean(!self.is_equal(left_expression, right_expression))
+            Expression::Index {
+                object,
+                bracket,
+                index,
+            } => {
+                let object_value = object.evaluate(interpreter)?;
+                let index_value = index.evaluate(interpreter)?;
+
+                Expression::index(&object_value, &index_value)
+                    .map_err(|message| RuntimeError::new(message, bracket.get_line()))
             }
-            _ => panic!(
-                "Unsupported binary operation: {} {} {}",
-                left_expression.to_custom_string(),
-                token.lexeme(),
-                right_expression.to_custom_string()
-            ),
-        }
-    }
+            Expression::Map { brace, entries } => {
+                let mut values = Map::with_capacity(entries.len());
+                for (key, value) in entries {
+                    let value = value.evaluate(interpreter)?;
+                    values.insert(Expression::map_key(key), value);
+                }
 
-    fn evaluate_unary(
-        &self,
-        token: &Token,
-        expression: &Expression,
-        environment: &mut Environment,
-    ) -> Literal {
-        let right_expression = expression.evaluate(environment);
+                let map = Literal::Map(Rc::new(values));
+                interpreter.allocate(map.allocated_bytes(false), brace.get_line())?;
 
-        match (token.get_token_type(), right_expression) {
-            (TokenType::Minus, Literal::Number(value)) => Literal::Number(-value),
-            _ => todo!(),
+                Ok(map)
+            }
         }
     }
 
-    fn is_equal(&self, left: Literal, right: Literal) -> bool {
-        match (left, right) {
-            (Literal::Number(l), Literal::Number(r)) => l == r,
-            (Literal::Text(l), Literal::Text(r)) => l == r,
-            (Literal::Boolean(l), Literal::Boolean(r)) => l == r,
-            (Literal::Nil, Literal::Nil) => true,
-            _ => {
-                panic!("Can't compare 2 different types");
+    // The line of the first token in the expression, for errors that are not tied to a single
+    // operator. Expressions made only of literals carry no line and report 0.
+    pub fn line(&self) -> u32 {
+        match self {
+            Expression::Binary { operator, .. }
+            | Expression::Unary { operator, .. }
+            | Expression::Logical { operator, .. } => operator.get_line(),
+            Expression::Var { name }
+            | Expression::Assignment { name, .. }
+            | Expression::CompoundAssignment { name, .. }
+            | Expression::Increment { name, .. }
+            | Expression::Get { name, .. } => name.get_line(),
+            Expression::Conditional { question, .. } => question.get_line(),
+            Expression::Range { operator, .. } => operator.get_line(),
+            Expression::Destructure { equals, .. } => equals.get_line(),
+            Expression::Call { paren, .. } => paren.get_line(),
+            Expression::List { bracket, .. } | Expression::Index { bracket, .. } => {
+                bracket.get_line()
             }
+            Expression::Map { brace, .. } => brace.get_line(),
+            Expression::Function { declaration } => declaration.keyword.get_line(),
+            Expression::Match { keyword, .. } => keyword.get_line(),
+            Expression::Grouping { expression } => expression.line(),
+            Expression::Literal { line, .. } => *line,
         }
     }
-}
 
-pub struct Parser {
-    tokens: Vec<Token>,
-    current: usize,
-}
+    fn evaluate_call(
+        &self,
+        callee: &Expression,
+        paren: &Token,
+        arguments: &[Expression],
+        named: &[(Token, Expression)],
+        interpreter: &mut Interpreter,
+    ) -> Result<Literal, RuntimeError> {
+        let callee_value = match callee {
+            // `object?.method()` skips the call, arguments included, when the object is `nil`.
+            Expression::Get {
+                object,
+                name,
+                optional: true,
+            } => {
+                let object_value = object.evaluate(interpreter)?;
 
-impl Parser {
-    pub fn new(tokens: Vec<Token>) -> Self {
-        Self { tokens, current: 0 }
-    }
+                if object_value == Literal::Nil {
+                    return Ok(Literal::Nil);
+                }
 
-    pub fn parse_expression(&mut self) -> Expression {
-        self.expression()
-    }
+                Expression::get_property(object_value, name)?
+            }
+            _ => callee.evaluate(interpreter)?,
+        };
 
-    pub fn parse(&mut self) -> Vec<Statement> {
-        let mut statements: Vec<Statement> = vec![];
+        let mut argument_values = Vec::with_capacity(arguments.len());
+        for argument in arguments {
+            argument_values.push(argument.evaluate(interpreter)?);
+        }
 
-        while !self.is_at_end() {
-            statements.push(self.declaration());
+        let mut named_values = Vec::with_capacity(named.len());
+        for (name, argument) in named {
+            named_values.push((name.clone(), argument.evaluate(interpreter)?));
         }
 
-        statements
-    }
+        let native = match callee_value {
+            Literal::Native(native) if named_values.is_empty() => native,
+            Literal::Native(native) => {
+                return Err(RuntimeError::new(
+                    format!("{}() doesn't take named arguments.", native.name),
+                    paren.get_line(),
+                ))
+            }
+            Literal::Constructor(enumeration, index) if named_values.is_empty() => {
+                let (variant, fields) = &enumeration.variants[index];
+
+                if argument_values.len() != fields.len() {
+                    return Err(RuntimeError::new(
+                        format!(
+                            "{}.{}() expects {} arguments but got {}.",
+                            enumeration.name,
+                            variant,
+                            fields.len(),
+                            argument_values.len()
+                        ),
+                        paren.get_line(),
+                    ));
+                }
 
-    fn declaration(&mut self) -> Statement {
-        if self.match_any(&[TokenType::Var]) {
-            return self.var_declaration();
+                return Ok(Literal::Variant(Rc::new(Variant {
+                    enumeration,
+                    index,
+                    values: argument_values,
+                })));
+            }
+            Literal::Constructor(enumeration, index) => {
+                return Err(RuntimeError::new(
+                    format!(
+                        "{}.{}() doesn't take named arguments.",
+                        enumeration.name, enumeration.variants[index].0
+                    ),
+                    paren.get_line(),
+                ))
+            }
+            // `{value, done}`, where `done` is only true once the body has run to its end, and
+            // `value` is then `nil`.
+            Literal::GeneratorNext(generator) if arguments.is_empty() && named.is_empty() => {
+                let value = interpreter.resume(&generator, paren.get_line())?;
+
+                let result = Literal::Map(Rc::new(Map::from_iter([
+                    ("value".to_string(), value.clone().unwrap_or(Literal::Nil)),
+                    ("done".to_string(), Literal::Boolean(value.is_none())),
+                ])));
+                interpreter.allocate(result.allocated_bytes(false), paren.get_line())?;
+
+                return Ok(result);
+            }
+            Literal::GeneratorNext(_) => {
+                return Err(RuntimeError::new(
+                    "next() doesn't take arguments.".to_string(),
+                    paren.get_line(),
+                ))
+            }
+            Literal::Function(function) => {
+                return interpreter.call_function(
+                    &function,
+                    argument_values,
+                    named_values,
+                    paren.get_line(),
+                )
+            }
+            _ => {
+                return Err(RuntimeError::new(
+                    format!("Can only call functions, got {}.", callee_value),
+                    paren.get_line(),
+                ))
+            }
+        };
+
+        let count = argument_values.len();
+        if count < native.min_arity || count > native.max_arity {
+            let expected = if native.min_arity == native.max_arity {
+                native.min_arity.to_string()
+            } else {
+                format!("{} to {}", native.min_arity, native.max_arity)
+            };
+
+            return Err(RuntimeError::new(
+                format!(
+                    "{}() expects {} arguments but got {}.",
+                    native.name, expected, count
+                ),
+                paren.get_line(),
+            ));
         }
 
-        self.statement()
+        let value = (native.function)(interpreter, &argument_values)
+            .map_err(|message| RuntimeError::new(message, paren.get_line()))?;
+
+        // What a native returns is built from scratch, down to the lists and maps inside it.
+        interpreter.allocate(value.allocated_bytes(true), paren.get_line())?;
+
+        Ok(value)
     }
 
-    fn var_declaration(&mut self) -> Statement {
-        let token = self
-            .consume(&TokenType::Identifier, "Expect variable name.".to_string())
-            .clone();
+    pub fn map_key(key: &Token) -> String {
+        match key.get_literal() {
+            Literal::Text(text) => text.clone(),
+            _ => key.lexeme().to_string(),
+        }
+    }
 
-        let iniatilizer = if self.match_any(&[TokenType::Equal]) {
-            self.parse_expression()
-        } else {
-            Expression::Literal {
-                literal_value: Literal::Nil,
+    // Strings are indexed by character, not by byte, so `"héllo"[1]` is `"é"`. Looking up a key
+    // that a map does not have gives `nil`.
+    fn index(object: &Literal, index: &Literal) -> Result<Literal, String> {
+        if let Literal::Map(entries) = object {
+            return match index {
+                Literal::Text(key) => Ok(entries.get(key).cloned().unwrap_or(Literal::Nil)),
+                _ => Err(format!("Map keys are strings, got {}.", index)),
+            };
+        }
+
+        let length = match object {
+            Literal::Text(text) => text.chars().count(),
+            Literal::List(elements) => elements.len(),
+            _ => {
+                return Err(format!(
+                    "Only strings, lists and maps can be indexed, got {}.",
+                    object
+                ))
             }
         };
 
-        self.consume(
-            &TokenType::Semicolon,
-            "Expect ';' after expression.".to_string(),
-        );
+        let position = match index.as_whole_number() {
+            Some(n) if n >= 0 && (n as usize) < length => n as usize,
+            _ => {
+                return Err(format!(
+                    "Index {} is out of range for a length of {}.",
+                    index, length
+                ))
+            }
+        };
 
-        Statement::Var {
-            token: (token),
-            expression: (iniatilizer),
+        match object {
+            Literal::Text(text) => Ok(Literal::Text(
+                text.chars().nth(position).unwrap_or_default().to_string(),
+            )),
+            Literal::List(elements) => Ok(elements[position].clone()),
+            _ => unreachable!(),
         }
     }
 
-    fn statement(&mut self) -> Statement {
-        if self.match_any(&[TokenType::If]) {
-            return self.if_statement();
+    fn get_property(object_value: Literal, name: &Token) -> Result<Literal, RuntimeError> {
+        if let Literal::Module(module) = &object_value {
+            return match module.environment.get(name.get_symbol()) {
+                Some(value) => Ok(value),
+                None => Err(RuntimeError::new(
+                    format!(
+                        "Module '{}' has no definition named '{}'.",
+                        module.path,
+                        name.lexeme()
+                    ),
+                    name.get_line(),
+                )),
+            };
         }
 
-        if self.match_any(&[TokenType::Print]) {
-            return self.print_statement();
+        match (&object_value, name.lexeme()) {
+            (Literal::Error { message, .. }, "message") => Ok(Literal::Text(message.clone())),
+            (Literal::Error { line, .. }, "line") => Ok(Literal::Int(i64::from(*line))),
+            (Literal::Error { .. }, _) => Err(RuntimeError::new(
+                format!("Undefined property '{}'.", name.lexeme()),
+                name.get_line(),
+            )),
+            (Literal::Enum(enumeration), variant) => {
+                match enumeration.variants.iter().position(|(v, _)| v == variant) {
+                    Some(index) if enumeration.variants[index].1.is_empty() => {
+                        Ok(Literal::Variant(Rc::new(Variant {
+                            enumeration: Rc::clone(enumeration),
+                            index,
+                            values: vec![],
+                        })))
+                    }
+                    Some(index) => Ok(Literal::Constructor(Rc::clone(enumeration), index)),
+                    None => Err(RuntimeError::new(
+                        format!("Enum {} has no variant '{}'.", enumeration.name, variant),
+                        name.get_line(),
+                    )),
+                }
+            }
+            (Literal::Generator(generator), "next") => {
+                Ok(Literal::GeneratorNext(Rc::clone(generator)))
+            }
+            (Literal::Generator(generator), _) => Err(RuntimeError::new(
+                format!(
+                    "Generator {}() has no property '{}'.",
+                    generator.name(),
+                    name.lexeme()
+                ),
+                name.get_line(),
+            )),
+            (Literal::Variant(variant), field) => {
+                match variant.fields().iter().position(|f| f == field) {
+                    Some(index) => Ok(variant.values[index].clone()),
+                    None => Err(RuntimeError::new(
+                        format!("{} has no field '{}'.", variant.name(), field),
+                        name.get_line(),
+                    )),
+                }
+            }
+            _ => Err(RuntimeError::new(
+                format!(
+                    "Only errors, modules, enums, variants and generators have properties, got {}.",
+                    object_value
+                ),
+                name.get_line(),
+            )),
         }
+    }
 
-        if self.match_any(&[TokenType::LeftBrace]) {
-            return self.block_statement();
+    fn variable_value(
+        name: &Token,
+        interpreter: &mut Interpreter,
+    ) -> Result<Literal, RuntimeError> {
+        match interpreter.environment().get(name.get_symbol()) {
+            Some(value) => Ok(value.clone()),
+            _ => Err(RuntimeError::new(
+                format!("Undefined variable '{}'.", name.lexeme()),
+                name.get_line(),
+            )),
         }
+    }
 
-        self.expression_statement()
+    // Stores `current <operator> right` into the variable, which has already been read, so the
+    // target is only looked up once.
+    fn update_variable(
+        &self,
+        name: &Token,
+        current: Literal,
+        operator: &Token,
+        right: Literal,
+        interpreter: &mut Interpreter,
+    ) -> Result<Literal, RuntimeError> {
+        let value = Expression::binary_operation(current, operator, right)
+            .map_err(|message| RuntimeError::new(message, operator.get_line()))?;
+
+        interpreter.allocate(value.allocated_bytes(false), operator.get_line())?;
+
+        interpreter
+            .environment()
+            .assign(name.get_symbol(), value.clone())
+            .map_err(|message| RuntimeError::new(message, name.get_line()))?;
+
+        Ok(value)
     }
 
-    fn if_statement(&mut self) -> Statement {
-        self.consume(&TokenType::LeftParen, "Expect '(' after 'if'.".to_string());
+    // The binary operator a compound assignment applies, `+` for `+=`.
+    pub fn compound_operator(operator: &Token) -> Token {
+        let token_type = match operator.get_token_type() {
+            TokenType::PlusEqual => TokenType::Plus,
+            TokenType::MinusEqual => TokenType::Minus,
+            TokenType::StarEqual => TokenType::Star,
+            TokenType::SlashEqual => TokenType::Slash,
+            _ => TokenType::Percent,
+        };
+        let lexeme = operator.lexeme().trim_end_matches('=');
+
+        Token::new(
+            token_type,
+            lexeme.to_string(),
+            Literal::Nil,
+            operator.get_line(),
+        )
+    }
 
-        let condition = self.expression();
+    fn evaluate_binary(
+        &self,
+        left: &Expression,
+        token: &Token,
+        right: &Expression,
+        interpreter: &mut Interpreter,
+    ) -> Result<Literal, RuntimeError> {
+        let left_expression = left.evaluate(interpreter)?;
+        let right_expression = right.evaluate(interpreter)?;
 
-        self.consume(
-            &TokenType::RightParen,
-            "Expect ')' after if condition.".to_string(),
-        );
+        let value = Expression::binary_operation(left_expression, token, right_expression)
+            .map_err(|message| RuntimeError::new(message, token.get_line()))?;
 
-        let then = self.statement();
+        interpreter.allocate(value.allocated_bytes(false), token.get_line())?;
 
-        if self.match_any(&[TokenType::Else]) {
-            Statement::IfElse {
-                condition,
-                then_statement: (Box::from(then)),
-                else_stament: (Box::from(self.statement())),
+        Ok(value)
+    }
+
+    // Applies a binary operator to two already evaluated operands. Kept free of any environment
+    // access so the optimizer can fold constants with exactly the same semantics.
+    pub fn binary_operation(
+        left_expression: Literal,
+        token: &Token,
+        right_expression: Literal,
+    ) -> Result<Literal, String> {
+        if let (Literal::Int(l), Literal::Int(r)) = (&left_expression, &right_expression) {
+            if let Some(result) = Expression::int_operation(*l, token, *r) {
+                return result;
+            }
+        }
+
+        if let (Some(l), Some(r)) = (left_expression.as_float(), right_expression.as_float()) {
+            if let Some(result) = Expression::float_operation(l, token, r) {
+                return result;
+            }
+        }
+
+        let value = match (&left_expression, token.get_token_type(), &right_expression) {
+            (Literal::Text(l), TokenType::Plus, Literal::Text(r)) => {
+                Literal::Text(format!("{}{}", l, r))
+            }
+            // Strings compare lexicographically by code point.
+            (Literal::Text(l), TokenType::Greater, Literal::Text(r)) => Literal::Boolean(l > r),
+            (Literal::Text(l), TokenType::GreaterEqual, Literal::Text(r)) => {
+                Literal::Boolean(l >= r)
+            }
+            (Literal::Text(l), TokenType::Less, Literal::Text(r)) => Literal::Boolean(l < r),
+            (Literal::Text(l), TokenType::LessEqual, Literal::Text(r)) => Literal::Boolean(l <= r),
+            (_, TokenType::EqualEqual, _) => {
+                Literal::Boolean(Expression::is_equal(left_expression, right_expression)?)
+            }
+            (_, TokenType::BangEqual, _) => {
+                Literal::Boolean(!Expression::is_equal(left_expression, right_expression)?)
+            }
+            (_, TokenType::Is, _) => Literal::Boolean(Expression::is_instance(
+                &left_expression,
+                &right_expression,
+            )?),
+            _ => {
+                return Err(format!(
+                    "Unsupported binary operation: {} {} {}",
+                    left_expression.to_custom_string(),
+                    token.lexeme(),
+                    right_expression.to_custom_string()
+                ))
+            }
+        };
+
+        Ok(value)
+    }
+
+    // Integer arithmetic is checked: a result that does not fit in an i64 is an error rather than
+    // wrapping or silently turning into a float. The bitwise operators work on the two's
+    // complement bits instead, so `<<` drops what it shifts out and `>>` keeps the sign. `/` is not handled here, it always divides as
+    // floats so `10 / 4` is 2.5; `~/` is the integer division. Both it and `%` truncate toward
+    // zero, so `a == (a ~/ b) * b + a % b`.
+    fn int_operation(l: i64, token: &Token, r: i64) -> Option<Result<Literal, String>> {
+        let checked = match token.get_token_type() {
+            TokenType::Plus => l.checked_add(r),
+            TokenType::Minus => l.checked_sub(r),
+            TokenType::Star => l.checked_mul(r),
+            TokenType::Percent | TokenType::TildeSlash if r == 0 => {
+                return Some(Err("Can't divide by zero".to_string()))
+            }
+            TokenType::Percent => l.checked_rem(r),
+            TokenType::TildeSlash => l.checked_div(r),
+            // A negative exponent makes a fraction, so it is left to the float version.
+            TokenType::StarStar if r < 0 => return None,
+            TokenType::StarStar => u32::try_from(r).ok().and_then(|r| l.checked_pow(r)),
+            TokenType::Ampersand => Some(l & r),
+            TokenType::Pipe => Some(l | r),
+            TokenType::Caret => Some(l ^ r),
+            TokenType::LessLess | TokenType::GreaterGreater if !(0..64).contains(&r) => {
+                return Some(Err(format!("Shift amount out of range: {}", r)))
+            }
+            TokenType::LessLess => Some(l << r),
+            TokenType::GreaterGreater => Some(l >> r),
+            TokenType::Greater => return Some(Ok(Literal::Boolean(l > r))),
+            TokenType::GreaterEqual => return Some(Ok(Literal::Boolean(l >= r))),
+            TokenType::Less => return Some(Ok(Literal::Boolean(l < r))),
+            TokenType::LessEqual => return Some(Ok(Literal::Boolean(l <= r))),
+            _ => return None,
+        };
+
+        Some(
+            checked
+                .map(Literal::Int)
+                .ok_or_else(|| format!("Integer overflow: {} {} {}", l, token.lexeme(), r)),
+        )
+    }
+
+    // Any arithmetic with at least one float operand. `~/` truncates the float quotient.
+    fn float_operation(l: f64, token: &Token, r: f64) -> Option<Result<Literal, String>> {
+        let value = match token.get_token_type() {
+            TokenType::Plus => Literal::Number(l + r),
+            TokenType::Minus => Literal::Number(l - r),
+            TokenType::Star => Literal::Number(l * r),
+            TokenType::Slash | TokenType::Percent | TokenType::TildeSlash if r == 0.0 => {
+                return Some(Err("Can't divide by zero".to_string()))
+            }
+            TokenType::Slash => Literal::Number(l / r),
+            TokenType::Percent => Literal::Number(l % r),
+            TokenType::TildeSlash => Literal::Number((l / r).trunc()),
+            TokenType::StarStar => Literal::Number(l.powf(r)),
+            TokenType::Greater => Literal::Boolean(l > r),
+            TokenType::GreaterEqual => Literal::Boolean(l >= r),
+            TokenType::Less => Literal::Boolean(l < r),
+            TokenType::LessEqual => Literal::Boolean(l <= r),
+            _ => return None,
+        };
+
+        Some(Ok(value))
+    }
+
+    fn evaluate_unary(
+        &self,
+        token: &Token,
+        expression: &Expression,
+        interpreter: &mut Interpreter,
+    ) -> Result<Literal, RuntimeError> {
+        let right_expression = expression.evaluate(interpreter)?;
+
+        match (token.get_token_type(), right_expression) {
+            (TokenType::Minus, Literal::Number(value)) => Ok(Literal::Number(-value)),
+            (TokenType::Tilde, Literal::Int(value)) => Ok(Literal::Int(!value)),
+            (TokenType::Minus, Literal::Int(value)) => match value.checked_neg() {
+                Some(value) => Ok(Literal::Int(value)),
+                None => Err(RuntimeError::new(
+                    format!("Integer overflow: -{}", value),
+                    token.get_line(),
+                )),
+            },
+            (_, right_expression) => Err(RuntimeError::new(
+                format!(
+                    "Unsupported unary operation: {}{}",
+                    token.lexeme(),
+                    right_expression.to_custom_string()
+                ),
+                token.get_line(),
+            )),
+        }
+    }
+
+    // `value is Shape` or `value is Shape.Circle`; `Shape.Empty`, a variant without fields, stands
+    // for its own variant. Values that are not variants are simply not instances.
+    fn is_instance(value: &Literal, of: &Literal) -> Result<bool, String> {
+        let (enumeration, index) = match of {
+            Literal::Enum(enumeration) => (enumeration, None),
+            Literal::Constructor(enumeration, index) => (enumeration, Some(*index)),
+            Literal::Variant(variant) if variant.values.is_empty() => {
+                (&variant.enumeration, Some(variant.index))
+            }
+            _ => {
+                return Err(format!(
+                    "Right side of 'is' must be an enum or a variant, got {}.",
+                    of
+                ))
+            }
+        };
+
+        Ok(match value {
+            Literal::Variant(variant) => {
+                Rc::ptr_eq(&variant.enumeration, enumeration)
+                    && index.is_none_or(|index| index == variant.index)
+            }
+            _ => false,
+        })
+    }
+
+    // Lists, maps and variants are compared from a work list of the pairs of values inside them
+    // that are left to compare, so that deeply nested ones don't overflow the native stack.
+    pub fn is_equal(left: Literal, right: Literal) -> Result<bool, String> {
+        let mut pending = vec![];
+
+        if !Expression::is_shallow_equal(left, right, &mut pending)? {
+            return Ok(false);
+        }
+
+        // Nested values of different types are simply unequal rather than an error.
+        while let Some((left, right)) = pending.pop() {
+            if !Expression::is_shallow_equal(left, right, &mut pending).unwrap_or(false) {
+                return Ok(false);
+            }
+        }
+
+        Ok(true)
+    }
+
+    // Compares two values without looking inside lists, maps and variants, whose values are added
+    // to `pending` instead.
+    fn is_shallow_equal(
+        left: Literal,
+        right: Literal,
+        pending: &mut Vec<(Literal, Literal)>,
+    ) -> Result<bool, String> {
+        match (left, right) {
+            (Literal::Number(l), Literal::Number(r)) => Ok(l == r),
+            (Literal::Int(l), Literal::Int(r)) => Ok(l == r),
+            // `1 == 1.0`: numbers compare by value whatever their representation. Converting the
+            // integer would round it, `9007199254740993 == 9007199254740992.0` has to stay false, so
+            // a whole float in range is converted instead. 2^63 is the first one past `i64::MAX`.
+            (Literal::Int(l), Literal::Number(r)) | (Literal::Number(r), Literal::Int(l)) => {
+                Ok(r.fract() == 0.0
+                    && (-9223372036854775808.0..9223372036854775808.0).contains(&r)
+                    && r as i64 == l)
+            }
+            (Literal::Text(l), Literal::Text(r)) => Ok(l == r),
+            (Literal::Boolean(l), Literal::Boolean(r)) => Ok(l == r),
+            (Literal::Nil, Literal::Nil) => Ok(true),
+            (left @ Literal::Error { .. }, right @ Literal::Error { .. }) => Ok(left == right),
+            (Literal::Module(l), Literal::Module(r)) => Ok(Rc::ptr_eq(&l, &r)),
+            (Literal::Native(l), Literal::Native(r)) => Ok(l == r),
+            (Literal::Function(l), Literal::Function(r)) => Ok(Rc::ptr_eq(&l, &r)),
+            (Literal::Enum(l), Literal::Enum(r)) => Ok(Rc::ptr_eq(&l, &r)),
+            (Literal::Generator(l), Literal::Generator(r))
+            | (Literal::GeneratorNext(l), Literal::GeneratorNext(r)) => Ok(Rc::ptr_eq(&l, &r)),
+            (Literal::Lines(l), Literal::Lines(r)) => Ok(Rc::ptr_eq(&l, &r)),
+            (Literal::Constructor(l, i), Literal::Constructor(r, j)) => {
+                Ok(Rc::ptr_eq(&l, &r) && i == j)
+            }
+            // Variants are equal when they come from the same declaration and variant and their
+            // fields are equal.
+            (Literal::Variant(l), Literal::Variant(r)) => {
+                if !Rc::ptr_eq(&l.enumeration, &r.enumeration) || l.index != r.index {
+                    return Ok(false);
+                }
+
+                pending.extend(l.values.iter().cloned().zip(r.values.iter().cloned()));
+                Ok(true)
+            }
+            // Lists are equal when their elements are.
+            (Literal::List(l), Literal::List(r)) => {
+                if l.len() != r.len() {
+                    return Ok(false);
+                }
+
+                pending.extend(l.iter().cloned().zip(r.iter().cloned()));
+                Ok(true)
+            }
+            // Maps are equal when they hold the same keys with equal values, in any order.
+            (Literal::Map(l), Literal::Map(r)) => {
+                if l.len() != r.len() {
+                    return Ok(false);
+                }
+
+                for (key, l) in l.iter() {
+                    match r.get(key) {
+                        Some(r) => pending.push((l.clone(), r.clone())),
+                        None => return Ok(false),
+                    }
+                }
+
+                Ok(true)
+            }
+            (l @ Literal::Range { .. }, r @ Literal::Range { .. }) => Ok(l == r),
+            _ => Err("Can't compare 2 different types".to_string()),
+        }
+    }
+}
+
+// How deep the tree built by the parser may get. Every later pass (the optimizer, the formatter,
+// the interpreter, even dropping the tree) walks it recursively, so deeper input is rejected here
+// rather than overflowing the native stack in one of them. Long operator chains like `1 + 1 + ...`
+// build left-deep trees and count one level per operator.
+pub const MAX_NESTING_DEPTH: usize = 1000;
+
+pub struct Parser {
+    tokens: Vec<Token>,
+    current: usize,
+    depth: usize,
+    // How many function bodies enclose the current token; `return` is only valid inside one.
+    functions: usize,
+    // Set while parsing the guard of a match arm, where `(x) =>` is the end of the guard rather
+    // than an arrow function. Arguments of calls inside the guard may still be arrow functions.
+    guard: bool,
+    // Set once a `yield` is found in the body of the innermost function being parsed.
+    yields: bool,
+    // Set while parsing a destructuring pattern, the only kind whose parts may have defaults.
+    defaults: bool,
+}
+
+impl Parser {
+    pub fn new(tokens: Vec<Token>) -> Self {
+        Self {
+            tokens: hoist_comments(tokens),
+            current: 0,
+            depth: 0,
+            functions: 0,
+            guard: false,
+            yields: false,
+            defaults: false,
+        }
+    }
+
+    pub fn parse_expression(&mut self) -> Expression {
+        self.expression()
+    }
+
+    pub fn parse(&mut self) -> Vec<Statement> {
+        let mut statements: Vec<Statement> = vec![];
+
+        while !self.is_at_end() {
+            statements.push(self.declaration());
+        }
+
+        statements
+    }
+
+    fn declaration(&mut self) -> Statement {
+        if self.match_any(&[TokenType::Comment]) {
+            let token = self.previous().clone();
+            let trailing =
+                self.current > 1 && self.tokens[self.current - 2].get_line() == token.get_line();
+
+            return Statement::Comment { token, trailing };
+        }
+
+        if self.match_any(&[TokenType::BlankLine]) {
+            return Statement::BlankLine {
+                token: self.previous().clone(),
+            };
+        }
+
+        if self.match_any(&[TokenType::Import]) {
+            return self.import_declaration();
+        }
+
+        if self.match_any(&[TokenType::Var]) {
+            return self.var_declaration();
+        }
+
+        if self.match_any(&[TokenType::Const]) {
+            return self.const_declaration();
+        }
+
+        if self.match_any(&[TokenType::Enum]) {
+            return self.enum_declaration();
+        }
+
+        // `fun (` starts an anonymous function, which is an expression statement.
+        if self.check(&TokenType::Fun) && self.check_next(&TokenType::Identifier) {
+            let keyword = self.advance().clone();
+            let name = self.advance().clone();
+
+            return Statement::Function {
+                declaration: (Rc::new(self.function(keyword, Some(name)))),
+            };
+        }
+
+        self.statement()
+    }
+
+    // The parameters and body that follow `fun` or `fun name`.
+    fn function(&mut self, keyword: Token, name: Option<Token>) -> FunctionDeclaration {
+        self.consume(
+            &TokenType::LeftParen,
+            "Expect '(' before parameters.".to_string(),
+        );
+        let parameters = self.parameters();
+
+        self.consume(
+            &TokenType::LeftBrace,
+            "Expect '{' before function body.".to_string(),
+        );
+
+        self.functions += 1;
+        let yields = std::mem::replace(&mut self.yields, false);
+        let Statement::Block { statements, .. } = self.block_statement() else {
+            unreachable!("block_statement always returns a block");
+        };
+        let generator = std::mem::replace(&mut self.yields, yields);
+        self.functions -= 1;
+
+        FunctionDeclaration {
+            keyword: (keyword),
+            name: (name),
+            parameters: (parameters),
+            body: (statements),
+            arrow: (false),
+            generator: (generator),
+        }
+    }
+
+    // `(a, b) => expression`, once the `(` is consumed.
+    fn arrow_function(&mut self) -> FunctionDeclaration {
+        let parameters = self.parameters();
+        let arrow = self
+            .consume(
+                &TokenType::Arrow,
+                "Expect '=>' after parameters.".to_string(),
+            )
+            .clone();
+
+        let body = self.expression();
+
+        FunctionDeclaration {
+            keyword: (arrow.clone()),
+            name: (None),
+            parameters: (parameters),
+            body: (vec![Statement::Return {
+                keyword: (arrow),
+                value: (Some(body)),
+            }]),
+            arrow: (true),
+            generator: (false),
+        }
+    }
+
+    // A comma separated list of parameters up to and including the closing `)`.
+    fn parameters(&mut self) -> Vec<Parameter> {
+        let mut parameters: Vec<Parameter> = vec![];
+
+        if !self.check(&TokenType::RightParen) {
+            loop {
+                if parameters.last().is_some_and(|parameter| parameter.rest) {
+                    panic!(
+                        "The rest parameter must be the last one at line {}.",
+                        self.peek().get_line()
+                    );
+                }
+
+                let rest = self.match_any(&[TokenType::DotDotDot]);
+                let name = self
+                    .consume(&TokenType::Identifier, "Expect parameter name.".to_string())
+                    .clone();
+
+                if parameters
+                    .iter()
+                    .any(|other| other.name.lexeme() == name.lexeme())
+                {
+                    panic!(
+                        "Duplicate parameter '{}' at line {}.",
+                        name.lexeme(),
+                        name.get_line()
+                    );
+                }
+
+                let default = if !rest && self.match_any(&[TokenType::Equal]) {
+                    Some(self.expression())
+                } else {
+                    None
+                };
+
+                parameters.push(Parameter {
+                    name: (name),
+                    default: (default),
+                    rest: (rest),
+                });
+
+                if !self.match_any(&[TokenType::Comma]) {
+                    break;
+                }
+            }
+        }
+
+        self.consume(
+            &TokenType::RightParen,
+            "Expect ')' after parameters.".to_string(),
+        );
+
+        parameters
+    }
+
+    // Whether the `(` at the current token opens the parameters of an arrow function rather than
+    // a grouping, that is whether its matching `)` is followed by `=>`.
+    fn is_arrow_function(&self) -> bool {
+        self.is_group_followed_by(&TokenType::Arrow)
+    }
+
+    // Whether the bracket at the current token is closed by one followed by `next`.
+    fn is_group_followed_by(&self, next: &TokenType) -> bool {
+        let mut depth = 0;
+
+        for (index, token) in self.tokens.iter().enumerate().skip(self.current) {
+            match token.get_token_type() {
+                TokenType::LeftParen | TokenType::LeftBracket | TokenType::LeftBrace => depth += 1,
+                TokenType::RightParen | TokenType::RightBracket | TokenType::RightBrace => {
+                    depth -= 1;
+
+                    if depth == 0 {
+                        return self
+                            .tokens
+                            .get(index + 1)
+                            .is_some_and(|token| token.type_equals_to(next));
+                    }
+                }
+                _ => (),
+            }
+        }
+
+        false
+    }
+
+    fn import_declaration(&mut self) -> Statement {
+        let keyword = self.previous().clone();
+
+        let path = self
+            .consume(
+                &TokenType::String,
+                "Expect module path after 'import'.".to_string(),
+            )
+            .clone();
+
+        self.consume(&TokenType::As, "Expect 'as' after module path.".to_string());
+
+        let name = self
+            .consume(&TokenType::Identifier, "Expect module name.".to_string())
+            .clone();
+
+        self.consume(
+            &TokenType::Semicolon,
+            "Expect ';' after import.".to_string(),
+        );
+
+        Statement::Import {
+            keyword: (keyword),
+            path: (path),
+            name: (name),
+        }
+    }
+
+    fn enum_declaration(&mut self) -> Statement {
+        let keyword = self.previous().clone();
+        let name = self
+            .consume(&TokenType::Identifier, "Expect enum name.".to_string())
+            .clone();
+
+        self.consume(
+            &TokenType::LeftBrace,
+            "Expect '{' before enum variants.".to_string(),
+        );
+
+        let mut variants: Vec<(Token, Vec<Token>)> = vec![];
+        let mut comments = vec![];
+        let mut separated = true;
+
+        while !self.check(&TokenType::RightBrace) && !self.is_at_end() {
+            if self.check(&TokenType::Comment) || self.check(&TokenType::BlankLine) {
+                comments.push((variants.len(), self.declaration()));
+                continue;
+            }
+
+            if !separated {
+                break;
+            }
+
+            let variant = self
+                .consume(&TokenType::Identifier, "Expect variant name.".to_string())
+                .clone();
+
+            if variants
+                .iter()
+                .any(|(other, _)| other.lexeme() == variant.lexeme())
+            {
+                panic!(
+                    "Duplicate variant '{}' at line {}.",
+                    variant.lexeme(),
+                    variant.get_line()
+                );
+            }
+
+            let mut fields: Vec<Token> = vec![];
+
+            if self.match_any(&[TokenType::LeftParen]) {
+                while !self.check(&TokenType::RightParen) && !self.is_at_end() {
+                    let field = self
+                        .consume(&TokenType::Identifier, "Expect field name.".to_string())
+                        .clone();
+
+                    if fields.iter().any(|other| other.lexeme() == field.lexeme()) {
+                        panic!(
+                            "Duplicate field '{}' at line {}.",
+                            field.lexeme(),
+                            field.get_line()
+                        );
+                    }
+                    fields.push(field);
+
+                    if !self.match_any(&[TokenType::Comma]) {
+                        break;
+                    }
+                }
+
+                self.consume(
+                    &TokenType::RightParen,
+                    "Expect ')' after variant fields.".to_string(),
+                );
+            }
+
+            variants.push((variant, fields));
+            separated = self.match_any(&[TokenType::Comma]);
+        }
+
+        self.consume(
+            &TokenType::RightBrace,
+            "Expect '}' after enum variants.".to_string(),
+        );
+
+        Statement::Enum {
+            keyword: (keyword),
+            name: (name),
+            variants: (variants),
+            comments: (comments),
+        }
+    }
+
+    fn var_declaration(&mut self) -> Statement {
+        if self.check(&TokenType::LeftBracket) || self.check(&TokenType::LeftBrace) {
+            return self.destructuring_declaration();
+        }
+
+        let token = self
+            .consume(&TokenType::Identifier, "Expect variable name.".to_string())
+            .clone();
+
+        let iniatilizer = if self.match_any(&[TokenType::Equal]) {
+            self.parse_expression()
+        } else {
+            Expression::Literal {
+                literal_value: Literal::Nil,
+                line: token.get_line(),
+            }
+        };
+
+        self.consume(
+            &TokenType::Semicolon,
+            "Expect ';' after expression.".to_string(),
+        );
+
+        Statement::Var {
+            token: (token),
+            expression: (iniatilizer),
+        }
+    }
+
+    // Unlike a variable, a constant has no `nil` to start from, so the initializer is required.
+    fn const_declaration(&mut self) -> Statement {
+        if self.check(&TokenType::LeftBracket) || self.check(&TokenType::LeftBrace) {
+            return self.destructuring_declaration();
+        }
+
+        let token = self
+            .consume(&TokenType::Identifier, "Expect constant name.".to_string())
+            .clone();
+
+        if !self.check(&TokenType::Equal) {
+            panic!(
+                "Constant {} must be initialized at line {}.",
+                token.lexeme(),
+                token.get_line()
+            );
+        }
+        self.advance();
+
+        let initializer = self.parse_expression();
+        self.consume(
+            &TokenType::Semicolon,
+            "Expect ';' after expression.".to_string(),
+        );
+
+        Statement::Const {
+            token: (token),
+            expression: (initializer),
+        }
+    }
+
+    // `var [a, b] = list;` or `const {name} = map;`, once the keyword has been matched.
+    fn destructuring_declaration(&mut self) -> Statement {
+        let keyword = self.previous().clone();
+        let pattern = self.destructuring_pattern();
+
+        self.consume(
+            &TokenType::Equal,
+            "Expect '=' after destructuring pattern.".to_string(),
+        );
+        let initializer = self.parse_expression();
+        self.consume(
+            &TokenType::Semicolon,
+            "Expect ';' after expression.".to_string(),
+        );
+
+        Statement::Destructure {
+            keyword: (keyword),
+            pattern: (pattern),
+            expression: (initializer),
+        }
+    }
+
+    fn statement(&mut self) -> Statement {
+        self.descend();
+        let statement = self.nested_statement();
+        self.ascend(1);
+
+        statement
+    }
+
+    fn nested_statement(&mut self) -> Statement {
+        if self.match_any(&[TokenType::If]) {
+            return self.if_statement();
+        }
+
+        if self.match_any(&[TokenType::Print]) {
+            return self.print_statement();
+        }
+
+        if self.match_any(&[TokenType::Throw]) {
+            return self.throw_statement();
+        }
+
+        if self.match_any(&[TokenType::Try]) {
+            return self.try_statement();
+        }
+
+        if self.match_any(&[TokenType::Return]) {
+            return self.return_statement();
+        }
+
+        if self.match_any(&[TokenType::Yield]) {
+            return self.yield_statement();
+        }
+
+        if self.match_any(&[TokenType::Match]) {
+            return self.match_statement();
+        }
+
+        if self.match_any(&[TokenType::For]) {
+            return self.for_statement();
+        }
+
+        if self.match_any(&[TokenType::LeftBrace]) {
+            return self.block_statement();
+        }
+
+        self.expression_statement()
+    }
+
+    // `for (x in iterable) body` or `for (key, value in iterable) body`, where both variables can
+    // also be destructuring patterns.
+    fn for_statement(&mut self) -> Statement {
+        let keyword = self.previous().clone();
+
+        self.consume(&TokenType::LeftParen, "Expect '(' after 'for'.".to_string());
+
+        let first = self.destructuring_pattern();
+        let (key, value) = if self.match_any(&[TokenType::Comma]) {
+            (Some(first), self.destructuring_pattern())
+        } else {
+            (None, first)
+        };
+
+        let mut bindings = vec![];
+        for pattern in key.iter().chain([&value]) {
+            Parser::check_bindings(pattern, &mut bindings);
+        }
+
+        self.consume(
+            &TokenType::In,
+            "Expect 'in' after for loop variable.".to_string(),
+        );
+        let iterable = self.expression();
+        self.consume(
+            &TokenType::RightParen,
+            "Expect ')' after for loop iterable.".to_string(),
+        );
+
+        let body = self.statement();
+
+        Statement::For {
+            keyword: (keyword),
+            key: (key.map(Box::from)),
+            value: (Box::from(value)),
+            iterable: (iterable),
+            body: (Box::from(body)),
+        }
+    }
+
+    fn if_statement(&mut self) -> Statement {
+        self.consume(&TokenType::LeftParen, "Expect '(' after 'if'.".to_string());
+
+        let condition = self.expression();
+
+        self.consume(
+            &TokenType::RightParen,
+            "Expect ')' after if condition.".to_string(),
+        );
+
+        let then = self.statement();
+
+        if self.match_any(&[TokenType::Else]) {
+            Statement::IfElse {
+                condition,
+                then_statement: (Box::from(then)),
+                else_stament: (Box::from(self.statement())),
             }
         } else {
             Statement::If {
@@ -315,20 +1785,529 @@ impl Parser {
         }
     }
 
-    fn print_statement(&mut self) -> Statement {
-        let expression = self.expression();
+    fn print_statement(&mut self) -> Statement {
+        let keyword = self.previous().clone();
+        let expression = self.expression();
+
+        self.consume(
+            &TokenType::Semicolon,
+            "Expect ';' after expression.".to_string(),
+        );
+
+        Statement::Print {
+            keyword: (keyword),
+            expression: (expression),
+        }
+    }
+
+    fn throw_statement(&mut self) -> Statement {
+        let keyword = self.previous().clone();
+        let expression = self.expression();
+
+        self.consume(
+            &TokenType::Semicolon,
+            "Expect ';' after thrown value.".to_string(),
+        );
+
+        Statement::Throw {
+            keyword: (keyword),
+            expression: (expression),
+        }
+    }
+
+    fn yield_statement(&mut self) -> Statement {
+        let keyword = self.previous().clone();
+
+        if self.functions == 0 {
+            panic!(
+                "Can't yield from top-level code at line {}.",
+                keyword.get_line()
+            );
+        }
+        self.yields = true;
+
+        let value = if self.check(&TokenType::Semicolon) {
+            None
+        } else {
+            Some(self.expression())
+        };
+
+        self.consume(
+            &TokenType::Semicolon,
+            "Expect ';' after yield value.".to_string(),
+        );
+
+        Statement::Yield {
+            keyword: (keyword),
+            value: (value),
+        }
+    }
+
+    fn return_statement(&mut self) -> Statement {
+        let keyword = self.previous().clone();
+
+        if self.functions == 0 {
+            panic!(
+                "Can't return from top-level code at line {}.",
+                keyword.get_line()
+            );
+        }
+
+        let value = if self.check(&TokenType::Semicolon) {
+            None
+        } else {
+            Some(self.expression())
+        };
+
+        self.consume(
+            &TokenType::Semicolon,
+            "Expect ';' after return value.".to_string(),
+        );
+
+        Statement::Return {
+            keyword: (keyword),
+            value: (value),
+        }
+    }
+
+    fn match_statement(&mut self) -> Statement {
+        let keyword = self.previous().clone();
+        let subject = self.match_subject();
+        let mut arms = vec![];
+        let mut comments = vec![];
+
+        while !self.check(&TokenType::RightBrace) && !self.is_at_end() {
+            if self.check(&TokenType::Comment) || self.check(&TokenType::BlankLine) {
+                comments.push((arms.len(), self.declaration()));
+                continue;
+            }
+
+            let (pattern, guard) = self.match_arm_head();
+
+            arms.push(MatchArm {
+                pattern: (pattern),
+                guard: (guard),
+                body: (self.statement()),
+            });
+        }
+
+        self.consume(
+            &TokenType::RightBrace,
+            "Expect '}' after match arms.".to_string(),
+        );
+
+        Statement::Match {
+            keyword: (keyword),
+            subject: (subject),
+            arms: (arms),
+            comments: (comments),
+        }
+    }
+
+    fn match_expression(&mut self) -> Expression {
+        let keyword = self.previous().clone();
+        let subject = self.match_subject();
+        let mut arms = vec![];
+
+        while !self.check(&TokenType::RightBrace) && !self.is_at_end() {
+            let (pattern, guard) = self.match_arm_head();
+
+            arms.push(MatchArm {
+                pattern: (pattern),
+                guard: (guard),
+                body: (self.expression()),
+            });
+
+            if !self.match_any(&[TokenType::Comma]) {
+                break;
+            }
+        }
+
+        self.consume(
+            &TokenType::RightBrace,
+            "Expect '}' after match arms.".to_string(),
+        );
+
+        Expression::Match {
+            keyword: (keyword),
+            subject: (Box::from(subject)),
+            arms: (arms),
+        }
+    }
+
+    // `(subject) {`
+    fn match_subject(&mut self) -> Expression {
+        self.consume(
+            &TokenType::LeftParen,
+            "Expect '(' after 'match'.".to_string(),
+        );
+        let subject = self.expression();
+        self.consume(
+            &TokenType::RightParen,
+            "Expect ')' after match subject.".to_string(),
+        );
+        self.consume(
+            &TokenType::LeftBrace,
+            "Expect '{' before match arms.".to_string(),
+        );
+
+        subject
+    }
+
+    // `pattern ( "if" expression )? "=>"`
+    fn match_arm_head(&mut self) -> (Pattern, Option<Expression>) {
+        let pattern = self.pattern();
+        let mut bindings = vec![];
+        Parser::check_bindings(&pattern, &mut bindings);
+
+        let guard = if self.match_any(&[TokenType::If]) {
+            let guard = self.guard;
+            self.guard = true;
+            let condition = self.expression();
+            self.guard = guard;
+
+            Some(condition)
+        } else {
+            None
+        };
+
+        self.consume(
+            &TokenType::Arrow,
+            "Expect '=>' after match pattern.".to_string(),
+        );
+
+        (pattern, guard)
+    }
+
+    // The target of `var [a, b] = ...;` or `[a, b] = ...`, where parts may have defaults.
+    fn destructuring_pattern(&mut self) -> Pattern {
+        let defaults = self.defaults;
+        self.defaults = true;
+        let pattern = self.pattern();
+        self.defaults = defaults;
+
+        let mut bindings = vec![];
+        Parser::check_bindings(&pattern, &mut bindings);
+
+        pattern
+    }
+
+    // pattern        → alternative ( "|" alternative )*
+    // alternative    → "_" | IDENTIFIER | "-"? NUMBER | STRING | "true" | "false" | "nil"
+    //                | "[" ( element ( "," element )* )? ( "," "..." IDENTIFIER )? "]"
+    //                | "{" ( key ( ":" element )? ( "," key ( ":" element )? )* )? "}"
+    //                | ( IDENTIFIER "." )? IDENTIFIER ( "(" ( element ( "," element )* )? ")" )?
+    // element        → pattern ( "=" expression )?
+    fn pattern(&mut self) -> Pattern {
+        self.descend();
+        let first = self.pattern_alternative();
+
+        let pattern = if self.check(&TokenType::Pipe) {
+            let mut alternatives = vec![first];
+
+            while self.match_any(&[TokenType::Pipe]) {
+                alternatives.push(self.pattern_alternative());
+            }
+
+            Pattern::Alternative {
+                alternatives: (alternatives),
+            }
+        } else {
+            first
+        };
+        self.ascend(1);
+
+        pattern
+    }
+
+    // A part of a list, map or variant pattern, with the default used when a destructured value
+    // lacks it.
+    fn pattern_element(&mut self, pattern: Pattern) -> Pattern {
+        if !self.match_any(&[TokenType::Equal]) {
+            return pattern;
+        }
+
+        let equals = self.previous().clone();
+
+        if !self.defaults {
+            panic!(
+                "Default values are only allowed when destructuring at line {}.",
+                equals.get_line()
+            );
+        }
+
+        Pattern::Default {
+            pattern: (Box::from(pattern)),
+            equals: (equals),
+            default: (Box::from(self.expression())),
+        }
+    }
+
+    fn pattern_alternative(&mut self) -> Pattern {
+        let token = self.advance().clone();
+
+        match token.get_token_type() {
+            TokenType::Identifier if token.lexeme() == "_" => Pattern::Wildcard { token: (token) },
+            TokenType::Identifier
+                if self.check(&TokenType::LeftParen) || self.check(&TokenType::Dot) =>
+            {
+                self.variant_pattern(token)
+            }
+            TokenType::Identifier => Pattern::Binding { name: (token) },
+            TokenType::Number | TokenType::String => Pattern::Literal {
+                value: (token.get_literal().clone()),
+                token: (token),
+            },
+            TokenType::True | TokenType::False | TokenType::Nil => Pattern::Literal {
+                value: (match token.get_token_type() {
+                    TokenType::True => Literal::Boolean(true),
+                    TokenType::False => Literal::Boolean(false),
+                    _ => Literal::Nil,
+                }),
+                token: (token),
+            },
+            TokenType::Minus if self.check(&TokenType::Number) => {
+                let value = match self.advance().get_literal() {
+                    Literal::Int(value) => Literal::Int(-value),
+                    Literal::Number(value) => Literal::Number(-value),
+                    literal => literal.clone(),
+                };
+
+                Pattern::Literal {
+                    token: (token),
+                    value: (value),
+                }
+            }
+            TokenType::LeftBracket => {
+                let mut elements = vec![];
+                let mut rest = None;
+
+                while !self.check(&TokenType::RightBracket) && !self.is_at_end() {
+                    if self.match_any(&[TokenType::DotDotDot]) {
+                        rest = Some(
+                            self.consume(
+                                &TokenType::Identifier,
+                                "Expect a name after '...'.".to_string(),
+                            )
+                            .clone(),
+                        );
+                        break;
+                    }
+
+                    let element = self.pattern();
+                    elements.push(self.pattern_element(element));
+
+                    if !self.match_any(&[TokenType::Comma]) {
+                        break;
+                    }
+                }
+
+                self.consume(
+                    &TokenType::RightBracket,
+                    "Expect ']' after list pattern.".to_string(),
+                );
+
+                Pattern::List {
+                    bracket: (token),
+                    elements: (elements),
+                    rest: (rest),
+                }
+            }
+            TokenType::LeftBrace => {
+                let mut entries = vec![];
+
+                while !self.check(&TokenType::RightBrace) && !self.is_at_end() {
+                    if !self.match_any(&[TokenType::Identifier, TokenType::String]) {
+                        panic!(
+                            "Expect a name or string as map key at line {}.",
+                            self.peek().get_line()
+                        );
+                    }
+                    let key = self.previous().clone();
+
+                    let pattern = if self.match_any(&[TokenType::Colon]) {
+                        let pattern = self.pattern();
+                        self.pattern_element(pattern)
+                    } else if key.type_equals_to(&TokenType::Identifier) {
+                        self.pattern_element(Pattern::Binding { name: key.clone() })
+                    } else {
+                        panic!("Expect ':' after string key at line {}.", key.get_line());
+                    };
+                    entries.push((key, pattern));
+
+                    if !self.match_any(&[TokenType::Comma]) {
+                        break;
+                    }
+                }
+
+                self.consume(
+                    &TokenType::RightBrace,
+                    "Expect '}' after map pattern.".to_string(),
+                );
+
+                Pattern::Map {
+                    brace: (token),
+                    entries: (entries),
+                }
+            }
+            _ => panic!("Expect a pattern at line {}.", token.get_line()),
+        }
+    }
+
+    // `Circle(r)`, `Shape.Circle(r)`, or `Shape.Circle` to only check the variant.
+    fn variant_pattern(&mut self, first: Token) -> Pattern {
+        let (enumeration, name) = if self.match_any(&[TokenType::Dot]) {
+            let name = self
+                .consume(
+                    &TokenType::Identifier,
+                    "Expect variant name after '.'.".to_string(),
+                )
+                .clone();
+
+            (Some(first), name)
+        } else {
+            (None, first)
+        };
+
+        let fields = if self.match_any(&[TokenType::LeftParen]) {
+            let mut fields = vec![];
+
+            while !self.check(&TokenType::RightParen) && !self.is_at_end() {
+                let field = self.pattern();
+                fields.push(self.pattern_element(field));
+
+                if !self.match_any(&[TokenType::Comma]) {
+                    break;
+                }
+            }
+
+            self.consume(
+                &TokenType::RightParen,
+                "Expect ')' after variant pattern.".to_string(),
+            );
+
+            Some(fields)
+        } else {
+            None
+        };
+
+        Pattern::Variant {
+            enumeration: (enumeration),
+            name: (name),
+            fields: (fields),
+        }
+    }
+
+    // A name can only be bound once per pattern, and not at all inside alternatives, where it
+    // would be left unbound whenever another alternative matched.
+    fn check_bindings(pattern: &Pattern, bindings: &mut Vec<String>) {
+        let mut bind = |name: &Token| {
+            if bindings.iter().any(|bound| bound == name.lexeme()) {
+                panic!(
+                    "Duplicate binding '{}' in pattern at line {}.",
+                    name.lexeme(),
+                    name.get_line()
+                );
+            }
+            bindings.push(name.lexeme().to_string());
+        };
+
+        match pattern {
+            Pattern::Wildcard { .. } | Pattern::Literal { .. } => (),
+            Pattern::Binding { name } => bind(name),
+            Pattern::Alternative { alternatives } => {
+                for alternative in alternatives {
+                    let mut inner = vec![];
+                    Parser::check_bindings(alternative, &mut inner);
+
+                    if !inner.is_empty() {
+                        panic!(
+                            "Alternative patterns can't bind names at line {}.",
+                            alternative.line()
+                        );
+                    }
+                }
+            }
+            Pattern::List { elements, rest, .. } => {
+                if let Some(rest) = rest.as_ref().filter(|rest| rest.lexeme() != "_") {
+                    bind(rest);
+                }
+
+                for element in elements {
+                    Parser::check_bindings(element, bindings);
+                }
+            }
+            Pattern::Map { entries, .. } => {
+                for (_, pattern) in entries {
+                    Parser::check_bindings(pattern, bindings);
+                }
+            }
+            Pattern::Variant { fields, .. } => {
+                for field in fields.iter().flatten() {
+                    Parser::check_bindings(field, bindings);
+                }
+            }
+            Pattern::Default { pattern, .. } => Parser::check_bindings(pattern, bindings),
+        }
+    }
+
+    fn try_statement(&mut self) -> Statement {
+        self.consume(&TokenType::LeftBrace, "Expect '{' after 'try'.".to_string());
+
+        let try_statement = self.block_statement();
+
+        let catch_clause = if self.match_any(&[TokenType::Catch]) {
+            self.consume(
+                &TokenType::LeftParen,
+                "Expect '(' after 'catch'.".to_string(),
+            );
+
+            let name = self
+                .consume(
+                    &TokenType::Identifier,
+                    "Expect error variable name.".to_string(),
+                )
+                .clone();
+
+            self.consume(
+                &TokenType::RightParen,
+                "Expect ')' after error variable name.".to_string(),
+            );
+            self.consume(
+                &TokenType::LeftBrace,
+                "Expect '{' after catch clause.".to_string(),
+            );
+
+            Some((name, Box::from(self.block_statement())))
+        } else {
+            None
+        };
 
-        self.consume(
-            &TokenType::Semicolon,
-            "Expect ';' after expression.".to_string(),
-        );
+        let finally_statement = if self.match_any(&[TokenType::Finally]) {
+            self.consume(
+                &TokenType::LeftBrace,
+                "Expect '{' after 'finally'.".to_string(),
+            );
 
-        Statement::Print {
-            expression: (expression),
+            Some(Box::from(self.block_statement()))
+        } else {
+            None
+        };
+
+        if catch_clause.is_none() && finally_statement.is_none() {
+            panic!("Expect 'catch' or 'finally' after try block.");
+        }
+
+        Statement::Try {
+            try_statement: (Box::from(try_statement)),
+            catch_clause: (catch_clause),
+            finally_statement: (finally_statement),
         }
     }
 
+    // Called once the `{` is consumed.
     fn block_statement(&mut self) -> Statement {
+        let line = self.previous().get_line();
         let mut statements = Vec::new();
 
         while !self.check(&TokenType::RightBrace) && !self.is_at_end() {
@@ -342,6 +2321,7 @@ impl Parser {
 
         Statement::Block {
             statements: (statements),
+            line: (line),
         }
     }
 
@@ -358,22 +2338,69 @@ impl Parser {
         }
     }
 
-    // expression     → ...
-    // equality       → ...
-    // comparison     → ...
-    // term           → ...
-    // factor         → ...
-    // unary          → ...
-    // primary        → ...
+    // From loosest to tightest binding; every binary level is left-associative except `**`.
+    //
+    // expression     → assignment
+    // assignment     → IDENTIFIER ( "=" | "+=" | "-=" | "*=" | "/=" | "%=" ) assignment
+    //                | ( list_pattern | map_pattern ) "=" assignment
+    //                | conditional
+    // conditional    → coalesce ( "?" expression ":" conditional )?
+    // coalesce       → or ( "??" or )*
+    // or             → and ( "or" and )*
+    // and            → equality ( "and" equality )*
+    // equality       → comparison ( ( "!=" | "==" ) comparison )*
+    // comparison     → range ( ( ">" | ">=" | "<" | "<=" | "is" ) range )*
+    // range          → bitwise_or ( ".." bitwise_or ( "step" bitwise_or )? )?
+    // bitwise_or     → bitwise_xor ( "|" bitwise_xor )*
+    // bitwise_xor    → bitwise_and ( "^" bitwise_and )*
+    // bitwise_and    → shift ( "&" shift )*
+    // shift          → term ( ( "<<" | ">>" ) term )*
+    // term           → factor ( ( "-" | "+" ) factor )*
+    // factor         → unary ( ( "/" | "*" | "%" | "~/" ) unary )*
+    // unary          → ( "!" | "-" | "~" ) unary | ( "++" | "--" ) IDENTIFIER | power
+    // power          → postfix ( "**" unary )?
+    // postfix        → call ( "++" | "--" )?
+    // call           → primary ( "(" arguments? ")" | "[" expression "]" | ( "." | "?." ) IDENTIFIER )*
+    // arguments      → expression ( "," expression )* ( "," IDENTIFIER ":" expression )*
+    //                | IDENTIFIER ":" expression ( "," IDENTIFIER ":" expression )*
+    // parameters     → parameter ( "," parameter )* ( "," "..." IDENTIFIER )? | "..." IDENTIFIER
+    // parameter      → IDENTIFIER ( "=" expression )?
+    // primary        → ... | "[" arguments? "]" | "{" ( key ":" expression ( "," key ":" expression )* )? "}"
+    //                | "fun" "(" parameters? ")" block | "(" parameters? ")" "=>" expression
+    //                | "match" "(" expression ")" "{" ( arm ( "," arm )* )? "}"
+    // arm            → pattern ( "if" expression )? "=>" expression
     fn expression(&mut self) -> Expression {
-        self.assignment()
+        self.descend();
+        let expression = self.assignment();
+        self.ascend(1);
+
+        expression
     }
 
     fn assignment(&mut self) -> Expression {
-        let expression = self.or();
+        if (self.check(&TokenType::LeftBracket) || self.check(&TokenType::LeftBrace))
+            && self.is_group_followed_by(&TokenType::Equal)
+        {
+            let pattern = self.destructuring_pattern();
+            let equals = self.advance().clone();
+
+            self.descend();
+            let value = self.assignment();
+            self.ascend(1);
+
+            return Expression::Destructure {
+                pattern: (pattern),
+                equals: (equals),
+                value: (Box::from(value)),
+            };
+        }
+
+        let expression = self.conditional();
 
         if self.match_any(&[TokenType::Equal]) {
+            self.descend();
             let value = self.assignment();
+            self.ascend(1);
 
             match expression {
                 Expression::Var { name } => {
@@ -386,13 +2413,95 @@ impl Parser {
             }
         }
 
+        if self.match_any(&[
+            TokenType::PlusEqual,
+            TokenType::MinusEqual,
+            TokenType::StarEqual,
+            TokenType::SlashEqual,
+            TokenType::PercentEqual,
+        ]) {
+            let operator = self.previous().clone();
+
+            self.descend();
+            let value = self.assignment();
+            self.ascend(1);
+
+            match expression {
+                Expression::Var { name } => {
+                    return Expression::CompoundAssignment {
+                        name: (name),
+                        operator: (operator),
+                        value: (Box::from(value)),
+                    }
+                }
+                _ => panic!("Invalid assignment target."),
+            }
+        }
+
         expression
     }
 
+    fn conditional(&mut self) -> Expression {
+        let condition = self.coalesce();
+
+        if self.match_any(&[TokenType::Question]) {
+            let question = self.previous().clone();
+
+            self.descend();
+            let then_branch = self.expression();
+            self.consume(
+                &TokenType::Colon,
+                "Expect ':' after then branch of conditional expression.".to_string(),
+            );
+            let else_branch = self.conditional();
+            self.ascend(1);
+
+            return Expression::Conditional {
+                condition: (Box::from(condition)),
+                question: (question),
+                then_branch: (Box::from(then_branch)),
+                else_branch: (Box::from(else_branch)),
+            };
+        }
+
+        condition
+    }
+
+    // `??` short-circuits like `or`, so it is a `Logical` expression too.
+    fn coalesce(&mut self) -> Expression {
+        let mut expr = self.or();
+
+        let mut levels = 0;
+
+        while self.match_any(&[TokenType::QuestionQuestion]) {
+            self.descend();
+            levels += 1;
+
+            let operator = self.previous().clone();
+
+            let right_expression = self.or();
+
+            expr = Expression::Logical {
+                left: (Box::from(expr)),
+                operator: (operator),
+                right: (Box::from(right_expression)),
+            };
+        }
+
+        self.ascend(levels);
+
+        expr
+    }
+
     fn or(&mut self) -> Expression {
         let mut expr = self.and();
 
+        let mut levels = 0;
+
         while self.match_any(&[TokenType::Or]) {
+            self.descend();
+            levels += 1;
+
             let operator = self.previous().clone();
 
             let right_expression = self.and();
@@ -404,13 +2513,20 @@ impl Parser {
             };
         }
 
+        self.ascend(levels);
+
         expr
     }
 
     fn and(&mut self) -> Expression {
         let mut expr = self.equality();
 
+        let mut levels = 0;
+
         while self.match_any(&[TokenType::And]) {
+            self.descend();
+            levels += 1;
+
             let operator = self.previous().clone();
 
             let right_expression = self.equality();
@@ -422,13 +2538,20 @@ impl Parser {
             };
         }
 
+        self.ascend(levels);
+
         expr
     }
 
     fn equality(&mut self) -> Expression {
         let mut expr = self.comparison();
 
+        let mut levels = 0;
+
         while self.match_any(&[TokenType::BangEqual, TokenType::EqualEqual]) {
+            self.descend();
+            levels += 1;
+
             let operator = self.previous().clone();
 
             let right_expression = self.comparison();
@@ -440,21 +2563,106 @@ impl Parser {
             }
         }
 
+        self.ascend(levels);
+
         expr
     }
 
     fn comparison(&mut self) -> Expression {
-        let mut expr = self.term();
+        let mut expr = self.range();
+
+        let mut levels = 0;
 
         while self.match_any(&[
             TokenType::Greater,
             TokenType::GreaterEqual,
             TokenType::Less,
             TokenType::LessEqual,
+            TokenType::Is,
         ]) {
+            self.descend();
+            levels += 1;
+
+            let operator = self.previous().clone();
+
+            let right_expression: Expression = self.range();
+
+            expr = Expression::Binary {
+                left: (Box::from(expr)),
+                operator: (operator),
+                right: (Box::from(right_expression)),
+            };
+        }
+
+        self.ascend(levels);
+
+        expr
+    }
+
+    fn range(&mut self) -> Expression {
+        let start = self.bitwise_or();
+
+        if !self.match_any(&[TokenType::DotDot]) {
+            return start;
+        }
+
+        let operator = self.previous().clone();
+
+        self.descend();
+        let end = self.bitwise_or();
+        // `step` is only a keyword here, it stays a valid name everywhere else.
+        let step = if self.check(&TokenType::Identifier) && self.peek().lexeme() == "step" {
+            self.advance();
+            Some(Box::from(self.bitwise_or()))
+        } else {
+            None
+        };
+        self.ascend(1);
+
+        Expression::Range {
+            start: (Box::from(start)),
+            operator: (operator),
+            end: (Box::from(end)),
+            step: (step),
+        }
+    }
+
+    fn bitwise_or(&mut self) -> Expression {
+        self.binary_level(&[TokenType::Pipe], Self::bitwise_xor)
+    }
+
+    fn bitwise_xor(&mut self) -> Expression {
+        self.binary_level(&[TokenType::Caret], Self::bitwise_and)
+    }
+
+    fn bitwise_and(&mut self) -> Expression {
+        self.binary_level(&[TokenType::Ampersand], Self::shift)
+    }
+
+    fn shift(&mut self) -> Expression {
+        self.binary_level(
+            &[TokenType::LessLess, TokenType::GreaterGreater],
+            Self::term,
+        )
+    }
+
+    // A left-associative level of binary operators whose operands are parsed by `operand`.
+    fn binary_level(
+        &mut self,
+        operators: &[TokenType],
+        operand: fn(&mut Self) -> Expression,
+    ) -> Expression {
+        let mut expr = operand(self);
+
+        let mut levels = 0;
+
+        while self.match_any(operators) {
+            self.descend();
+            levels += 1;
+
             let operator = self.previous().clone();
 
-            let right_expression: Expression = self.term();
+            let right_expression = operand(self);
 
             expr = Expression::Binary {
                 left: (Box::from(expr)),
@@ -463,13 +2671,20 @@ impl Parser {
             };
         }
 
+        self.ascend(levels);
+
         expr
     }
 
     fn term(&mut self) -> Expression {
         let mut expr: Expression = self.factor();
 
+        let mut levels = 0;
+
         while self.match_any(&[TokenType::Minus, TokenType::Plus]) {
+            self.descend();
+            levels += 1;
+
             let operator = self.previous().clone();
 
             let right_expression: Expression = self.factor();
@@ -481,13 +2696,25 @@ impl Parser {
             };
         }
 
+        self.ascend(levels);
+
         expr
     }
 
     fn factor(&mut self) -> Expression {
         let mut expr: Expression = self.unary();
 
-        while self.match_any(&[TokenType::Slash, TokenType::Star]) {
+        let mut levels = 0;
+
+        while self.match_any(&[
+            TokenType::Slash,
+            TokenType::Star,
+            TokenType::Percent,
+            TokenType::TildeSlash,
+        ]) {
+            self.descend();
+            levels += 1;
+
             let operator = self.previous().clone();
 
             let right_expression: Expression = self.unary();
@@ -499,14 +2726,28 @@ impl Parser {
             }
         }
 
+        self.ascend(levels);
+
         expr
     }
 
     fn unary(&mut self) -> Expression {
-        if self.match_any(&[TokenType::Bang, TokenType::Minus]) {
+        if self.match_any(&[TokenType::PlusPlus, TokenType::MinusMinus]) {
+            let operator = self.previous().clone();
+
+            self.descend();
+            let target = self.unary();
+            self.ascend(1);
+
+            return Parser::increment(target, operator, true);
+        }
+
+        if self.match_any(&[TokenType::Bang, TokenType::Minus, TokenType::Tilde]) {
             let operator = self.previous().clone();
 
+            self.descend();
             let right_expression: Expression = self.unary();
+            self.ascend(1);
 
             return Expression::Unary {
                 operator: (operator),
@@ -514,25 +2755,187 @@ impl Parser {
             };
         }
 
-        self.primary()
+        self.power()
+    }
+
+    // `-2 ** 2` is `-(2 ** 2)`, and the exponent may itself be negated: `2 ** -1`.
+    fn power(&mut self) -> Expression {
+        let base = self.postfix();
+
+        if self.match_any(&[TokenType::StarStar]) {
+            let operator = self.previous().clone();
+
+            self.descend();
+            let exponent = self.unary();
+            self.ascend(1);
+
+            return Expression::Binary {
+                left: (Box::from(base)),
+                operator: (operator),
+                right: (Box::from(exponent)),
+            };
+        }
+
+        base
+    }
+
+    fn postfix(&mut self) -> Expression {
+        let expression = self.call();
+
+        if self.match_any(&[TokenType::PlusPlus, TokenType::MinusMinus]) {
+            let operator = self.previous().clone();
+
+            return Parser::increment(expression, operator, false);
+        }
+
+        expression
+    }
+
+    fn increment(target: Expression, operator: Token, prefix: bool) -> Expression {
+        match target {
+            Expression::Var { name } => Expression::Increment {
+                name: (name),
+                operator: (operator),
+                prefix: (prefix),
+            },
+            _ => panic!(
+                "Invalid {} target at line {}.",
+                operator.lexeme(),
+                operator.get_line()
+            ),
+        }
+    }
+
+    fn call(&mut self) -> Expression {
+        let mut expr = self.primary();
+
+        let mut levels = 0;
+
+        while self.match_any(&[
+            TokenType::Dot,
+            TokenType::QuestionDot,
+            TokenType::LeftParen,
+            TokenType::LeftBracket,
+        ]) {
+            self.descend();
+            levels += 1;
+
+            if self.previous().type_equals_to(&TokenType::LeftParen) {
+                expr = self.finish_call(expr);
+                continue;
+            }
+
+            if self.previous().type_equals_to(&TokenType::LeftBracket) {
+                let index = self.expression();
+                let bracket = self
+                    .consume(
+                        &TokenType::RightBracket,
+                        "Expect ']' after index.".to_string(),
+                    )
+                    .clone();
+
+                expr = Expression::Index {
+                    object: (Box::from(expr)),
+                    bracket: (bracket),
+                    index: (Box::from(index)),
+                };
+                continue;
+            }
+
+            let optional = self.previous().type_equals_to(&TokenType::QuestionDot);
+
+            let name = self
+                .consume(
+                    &TokenType::Identifier,
+                    "Expect property name after '.'.".to_string(),
+                )
+                .clone();
+
+            expr = Expression::Get {
+                object: (Box::from(expr)),
+                name: (name),
+                optional: (optional),
+            };
+        }
+
+        self.ascend(levels);
+
+        expr
+    }
+
+    fn finish_call(&mut self, callee: Expression) -> Expression {
+        let guard = std::mem::replace(&mut self.guard, false);
+        let mut arguments = vec![];
+        let mut named: Vec<(Token, Expression)> = vec![];
+
+        if !self.check(&TokenType::RightParen) {
+            loop {
+                if self.check(&TokenType::Identifier) && self.check_next(&TokenType::Colon) {
+                    let name = self.advance().clone();
+                    self.advance();
+
+                    if named
+                        .iter()
+                        .any(|(other, _)| other.lexeme() == name.lexeme())
+                    {
+                        panic!(
+                            "Duplicate argument '{}' at line {}.",
+                            name.lexeme(),
+                            name.get_line()
+                        );
+                    }
+
+                    named.push((name, self.expression()));
+                } else if named.is_empty() {
+                    arguments.push(self.expression());
+                } else {
+                    panic!(
+                        "Expect named argument after a named one at line {}.",
+                        self.peek().get_line()
+                    );
+                }
+
+                if !self.match_any(&[TokenType::Comma]) {
+                    break;
+                }
+            }
+        }
+
+        let paren = self
+            .consume(
+                &TokenType::RightParen,
+                "Expect ')' after arguments.".to_string(),
+            )
+            .clone();
+        self.guard = guard;
+
+        Expression::Call {
+            callee: (Box::from(callee)),
+            paren: (paren),
+            arguments: (arguments),
+            named: (named),
+        }
     }
 
     fn primary(&mut self) -> Expression {
         if self.match_any(&[TokenType::True]) {
             return Expression::Literal {
                 literal_value: (Literal::Boolean(true)),
+                line: self.previous().get_line(),
             };
         }
 
         if self.match_any(&[TokenType::False]) {
             return Expression::Literal {
                 literal_value: (Literal::Boolean(false)),
+                line: self.previous().get_line(),
             };
         }
 
         if self.match_any(&[TokenType::Nil]) {
             return Expression::Literal {
                 literal_value: (Literal::Nil),
+                line: self.previous().get_line(),
             };
         }
 
@@ -541,6 +2944,7 @@ impl Parser {
 
             return Expression::Literal {
                 literal_value: (previous_literal),
+                line: self.previous().get_line(),
             };
         }
 
@@ -552,6 +2956,87 @@ impl Parser {
             };
         }
 
+        if self.match_any(&[TokenType::LeftBracket]) {
+            let mut elements = vec![];
+
+            if !self.check(&TokenType::RightBracket) {
+                loop {
+                    elements.push(self.expression());
+
+                    if !self.match_any(&[TokenType::Comma]) {
+                        break;
+                    }
+                }
+            }
+
+            let bracket = self
+                .consume(
+                    &TokenType::RightBracket,
+                    "Expect ']' after list elements.".to_string(),
+                )
+                .clone();
+
+            return Expression::List {
+                bracket: (bracket),
+                elements: (elements),
+            };
+        }
+
+        if self.match_any(&[TokenType::LeftBrace]) {
+            let mut entries = vec![];
+
+            if !self.check(&TokenType::RightBrace) {
+                loop {
+                    if !self.match_any(&[TokenType::Identifier, TokenType::String]) {
+                        panic!(
+                            "Expect a name or string as map key at line {}.",
+                            self.peek().get_line()
+                        );
+                    }
+                    let key = self.previous().clone();
+
+                    self.consume(&TokenType::Colon, "Expect ':' after map key.".to_string());
+                    entries.push((key, self.expression()));
+
+                    if !self.match_any(&[TokenType::Comma]) {
+                        break;
+                    }
+                }
+            }
+
+            let brace = self
+                .consume(
+                    &TokenType::RightBrace,
+                    "Expect '}' after map entries.".to_string(),
+                )
+                .clone();
+
+            return Expression::Map {
+                brace: (brace),
+                entries: (entries),
+            };
+        }
+
+        if self.match_any(&[TokenType::Fun]) {
+            let keyword = self.previous().clone();
+
+            return Expression::Function {
+                declaration: (Rc::new(self.function(keyword, None))),
+            };
+        }
+
+        if self.match_any(&[TokenType::Match]) {
+            return self.match_expression();
+        }
+
+        if self.check(&TokenType::LeftParen) && !self.guard && self.is_arrow_function() {
+            self.advance();
+
+            return Expression::Function {
+                declaration: (Rc::new(self.arrow_function())),
+            };
+        }
+
         if self.match_any(&[TokenType::LeftParen]) {
             let expression = self.expression();
 
@@ -568,6 +3053,18 @@ impl Parser {
         panic!("Invalid syntax")
     }
 
+    fn descend(&mut self) {
+        if self.depth >= MAX_NESTING_DEPTH || stack_exhausted() {
+            panic!("Too much nesting at line {}.", self.peek().get_line());
+        }
+
+        self.depth += 1;
+    }
+
+    fn ascend(&mut self, levels: usize) {
+        self.depth -= levels;
+    }
+
     fn match_any(&mut self, token_types: &[TokenType]) -> bool {
         for token_type in token_types {
             if self.check(token_type) {
@@ -589,12 +3086,18 @@ impl Parser {
         current.type_equals_to(token_type)
     }
 
+    fn check_next(&self, token_type: &TokenType) -> bool {
+        self.tokens
+            .get(self.current + 1)
+            .is_some_and(|next| next.type_equals_to(token_type))
+    }
+
     fn advance(&mut self) -> &Token {
         if !self.is_at_end() {
             self.current += 1;
         }
 
-        return self.previous();
+        self.previous()
     }
 
     fn is_at_end(&self) -> bool {
@@ -618,3 +3121,115 @@ impl Parser {
         panic!("{}", message);
     }
 }
+
+// Comments are only kept as statements, so a comment that sits in the middle of a statement is
+// moved after the end of that statement. It never moves into a block: `} // done` before an `else`
+// or a comment between the parameters of a function stays in the scope it was written in. The
+// variants of an enum keep theirs in place, the parser stores them with the enum. Blank lines only
+// matter between statements and are dropped anywhere else. Token streams without comments come
+// out unchanged.
+fn hoist_comments(tokens: Vec<Token>) -> Vec<Token> {
+    let mut hoisted: Vec<Token> = Vec::with_capacity(tokens.len());
+    let mut pending: Vec<Token> = vec![];
+    // The braces that are open, and how many there were when the pending comments were found. The
+    // body of an enum holds how many parentheses were open around it, to tell the commas between
+    // its variants from the ones between fields.
+    let mut braces: Vec<Option<usize>> = vec![];
+    let mut parentheses: usize = 0;
+    let mut pending_depth = 0;
+
+    for (index, token) in tokens.iter().enumerate() {
+        let rest = &tokens[index + 1..];
+        let keep = |hoisted: &[Token]| {
+            starts_declaration(hoisted, rest) || braces.last() == Some(&Some(parentheses))
+        };
+
+        if token.type_equals_to(&TokenType::BlankLine) {
+            if pending.is_empty() && keep(&hoisted) {
+                hoisted.push(token.clone());
+            }
+
+            continue;
+        }
+
+        if token.type_equals_to(&TokenType::Comment) {
+            if pending.is_empty() && keep(&hoisted) {
+                hoisted.push(token.clone());
+            } else {
+                if pending.is_empty() {
+                    pending_depth = braces.len();
+                }
+                pending.push(token.clone());
+            }
+
+            continue;
+        }
+
+        if token.is_eof() {
+            hoisted.append(&mut pending);
+        }
+
+        match token.get_token_type() {
+            TokenType::LeftBrace => {
+                // `enum Name {`
+                let enumeration = hoisted
+                    .iter()
+                    .rev()
+                    .filter(is_code)
+                    .nth(1)
+                    .is_some_and(|keyword| keyword.type_equals_to(&TokenType::Enum));
+
+                braces.push(enumeration.then_some(parentheses));
+            }
+            TokenType::RightBrace => {
+                braces.pop();
+            }
+            TokenType::LeftParen | TokenType::LeftBracket => parentheses += 1,
+            TokenType::RightParen | TokenType::RightBracket => {
+                parentheses = parentheses.saturating_sub(1)
+            }
+            _ => (),
+        }
+
+        hoisted.push(token.clone());
+
+        if !pending.is_empty()
+            && braces.len() <= pending_depth
+            && starts_declaration(&hoisted, rest)
+        {
+            hoisted.append(&mut pending);
+        }
+    }
+
+    hoisted
+}
+
+fn is_code(token: &&Token) -> bool {
+    !token.type_equals_to(&TokenType::Comment) && !token.type_equals_to(&TokenType::BlankLine)
+}
+
+fn starts_declaration(before: &[Token], after: &[Token]) -> bool {
+    let Some(previous) = before.iter().rev().find(is_code) else {
+        return true;
+    };
+
+    match previous.get_token_type() {
+        TokenType::Semicolon | TokenType::LeftBrace => true,
+        TokenType::RightBrace => !after.iter().find(is_code).is_some_and(|next| {
+            // A `}` followed by one of these closes a block that is part of a larger statement:
+            // the body of a function expression, or a branch with more to come.
+            matches!(
+                next.get_token_type(),
+                TokenType::Else
+                    | TokenType::Catch
+                    | TokenType::Finally
+                    | TokenType::RightParen
+                    | TokenType::RightBracket
+                    | TokenType::Comma
+                    | TokenType::Semicolon
+                    | TokenType::Dot
+            )
+        }),
+        _ => false,
+    }
+}
@@ -0,0 +1,196 @@
+use std::rc::Rc;
+
+use crate::{interner::Symbol, Expression, List, Literal, Token, Variant};
+
+// The left-hand side of a `match` arm. Patterns only look at a value, they never evaluate code:
+// the guard of an arm is where arbitrary conditions go.
+#[derive(PartialEq, Clone)]
+pub enum Pattern {
+    // `_`, which matches anything and binds nothing.
+    Wildcard {
+        token: Token,
+    },
+    // A number, string, `true`, `false` or `nil`, compared with the rules of `==`.
+    Literal {
+        token: Token,
+        value: Literal,
+    },
+    // A name, which matches anything and binds it.
+    Binding {
+        name: Token,
+    },
+    // `1 | 2 | 3`: the first alternative that matches wins. Alternatives bind nothing.
+    Alternative {
+        alternatives: Vec<Pattern>,
+    },
+    // `[x, y]` matches lists of exactly that length, `[x, ...rest]` lists at least that long with
+    // the remaining elements bound to `rest` (or dropped by `..._`).
+    List {
+        bracket: Token,
+        elements: Vec<Pattern>,
+        rest: Option<Token>,
+    },
+    // `{name: n, age}` matches maps that have at least those keys; `age` is short for `age: age`.
+    Map {
+        brace: Token,
+        entries: Vec<(Token, Pattern)>,
+    },
+    // `Shape.Circle(r)` matches values built by the `Circle` variant of an enum named `Shape`, and
+    // `Circle(r)` by the `Circle` variant of any enum. Without the parentheses only the variant is
+    // checked, whatever its fields.
+    Variant {
+        enumeration: Option<Token>,
+        name: Token,
+        fields: Option<Vec<Pattern>>,
+    },
+    // `b = 0` inside a destructuring pattern: `default` is evaluated and destructured when the
+    // value has no part for `pattern`. Match arms can't have defaults.
+    Default {
+        pattern: Box<Pattern>,
+        equals: Token,
+        default: Box<Expression>,
+    },
+}
+
+// `pattern if guard => body`, where the body is a statement in a `match` statement and an
+// expression in a `match` expression.
+#[derive(PartialEq, Clone)]
+pub struct MatchArm<T> {
+    pub pattern: Pattern,
+    pub guard: Option<Expression>,
+    pub body: T,
+}
+
+impl Pattern {
+    pub fn line(&self) -> u32 {
+        match self {
+            Pattern::Wildcard { token } | Pattern::Literal { token, .. } => token.get_line(),
+            Pattern::Binding { name } | Pattern::Variant { name, .. } => name.get_line(),
+            Pattern::Alternative { alternatives } => alternatives.first().map_or(0, Pattern::line),
+            Pattern::List { bracket, .. } => bracket.get_line(),
+            Pattern::Map { brace, .. } => brace.get_line(),
+            Pattern::Default { pattern, .. } => pattern.line(),
+        }
+    }
+
+    // Whether the pattern matches every value, which makes the arm it starts a catch-all.
+    pub fn is_irrefutable(&self) -> bool {
+        match self {
+            Pattern::Wildcard { .. } | Pattern::Binding { .. } => true,
+            Pattern::Alternative { alternatives } => {
+                alternatives.iter().any(Pattern::is_irrefutable)
+            }
+            Pattern::Default { pattern, .. } => pattern.is_irrefutable(),
+            _ => false,
+        }
+    }
+
+    // The names the pattern binds, in order.
+    pub fn bindings(&self) -> Vec<&Token> {
+        match self {
+            Pattern::Binding { name } => vec![name],
+            Pattern::List { elements, rest, .. } => elements
+                .iter()
+                .flat_map(Pattern::bindings)
+                .chain(rest.iter())
+                .collect(),
+            Pattern::Map { entries, .. } => entries
+                .iter()
+                .flat_map(|(_, pattern)| pattern.bindings())
+                .collect(),
+            Pattern::Variant { fields, .. } => fields
+                .iter()
+                .flatten()
+                .flat_map(Pattern::bindings)
+                .collect(),
+            Pattern::Default { pattern, .. } => pattern.bindings(),
+            Pattern::Wildcard { .. } | Pattern::Literal { .. } | Pattern::Alternative { .. } => {
+                vec![]
+            }
+        }
+    }
+
+    // Whether a variant pattern names the variant `variant` was built by, whatever its fields.
+    pub fn names_variant(&self, variant: &Variant) -> bool {
+        match self {
+            Pattern::Variant {
+                enumeration, name, ..
+            } => {
+                let (variant_name, _) = &variant.enumeration.variants[variant.index];
+
+                *variant_name == name.lexeme()
+                    && enumeration
+                        .as_ref()
+                        .is_none_or(|enumeration| enumeration.lexeme() == variant.enumeration.name)
+            }
+            _ => false,
+        }
+    }
+
+    // Whether `value` has the shape of the pattern. The names it binds are pushed to `bindings`,
+    // which is only meaningful when it does.
+    pub fn matches(&self, value: &Literal, bindings: &mut Vec<(Symbol, Literal)>) -> bool {
+        match (self, value) {
+            (Pattern::Wildcard { .. }, _) => true,
+            (
+                Pattern::Literal {
+                    value: expected, ..
+                },
+                value,
+            ) => Expression::is_equal(expected.clone(), value.clone()).unwrap_or(false),
+            (Pattern::Binding { name }, value) => {
+                bindings.push((name.get_symbol(), value.clone()));
+                true
+            }
+            (Pattern::Alternative { alternatives }, value) => alternatives
+                .iter()
+                .any(|alternative| alternative.matches(value, bindings)),
+            (Pattern::List { elements, rest, .. }, Literal::List(values)) => {
+                let fits = match rest {
+                    Some(_) => values.len() >= elements.len(),
+                    None => values.len() == elements.len(),
+                };
+
+                if !fits
+                    || !elements
+                        .iter()
+                        .zip(values.iter())
+                        .all(|(element, value)| element.matches(value, bindings))
+                {
+                    return false;
+                }
+
+                if let Some(rest) = rest.as_ref().filter(|rest| rest.lexeme() != "_") {
+                    let remaining = values[elements.len()..].to_vec();
+                    bindings.push((
+                        rest.get_symbol(),
+                        Literal::List(Rc::new(List::from(remaining))),
+                    ));
+                }
+
+                true
+            }
+            (Pattern::Variant { fields, .. }, Literal::Variant(variant)) => {
+                self.names_variant(variant)
+                    && fields.as_ref().is_none_or(|fields| {
+                        fields.len() == variant.values.len()
+                            && fields
+                                .iter()
+                                .zip(variant.values.iter())
+                                .all(|(field, value)| field.matches(value, bindings))
+                    })
+            }
+            (Pattern::Default { pattern, .. }, value) => pattern.matches(value, bindings),
+            (Pattern::Map { entries, .. }, Literal::Map(values)) => {
+                entries.iter().all(|(key, pattern)| {
+                    let key = Expression::map_key(key);
+
+                    values
+                        .get(&key)
+                        .is_some_and(|value| pattern.matches(value, bindings))
+                })
+            }
+            _ => false,
+        }
+    }
+}
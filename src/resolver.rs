@@ -0,0 +1,440 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::{
+    pattern::{MatchArm, Pattern},
+    Expression, FunctionDeclaration, Statement, Token, TokenType,
+};
+
+// Static checks over the whole tree, run once it is parsed and before anything executes. Warnings
+// don't stop the program, they are only reported. Changing a constant is an error instead, which
+// panics like a syntax error does, so that a script doing it never starts.
+pub struct Warning {
+    pub message: String,
+    pub line: u32,
+}
+
+impl fmt::Display for Warning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "[line {}] Warning: {}", self.line, self.message)
+    }
+}
+
+pub fn resolve(statements: &[Statement]) -> Vec<Warning> {
+    let mut resolver = Resolver::default();
+    resolver.scopes.push(HashMap::new());
+    resolver.statements(statements);
+
+    resolver.warnings
+}
+
+#[derive(Default)]
+struct Resolver {
+    warnings: Vec<Warning>,
+    // The variants of every enum declared so far, with the number of fields of each.
+    enums: HashMap<String, Vec<(String, usize)>>,
+    // The names declared in each scope around the current statement, innermost last, and whether
+    // each of them is a constant.
+    scopes: Vec<HashMap<String, bool>>,
+}
+
+impl Resolver {
+    fn statements(&mut self, statements: &[Statement]) {
+        for statement in statements {
+            self.statement(statement);
+        }
+    }
+
+    fn statement(&mut self, statement: &Statement) {
+        match statement {
+            Statement::Expression { expression }
+            | Statement::Print { expression, .. }
+            | Statement::Throw { expression, .. } => self.expression(expression),
+            Statement::Var { token, expression } => {
+                self.expression(expression);
+                self.declare(token, false);
+            }
+            Statement::Const { token, expression } => {
+                self.expression(expression);
+                self.declare(token, true);
+            }
+            Statement::Destructure {
+                keyword,
+                pattern,
+                expression,
+            } => {
+                self.pattern(pattern);
+                self.expression(expression);
+
+                for name in pattern.bindings() {
+                    self.declare(name, keyword.get_token_type() == &TokenType::Const);
+                }
+            }
+            Statement::For {
+                key,
+                value,
+                iterable,
+                body,
+                ..
+            } => {
+                self.expression(iterable);
+                self.scopes.push(HashMap::new());
+
+                for pattern in key.iter().chain([value]) {
+                    self.pattern(pattern);
+
+                    for name in pattern.bindings() {
+                        self.declare(name, false);
+                    }
+                }
+
+                self.statement(body);
+                self.scopes.pop();
+            }
+            Statement::Block { statements, .. } => {
+                self.scopes.push(HashMap::new());
+                self.statements(statements);
+                self.scopes.pop();
+            }
+            Statement::If {
+                condition,
+                then_statement,
+            } => {
+                self.expression(condition);
+                self.statement(then_statement);
+            }
+            Statement::IfElse {
+                condition,
+                then_statement,
+                else_stament,
+            } => {
+                self.expression(condition);
+                self.statement(then_statement);
+                self.statement(else_stament);
+            }
+            Statement::Try {
+                try_statement,
+                catch_clause,
+                finally_statement,
+            } => {
+                self.statement(try_statement);
+
+                if let Some((name, catch_statement)) = catch_clause {
+                    self.scopes.push(HashMap::new());
+                    self.declare(name, false);
+                    self.statement(catch_statement);
+                    self.scopes.pop();
+                }
+
+                if let Some(finally_statement) = finally_statement {
+                    self.statement(finally_statement);
+                }
+            }
+            Statement::Function { declaration } => {
+                if let Some(name) = &declaration.name {
+                    self.declare(name, false);
+                }
+
+                self.function(declaration);
+            }
+            Statement::Return { value, .. } | Statement::Yield { value, .. } => {
+                if let Some(value) = value {
+                    self.expression(value);
+                }
+            }
+            Statement::Match {
+                keyword,
+                subject,
+                arms,
+                ..
+            } => {
+                self.expression(subject);
+                self.arms(arms, keyword.get_line(), Resolver::statement);
+            }
+            Statement::Enum { name, variants, .. } => {
+                self.declare(name, false);
+                self.enums.insert(
+                    name.lexeme().to_string(),
+                    variants
+                        .iter()
+                        .map(|(variant, fields)| (variant.lexeme().to_string(), fields.len()))
+                        .collect(),
+                );
+            }
+            Statement::Import { name, .. } => self.declare(name, false),
+            Statement::Comment { .. } | Statement::BlankLine { .. } => (),
+        }
+    }
+
+    fn expression(&mut self, expression: &Expression) {
+        match expression {
+            Expression::Grouping { expression } => self.expression(expression),
+            Expression::Binary { left, right, .. } | Expression::Logical { left, right, .. } => {
+                self.expression(left);
+                self.expression(right);
+            }
+            Expression::Unary { right, .. } => self.expression(right),
+            Expression::Assignment { name, value }
+            | Expression::CompoundAssignment { name, value, .. } => {
+                self.expression(value);
+                self.assign(name);
+            }
+            Expression::Increment { name, .. } => self.assign(name),
+            Expression::Destructure { pattern, value, .. } => {
+                self.pattern(pattern);
+                self.expression(value);
+
+                for name in pattern.bindings() {
+                    self.assign(name);
+                }
+            }
+            Expression::Get { object, .. } => self.expression(object),
+            Expression::Range {
+                start, end, step, ..
+            } => {
+                self.expression(start);
+                self.expression(end);
+
+                if let Some(step) = step {
+                    self.expression(step);
+                }
+            }
+            Expression::Conditional {
+                condition,
+                then_branch,
+                else_branch,
+                ..
+            } => {
+                self.expression(condition);
+                self.expression(then_branch);
+                self.expression(else_branch);
+            }
+            Expression::Call {
+                callee,
+                arguments,
+                named,
+                ..
+            } => {
+                self.expression(callee);
+
+                for argument in arguments.iter().chain(named.iter().map(|(_, value)| value)) {
+                    self.expression(argument);
+                }
+            }
+            Expression::List { elements, .. } => {
+                for element in elements {
+                    self.expression(element);
+                }
+            }
+            Expression::Index { object, index, .. } => {
+                self.expression(object);
+                self.expression(index);
+            }
+            Expression::Map { entries, .. } => {
+                for (_, value) in entries {
+                    self.expression(value);
+                }
+            }
+            Expression::Function { declaration } => self.function(declaration),
+            Expression::Match {
+                keyword,
+                subject,
+                arms,
+            } => {
+                self.expression(subject);
+                self.arms(arms, keyword.get_line(), Resolver::expression);
+            }
+            Expression::Literal { .. } | Expression::Var { .. } => {}
+        }
+    }
+
+    // The body runs in a scope of its own, holding the parameters.
+    fn function(&mut self, declaration: &FunctionDeclaration) {
+        self.scopes.push(HashMap::new());
+
+        for parameter in &declaration.parameters {
+            if let Some(default) = &parameter.default {
+                self.expression(default);
+            }
+
+            self.declare(&parameter.name, false);
+        }
+
+        self.statements(&declaration.body);
+        self.scopes.pop();
+    }
+
+    // Declaring a name again in the same scope replaces a constant as surely as assigning it.
+    fn declare(&mut self, name: &Token, constant: bool) {
+        let scope = self
+            .scopes
+            .last_mut()
+            .expect("The resolver is always in a scope");
+
+        if scope.get(name.lexeme()) == Some(&true) {
+            panic!(
+                "Constant {} is already declared in this scope at line {}.",
+                name.lexeme(),
+                name.get_line()
+            );
+        }
+
+        scope.insert(name.lexeme().to_string(), constant);
+    }
+
+    // Names declared nowhere around, like those of the standard library, are never constants.
+    fn assign(&mut self, name: &Token) {
+        let constant = self
+            .scopes
+            .iter()
+            .rev()
+            .find_map(|scope| scope.get(name.lexeme()));
+
+        if constant == Some(&true) {
+            panic!(
+                "Can't assign to constant {} at line {}.",
+                name.lexeme(),
+                name.get_line()
+            );
+        }
+    }
+
+    // A match without an unguarded catch-all arm fails at runtime on any value its patterns miss,
+    // unless its arms cover every variant of a known enum.
+    fn arms<T>(&mut self, arms: &[MatchArm<T>], line: u32, body: fn(&mut Self, &T)) {
+        for arm in arms {
+            self.pattern(&arm.pattern);
+            self.scopes.push(HashMap::new());
+
+            for name in arm.pattern.bindings() {
+                self.declare(name, false);
+            }
+
+            if let Some(guard) = &arm.guard {
+                self.expression(guard);
+            }
+
+            body(self, &arm.body);
+            self.scopes.pop();
+        }
+
+        let mut covered: HashMap<&str, Vec<&str>> = HashMap::new();
+
+        for arm in arms.iter().filter(|arm| arm.guard.is_none()) {
+            Resolver::covered_variants(&arm.pattern, &mut covered);
+        }
+
+        let exhaustive = arms
+            .iter()
+            .any(|arm| arm.guard.is_none() && arm.pattern.is_irrefutable())
+            || covered.iter().any(|(enumeration, names)| {
+                self.enums.get(*enumeration).is_some_and(|variants| {
+                    variants
+                        .iter()
+                        .all(|(variant, _)| names.contains(&variant.as_str()))
+                })
+            });
+
+        if !exhaustive {
+            self.warnings.push(Warning {
+                message: "match is not exhaustive and has no '_' arm.".to_string(),
+                line,
+            });
+        }
+    }
+
+    // The variants a pattern matches whatever their fields hold, by enum. Only `Shape.Circle`
+    // patterns count, since `Circle` alone doesn't say which enum it belongs to.
+    fn covered_variants<'a>(pattern: &'a Pattern, covered: &mut HashMap<&'a str, Vec<&'a str>>) {
+        match pattern {
+            Pattern::Variant {
+                enumeration: Some(enumeration),
+                name,
+                fields,
+            } if fields.iter().flatten().all(Pattern::is_irrefutable) => {
+                covered
+                    .entry(enumeration.lexeme())
+                    .or_default()
+                    .push(name.lexeme());
+            }
+            Pattern::Alternative { alternatives } => {
+                for alternative in alternatives {
+                    Resolver::covered_variants(alternative, covered);
+                }
+            }
+            _ => (),
+        }
+    }
+
+    // Qualified variant patterns of known enums that can never match, and the defaults of
+    // destructuring patterns.
+    fn pattern(&mut self, pattern: &Pattern) {
+        match pattern {
+            Pattern::Variant {
+                enumeration,
+                name,
+                fields,
+            } => {
+                let variants = enumeration
+                    .as_ref()
+                    .and_then(|enumeration| self.enums.get(enumeration.lexeme()));
+
+                if let (Some(enumeration), Some(variants)) = (enumeration, variants) {
+                    match variants
+                        .iter()
+                        .find(|(variant, _)| variant == name.lexeme())
+                    {
+                        None => self.warnings.push(Warning {
+                            message: format!(
+                                "Enum {} has no variant '{}'.",
+                                enumeration.lexeme(),
+                                name.lexeme()
+                            ),
+                            line: name.get_line(),
+                        }),
+                        Some((_, count)) => {
+                            if let Some(fields) = fields.as_ref().filter(|f| f.len() != *count) {
+                                self.warnings.push(Warning {
+                                    message: format!(
+                                        "{}.{} has {} fields but the pattern has {}.",
+                                        enumeration.lexeme(),
+                                        name.lexeme(),
+                                        count,
+                                        fields.len()
+                                    ),
+                                    line: name.get_line(),
+                                });
+                            }
+                        }
+                    }
+                }
+
+                for field in fields.iter().flatten() {
+                    self.pattern(field);
+                }
+            }
+            Pattern::Alternative {
+                alternatives: patterns,
+            }
+            | Pattern::List {
+                elements: patterns, ..
+            } => {
+                for pattern in patterns {
+                    self.pattern(pattern);
+                }
+            }
+            Pattern::Map { entries, .. } => {
+                for (_, pattern) in entries {
+                    self.pattern(pattern);
+                }
+            }
+            Pattern::Default {
+                pattern, default, ..
+            } => {
+                self.pattern(pattern);
+                self.expression(default);
+            }
+            Pattern::Wildcard { .. } | Pattern::Literal { .. } | Pattern::Binding { .. } => (),
+        }
+    }
+}
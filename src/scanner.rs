@@ -1,7 +1,13 @@
 use core::f64;
 use std::collections::HashMap;
+use std::rc::Rc;
 use std::sync::OnceLock;
 
+use crate::generator::Generator;
+use crate::interner::{intern, Symbol};
+use crate::stdlib::{Lines, NativeFunction};
+use crate::{Enum, Function, List, Map, Module, Variant};
+
 // A static, lazily-initialized, thread-safe HashMap that maps keyword strings to token types.
 // `OnceLock` ensures that the HashMap is initialized only once, the first time it is accessed,
 // making it ideal for a global, read-only reference. This allows efficient and safe access to
@@ -13,21 +19,33 @@ fn get_key_words() -> &'static HashMap<&'static str, TokenType> {
         let mut map = HashMap::new();
 
         map.insert("and", TokenType::And);
+        map.insert("as", TokenType::As);
+        map.insert("catch", TokenType::Catch);
         map.insert("class", TokenType::Class);
+        map.insert("const", TokenType::Const);
         map.insert("else", TokenType::Else);
+        map.insert("enum", TokenType::Enum);
         map.insert("false", TokenType::False);
+        map.insert("finally", TokenType::Finally);
         map.insert("for", TokenType::For);
         map.insert("fun", TokenType::Fun);
         map.insert("if", TokenType::If);
+        map.insert("import", TokenType::Import);
+        map.insert("in", TokenType::In);
+        map.insert("is", TokenType::Is);
+        map.insert("match", TokenType::Match);
         map.insert("nil", TokenType::Nil);
         map.insert("or", TokenType::Or);
         map.insert("print", TokenType::Print);
         map.insert("return", TokenType::Return);
         map.insert("super", TokenType::Super);
         map.insert("this", TokenType::This);
+        map.insert("throw", TokenType::Throw);
         map.insert("true", TokenType::True);
+        map.insert("try", TokenType::Try);
         map.insert("var", TokenType::Var);
         map.insert("while", TokenType::While);
+        map.insert("yield", TokenType::Yield);
 
         map
     })
@@ -39,10 +57,11 @@ pub struct Scanner<'a> {
     start: usize,
     current: usize,
     line: u32,
+    keep_comments: bool,
 }
 
 fn is_alpha(s: &str) -> bool {
-    ("a"..="z").contains(&s)
+    ("a"..="z").contains(&s) || ("A"..="Z").contains(&s) || s == "_"
 }
 
 fn is_digit(s: &str) -> bool {
@@ -61,6 +80,17 @@ impl<'a> Scanner<'a> {
             start: 0,
             current: 0,
             line: 1,
+            keep_comments: false,
+        }
+    }
+
+    // Comments are normally thrown away. Tools that need to reproduce the source, like the
+    // formatter, ask for them to be kept as `TokenType::Comment` tokens instead, along with a
+    // `TokenType::BlankLine` token for every run of empty lines between two tokens.
+    pub fn new_with_comments(s: &'a str) -> Self {
+        Self {
+            keep_comments: true,
+            ..Self::new(s)
         }
     }
 
@@ -89,12 +119,46 @@ impl<'a> Scanner<'a> {
             ")" => self.add_token(TokenType::RightParen, Literal::Nil),
             "{" => self.add_token(TokenType::LeftBrace, Literal::Nil),
             "}" => self.add_token(TokenType::RightBrace, Literal::Nil),
+            "[" => self.add_token(TokenType::LeftBracket, Literal::Nil),
+            "]" => self.add_token(TokenType::RightBracket, Literal::Nil),
             "," => self.add_token(TokenType::Comma, Literal::Nil),
             ";" => self.add_token(TokenType::Semicolon, Literal::Nil),
-            "." => self.add_token(TokenType::Dot, Literal::Nil),
-            "-" => self.add_token(TokenType::Minus, Literal::Nil),
-            "+" => self.add_token(TokenType::Plus, Literal::Nil),
-            "*" => self.add_token(TokenType::Star, Literal::Nil),
+            ":" => self.add_token(TokenType::Colon, Literal::Nil),
+            "." => {
+                if self.peek() == "." && self.peek_next() == "." {
+                    self.current += 2;
+                    self.add_token(TokenType::DotDotDot, Literal::Nil);
+                } else if self.peek() == "." {
+                    self.current += 1;
+                    self.add_token(TokenType::DotDot, Literal::Nil);
+                } else {
+                    self.add_token(TokenType::Dot, Literal::Nil);
+                }
+            }
+            "-" => self.operator(
+                &[("-", TokenType::MinusMinus), ("=", TokenType::MinusEqual)],
+                TokenType::Minus,
+            ),
+            "+" => self.operator(
+                &[("+", TokenType::PlusPlus), ("=", TokenType::PlusEqual)],
+                TokenType::Plus,
+            ),
+            "*" => self.operator(
+                &[("*", TokenType::StarStar), ("=", TokenType::StarEqual)],
+                TokenType::Star,
+            ),
+            "%" => self.operator(&[("=", TokenType::PercentEqual)], TokenType::Percent),
+            "~" => self.operator(&[("/", TokenType::TildeSlash)], TokenType::Tilde),
+            "&" => self.add_token(TokenType::Ampersand, Literal::Nil),
+            "|" => self.add_token(TokenType::Pipe, Literal::Nil),
+            "^" => self.add_token(TokenType::Caret, Literal::Nil),
+            "?" => self.operator(
+                &[
+                    ("?", TokenType::QuestionQuestion),
+                    (".", TokenType::QuestionDot),
+                ],
+                TokenType::Question,
+            ),
             "!" => {
                 let token_type = if self.match_next("=") {
                     TokenType::BangEqual
@@ -104,45 +168,50 @@ impl<'a> Scanner<'a> {
 
                 self.add_token(token_type, Literal::Nil)
             }
-            "=" => {
-                let token_type = if self.match_next("=") {
-                    TokenType::EqualEqual
-                } else {
-                    TokenType::Equal
-                };
-
-                self.add_token(token_type, Literal::Nil)
-            }
-            "<" => {
-                let token_type = if self.match_next("=") {
-                    TokenType::LessEqual
-                } else {
-                    TokenType::Less
-                };
-
-                self.add_token(token_type, Literal::Nil)
-            }
-            ">" => {
-                let token_type = if self.match_next("=") {
-                    TokenType::GreaterEqual
-                } else {
-                    TokenType::Greater
-                };
-
-                self.add_token(token_type, Literal::Nil)
-            }
+            "=" => self.operator(
+                &[("=", TokenType::EqualEqual), (">", TokenType::Arrow)],
+                TokenType::Equal,
+            ),
+            "<" => self.operator(
+                &[("=", TokenType::LessEqual), ("<", TokenType::LessLess)],
+                TokenType::Less,
+            ),
+            ">" => self.operator(
+                &[
+                    ("=", TokenType::GreaterEqual),
+                    (">", TokenType::GreaterGreater),
+                ],
+                TokenType::Greater,
+            ),
             "/" => {
                 if self.match_next("/") {
                     // A comment goes until the end of the line.
-                    while self.peek() != "\0" {
+                    while self.peek() != "\n" && !self.is_at_end() {
                         self.advance();
                     }
+
+                    if self.keep_comments {
+                        self.add_token(TokenType::Comment, Literal::Nil);
+                    }
                 } else {
-                    self.add_token(TokenType::Slash, Literal::Nil);
+                    self.operator(&[("=", TokenType::SlashEqual)], TokenType::Slash);
                 }
             }
-            " " => (),
-            "\r" | "\t" | "\n" => {
+            " " | "\r" | "\t" => (),
+            "\n" => {
+                let blank = self.tokens.last().is_some_and(|token| {
+                    token.get_line() < self.line && !token.type_equals_to(&TokenType::BlankLine)
+                });
+
+                if self.keep_comments && blank {
+                    self.tokens.push(Token::new(
+                        TokenType::BlankLine,
+                        String::new(),
+                        Literal::Nil,
+                        self.line,
+                    ));
+                }
+
                 self.line += 1;
             }
             "\"" => {
@@ -165,8 +234,28 @@ impl<'a> Scanner<'a> {
     }
 
     fn advance(&mut self) -> &str {
-        self.current += 1;
-        &self.source[self.current - 1..self.current]
+        let start = self.current;
+        self.current += self.char_len(start);
+        &self.source[start..self.current]
+    }
+
+    // The source is walked one character at a time, which is not always one byte: string
+    // literals and comments may contain any UTF-8.
+    fn char_len(&self, at: usize) -> usize {
+        self.source[at..].chars().next().map_or(1, char::len_utf8)
+    }
+
+    // Adds the token of a one or two character operator: `pairs` maps each character that may
+    // follow the first one to the token the two make together.
+    fn operator(&mut self, pairs: &[(&str, TokenType)], single: TokenType) {
+        for (next, token_type) in pairs {
+            if self.match_next(next) {
+                self.add_token(token_type.clone(), Literal::Nil);
+                return;
+            }
+        }
+
+        self.add_token(single, Literal::Nil)
     }
 
     fn add_token(&mut self, token_type: TokenType, literal: Literal) {
@@ -182,13 +271,11 @@ impl<'a> Scanner<'a> {
             return false;
         }
 
-        let next = &self.source[self.current..self.current + 1];
-
-        if next != exp {
+        if self.peek() != exp {
             return false;
         }
 
-        self.current += 1;
+        self.current += exp.len();
 
         true
     }
@@ -198,14 +285,16 @@ impl<'a> Scanner<'a> {
             return "\0";
         }
 
-        &self.source[self.current..self.current + 1]
+        &self.source[self.current..self.current + self.char_len(self.current)]
     }
 
     fn peek_next(&self) -> &str {
-        if self.current + 1 >= self.source.len() {
+        let next = self.current + self.char_len(self.current);
+
+        if next >= self.source.len() {
             return "\0";
         }
-        &self.source[self.current + 1..self.current + 2]
+        &self.source[next..next + self.char_len(next)]
     }
 
     fn string(&mut self) {
@@ -243,10 +332,17 @@ impl<'a> Scanner<'a> {
 
         let content: &str = &self.source[self.start..self.current];
 
-        self.add_token(
-            TokenType::Number,
-            Literal::Number(content.parse::<f64>().unwrap()),
-        );
+        // A literal without a decimal point is an integer, and has to fit in one.
+        let literal = if content.contains('.') {
+            Literal::Number(content.parse::<f64>().unwrap())
+        } else {
+            match content.parse::<i64>() {
+                Ok(value) => Literal::Int(value),
+                Err(_) => panic!("Integer literal too large at line {}.", self.line),
+            }
+        };
+
+        self.add_token(TokenType::Number, literal);
     }
 
     fn identifier(&mut self) {
@@ -275,15 +371,21 @@ pub struct Token {
     lexeme: String,
     literal: Literal,
     line: u32,
+    symbol: Option<Symbol>,
 }
 
 impl Token {
     pub fn new(token_type: TokenType, lexeme: String, literal: Literal, line: u32) -> Self {
+        // Identifiers are interned as soon as they are scanned, so every later lookup of the
+        // name only deals with its symbol.
+        let symbol = (token_type == TokenType::Identifier).then(|| intern(&lexeme));
+
         Self {
             token_type: (token_type),
             lexeme: (lexeme),
             literal: (literal),
             line: (line),
+            symbol: (symbol),
         }
     }
 
@@ -319,23 +421,137 @@ impl Token {
     pub fn get_token_type(&self) -> &TokenType {
         &self.token_type
     }
+
+    pub fn get_line(&self) -> u32 {
+        self.line
+    }
+
+    pub fn get_symbol(&self) -> Symbol {
+        self.symbol.unwrap_or_else(|| intern(&self.lexeme))
+    }
 }
 
 #[derive(Clone, PartialEq, Debug)]
 pub enum Literal {
     Number(f64),
+    Int(i64),
     Text(String),
     Nil,
     Boolean(bool),
+    Error { message: String, line: u32 },
+    Module(Rc<Module>),
+    Native(NativeFunction),
+    Function(Rc<Function>),
+    // An `enum` declaration, a constructor of one of its variants (by index) and a value built by
+    // one.
+    Enum(Rc<Enum>),
+    Constructor(Rc<Enum>, usize),
+    Variant(Rc<Variant>),
+    // A suspended call of a function containing `yield`, and its `next` method bound to it.
+    Generator(Rc<Generator>),
+    GeneratorNext(Rc<Generator>),
+    // The lines of a file, read as a `for` loop asks for them (see `io.lines`).
+    Lines(Rc<Lines>),
+    List(Rc<List>),
+    // String keys in insertion order.
+    Map(Rc<Map>),
+    // `start..end step step`, the integers from `start` up to (or down to) `end`, excluded.
+    Range { start: i64, end: i64, step: i64 },
+}
+
+// Numbers print the way Rust prints them, except for the IEEE special values, which get the
+// spelling scripts use for them: `NaN`, `Infinity` and `-Infinity`.
+pub fn format_number(value: f64) -> String {
+    if value.is_nan() {
+        "NaN".to_string()
+    } else if value.is_infinite() {
+        if value > 0.0 { "Infinity" } else { "-Infinity" }.to_string()
+    } else {
+        value.to_string()
+    }
 }
 
 impl Literal {
+    // The value as a count or position: integers, and floats without a fractional part.
+    pub fn as_whole_number(&self) -> Option<i64> {
+        match self {
+            Literal::Int(value) => Some(*value),
+            Literal::Number(value) if value.fract() == 0.0 && value.abs() < 9.2e18 => {
+                Some(*value as i64)
+            }
+            _ => None,
+        }
+    }
+
+    // The bytes a newly built value holds outside of its `Literal`: the text of a string, the
+    // elements of a list and the entries of a map. The lists and maps inside it usually existed
+    // before and were accounted for when they were built, so they only count when `deep`, for
+    // values that natives build all at once.
+    pub fn allocated_bytes(&self, deep: bool) -> usize {
+        let nested = |value: &Literal| if deep { value.allocated_bytes(true) } else { 0 };
+
+        match self {
+            Literal::Text(text) => text.len(),
+            Literal::List(elements) => elements
+                .iter()
+                .map(|element| size_of::<Literal>() + nested(element))
+                .sum(),
+            Literal::Map(entries) => entries
+                .iter()
+                .map(|(key, value)| size_of::<(String, Literal)>() + key.len() + nested(value))
+                .sum(),
+            _ => 0,
+        }
+    }
+
+    // Integers promote to floats wherever the two meet.
+    pub fn as_float(&self) -> Option<f64> {
+        match self {
+            Literal::Number(value) => Some(*value),
+            Literal::Int(value) => Some(*value as f64),
+            _ => None,
+        }
+    }
+
     pub fn to_custom_string(&self) -> String {
         match self {
             Literal::Boolean(b) => b.to_string(),
             Literal::Nil => "nil".to_string(),
-            Literal::Number(n) => n.to_string(),
+            Literal::Number(n) => format_number(*n),
+            Literal::Int(n) => n.to_string(),
             Literal::Text(s) => s.clone(),
+            Literal::Error { message, .. } => format!("(error {})", message),
+            Literal::Module(module) => format!("(module {})", module.path),
+            Literal::Native(native) => format!("(native {})", native.name),
+            Literal::Function(function) => format!("(fn {})", function.name()),
+            Literal::Enum(enumeration) => format!("(enum {})", enumeration.name),
+            Literal::Constructor(enumeration, index) => format!(
+                "(constructor {}.{})",
+                enumeration.name, enumeration.variants[*index].0
+            ),
+            Literal::Variant(variant) => {
+                let values: Vec<String> = variant
+                    .values
+                    .iter()
+                    .map(|v| v.to_custom_string())
+                    .collect();
+                format!("({} {})", variant.name(), values.join(" "))
+            }
+            Literal::List(elements) => {
+                let elements: Vec<String> = elements.iter().map(|e| e.to_custom_string()).collect();
+                format!("(list {})", elements.join(" "))
+            }
+            Literal::Map(entries) => {
+                let entries: Vec<String> = entries
+                    .iter()
+                    .map(|(key, value)| format!("{} {}", key, value.to_custom_string()))
+                    .collect();
+                format!("(map {})", entries.join(" "))
+            }
+            Literal::Range { start, end, step } => format!("(range {} {} {})", start, end, step),
+            Literal::Generator(generator) => format!("(generator {})", generator.name()),
+            Literal::GeneratorNext(_) => "(fn next)".to_string(),
+            Literal::Lines(lines) => format!("(lines {})", lines.path),
         }
     }
 }
@@ -346,13 +562,38 @@ pub enum TokenType {
     RightParen,
     LeftBrace,
     RightBrace,
+    LeftBracket,
+    RightBracket,
     Comma,
+    Colon,
     Dot,
+    DotDot,
+    DotDotDot,
     Minus,
     Plus,
     Semicolon,
     Slash,
     Star,
+    Percent,
+    TildeSlash,
+    StarStar,
+    Ampersand,
+    Pipe,
+    Caret,
+    Tilde,
+    LessLess,
+    GreaterGreater,
+    PlusPlus,
+    MinusMinus,
+    PlusEqual,
+    MinusEqual,
+    StarEqual,
+    SlashEqual,
+    PercentEqual,
+    Question,
+    QuestionQuestion,
+    QuestionDot,
+    Arrow,
 
     Bang,
     BangEqual,
@@ -366,23 +607,37 @@ pub enum TokenType {
     Identifier,
     String,
     Number,
+    Comment,
+    BlankLine,
 
     And,
+    As,
+    Catch,
     Class,
+    Const,
     Else,
+    Enum,
     False,
+    Finally,
     Fun,
     For,
     If,
+    Import,
+    In,
+    Is,
+    Match,
     Nil,
     Or,
     Print,
     Return,
     Super,
     This,
+    Throw,
     True,
+    Try,
     Var,
     While,
+    Yield,
 
     Eof,
 }
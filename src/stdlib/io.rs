@@ -0,0 +1,182 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::Path;
+use std::rc::Rc;
+
+use super::{module_definitions, native, text};
+use crate::{interner::Symbol, Interpreter, List, Literal};
+
+// `import "io" as io;`
+//
+// Relative paths are relative to the working directory of the process, not to the script. Every
+// failure of the underlying system call is a runtime error naming the path and carrying the OS
+// message, so it can be caught like any other error.
+pub fn definitions() -> HashMap<Symbol, Literal> {
+    module_definitions(
+        &[],
+        &[
+            native("readFile", 1, read_file),
+            native("readLines", 1, read_lines),
+            native("lines", 1, lines),
+            native("writeFile", 2, write_file),
+            native("appendFile", 2, append_file),
+            native("exists", 1, exists),
+            native("listDir", 1, list_dir),
+            native("readLine", 0, read_line),
+            native("readAll", 0, read_all),
+        ],
+    )
+}
+
+fn failure(action: &str, path: &str, error: io::Error) -> String {
+    format!("Could not {} '{}': {}.", action, path, error)
+}
+
+fn read_file(_: &mut Interpreter, arguments: &[Literal]) -> Result<Literal, String> {
+    let path = text(arguments, 0, "io.readFile")?;
+
+    fs::read_to_string(path)
+        .map(Literal::Text)
+        .map_err(|error| failure("read", path, error))
+}
+
+// Lines come without their `\n` or `\r\n` terminator.
+fn read_lines(_: &mut Interpreter, arguments: &[Literal]) -> Result<Literal, String> {
+    let path = text(arguments, 0, "io.readLines")?;
+
+    let contents = fs::read_to_string(path).map_err(|error| failure("read", path, error))?;
+    let lines: Vec<Literal> = contents
+        .lines()
+        .map(|line| Literal::Text(line.to_string()))
+        .collect();
+
+    Ok(Literal::List(Rc::new(List::from(lines))))
+}
+
+// `for (line in io.lines(path))` reads the file one line at a time instead of all at once, for
+// files too large to hold in memory. The file is opened right away, so a missing one fails here
+// rather than in the loop, and closed once its last line was read.
+fn lines(_: &mut Interpreter, arguments: &[Literal]) -> Result<Literal, String> {
+    let path = text(arguments, 0, "io.lines")?;
+
+    let file = File::open(path).map_err(|error| failure("read", path, error))?;
+
+    Ok(Literal::Lines(Rc::new(Lines {
+        path: path.to_string(),
+        reader: RefCell::new(Some(BufReader::new(file))),
+    })))
+}
+
+// The value `io.lines` returns, which a `for` loop walks. A second loop over the same value goes
+// on where the first one stopped, like a generator.
+pub struct Lines {
+    pub path: String,
+    reader: RefCell<Option<BufReader<File>>>,
+}
+
+impl Lines {
+    // Like `readLines`, lines come without their terminator. `None` once the file is exhausted.
+    pub fn next_line(&self) -> Result<Option<String>, String> {
+        let mut reader = self.reader.borrow_mut();
+        let Some(file) = reader.as_mut() else {
+            return Ok(None);
+        };
+
+        let mut line = String::new();
+        let read = file
+            .read_line(&mut line)
+            .map_err(|error| failure("read", &self.path, error))?;
+
+        if read == 0 {
+            *reader = None;
+            return Ok(None);
+        }
+
+        if line.ends_with('\n') {
+            line.pop();
+            if line.ends_with('\r') {
+                line.pop();
+            }
+        }
+
+        Ok(Some(line))
+    }
+}
+
+impl PartialEq for Lines {
+    fn eq(&self, other: &Self) -> bool {
+        std::ptr::eq(self, other)
+    }
+}
+
+impl fmt::Debug for Lines {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "<lines {}>", self.path)
+    }
+}
+
+// Creates the file or replaces what it held.
+fn write_file(_: &mut Interpreter, arguments: &[Literal]) -> Result<Literal, String> {
+    let path = text(arguments, 0, "io.writeFile")?;
+    let contents = text(arguments, 1, "io.writeFile")?;
+
+    fs::write(path, contents).map_err(|error| failure("write", path, error))?;
+
+    Ok(Literal::Nil)
+}
+
+// Creates the file if it does not exist yet.
+fn append_file(_: &mut Interpreter, arguments: &[Literal]) -> Result<Literal, String> {
+    let path = text(arguments, 0, "io.appendFile")?;
+    let contents = text(arguments, 1, "io.appendFile")?;
+
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .and_then(|mut file| file.write_all(contents.as_bytes()))
+        .map_err(|error| failure("append to", path, error))?;
+
+    Ok(Literal::Nil)
+}
+
+fn exists(_: &mut Interpreter, arguments: &[Literal]) -> Result<Literal, String> {
+    let path = text(arguments, 0, "io.exists")?;
+
+    Ok(Literal::Boolean(Path::new(path).exists()))
+}
+
+// The names of the entries, sorted so scripts behave the same on every file system.
+fn list_dir(_: &mut Interpreter, arguments: &[Literal]) -> Result<Literal, String> {
+    let path = text(arguments, 0, "io.listDir")?;
+
+    let mut names = vec![];
+    for entry in fs::read_dir(path).map_err(|error| failure("list", path, error))? {
+        let entry = entry.map_err(|error| failure("list", path, error))?;
+        names.push(entry.file_name().to_string_lossy().into_owned());
+    }
+    names.sort();
+
+    Ok(Literal::List(Rc::new(List::from(
+        names.into_iter().map(Literal::Text).collect::<Vec<_>>(),
+    ))))
+}
+
+// `nil` once stdin is exhausted, so `while` loops over input have something to stop on.
+fn read_line(interpreter: &mut Interpreter, _: &[Literal]) -> Result<Literal, String> {
+    match interpreter.read_line() {
+        Ok(Some(line)) => Ok(Literal::Text(line)),
+        Ok(None) => Ok(Literal::Nil),
+        Err(error) => Err(failure("read", "<stdin>", error)),
+    }
+}
+
+fn read_all(interpreter: &mut Interpreter, _: &[Literal]) -> Result<Literal, String> {
+    interpreter
+        .read_all()
+        .map(Literal::Text)
+        .map_err(|error| failure("read", "<stdin>", error))
+}
@@ -0,0 +1,440 @@
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use super::{module_definitions, native, text, whole_number, NativeFunction};
+use crate::{interner::Symbol, Interpreter, List, Literal, Map};
+
+// `import "json" as json;`
+//
+// Objects become maps, arrays lists, numbers `Int` when they are whole and fit and `Number`
+// otherwise, and `null` is `nil`. Going the other way,
+// only those types (plus booleans and strings) can be written: modules, functions, errors and
+// the non-finite numbers have no JSON form and are an error.
+pub fn definitions() -> HashMap<Symbol, Literal> {
+    module_definitions(
+        &[],
+        &[
+            native("parse", 1, parse),
+            NativeFunction {
+                name: "stringify",
+                min_arity: 1,
+                max_arity: 2,
+                function: stringify,
+            },
+        ],
+    )
+}
+
+// Deeper documents are rejected instead of overflowing the stack of the recursive parser.
+const MAX_DEPTH: usize = 512;
+
+fn parse(_: &mut Interpreter, arguments: &[Literal]) -> Result<Literal, String> {
+    let source = text(arguments, 0, "json.parse")?;
+    let mut parser = JsonParser {
+        chars: source.chars().collect(),
+        current: 0,
+        line: 1,
+        column: 1,
+        depth: 0,
+    };
+
+    parser.skip_whitespace();
+    let value = parser.value()?;
+    parser.skip_whitespace();
+
+    if parser.peek().is_some() {
+        return Err(parser.error("Unexpected data after the JSON value"));
+    }
+
+    Ok(value)
+}
+
+struct JsonParser {
+    chars: Vec<char>,
+    current: usize,
+    line: usize,
+    column: usize,
+    depth: usize,
+}
+
+impl JsonParser {
+    fn value(&mut self) -> Result<Literal, String> {
+        match self.peek() {
+            Some('{') => self.nested(Self::object),
+            Some('[') => self.nested(Self::array),
+            Some('"') => Ok(Literal::Text(self.string()?)),
+            Some('-' | '0'..='9') => self.number(),
+            Some('t') => self.keyword("true", Literal::Boolean(true)),
+            Some('f') => self.keyword("false", Literal::Boolean(false)),
+            Some('n') => self.keyword("null", Literal::Nil),
+            Some(_) => Err(self.error("Expected a JSON value")),
+            None => Err(self.error("Unexpected end of input")),
+        }
+    }
+
+    fn nested(
+        &mut self,
+        parse: fn(&mut Self) -> Result<Literal, String>,
+    ) -> Result<Literal, String> {
+        if self.depth >= MAX_DEPTH {
+            return Err(self.error("Too much nesting"));
+        }
+
+        self.depth += 1;
+        let value = parse(self);
+        self.depth -= 1;
+
+        value
+    }
+
+    fn object(&mut self) -> Result<Literal, String> {
+        self.advance();
+        self.skip_whitespace();
+
+        let mut entries = Map::default();
+
+        if self.peek() == Some('}') {
+            self.advance();
+            return Ok(Literal::Map(Rc::new(entries)));
+        }
+
+        loop {
+            self.skip_whitespace();
+            if self.peek() != Some('"') {
+                return Err(self.error("Expected a string key"));
+            }
+            let key = self.string()?;
+
+            self.skip_whitespace();
+            self.expect(':')?;
+            self.skip_whitespace();
+
+            let value = self.value()?;
+            entries.insert(key, value);
+
+            self.skip_whitespace();
+            match self.advance() {
+                Some(',') => continue,
+                Some('}') => return Ok(Literal::Map(Rc::new(entries))),
+                _ => return Err(self.error_before("Expected ',' or '}'")),
+            }
+        }
+    }
+
+    fn array(&mut self) -> Result<Literal, String> {
+        self.advance();
+        self.skip_whitespace();
+
+        let mut elements = vec![];
+
+        if self.peek() == Some(']') {
+            self.advance();
+            return Ok(Literal::List(Rc::new(List::from(elements))));
+        }
+
+        loop {
+            self.skip_whitespace();
+            elements.push(self.value()?);
+
+            self.skip_whitespace();
+            match self.advance() {
+                Some(',') => continue,
+                Some(']') => return Ok(Literal::List(Rc::new(List::from(elements)))),
+                _ => return Err(self.error_before("Expected ',' or ']'")),
+            }
+        }
+    }
+
+    fn string(&mut self) -> Result<String, String> {
+        self.advance();
+
+        let mut value = String::new();
+
+        loop {
+            match self.advance() {
+                Some('"') => return Ok(value),
+                Some('\\') => value.push(self.escape()?),
+                Some(c) if (c as u32) < 0x20 => {
+                    return Err(self.error_before("Control character in string"))
+                }
+                Some(c) => value.push(c),
+                None => return Err(self.error("Unterminated string")),
+            }
+        }
+    }
+
+    fn escape(&mut self) -> Result<char, String> {
+        let c = match self.advance() {
+            Some('"') => '"',
+            Some('\\') => '\\',
+            Some('/') => '/',
+            Some('b') => '\u{8}',
+            Some('f') => '\u{c}',
+            Some('n') => '\n',
+            Some('r') => '\r',
+            Some('t') => '\t',
+            Some('u') => return self.unicode_escape(),
+            _ => return Err(self.error_before("Invalid escape sequence")),
+        };
+
+        Ok(c)
+    }
+
+    // `\uXXXX`, where characters outside the Basic Multilingual Plane come as a surrogate pair.
+    fn unicode_escape(&mut self) -> Result<char, String> {
+        let high = self.hex4()?;
+
+        let code = if (0xD800..0xDC00).contains(&high) {
+            if self.advance() != Some('\\') || self.advance() != Some('u') {
+                return Err(self.error_before("Expected a low surrogate"));
+            }
+            let low = self.hex4()?;
+            if !(0xDC00..0xE000).contains(&low) {
+                return Err(self.error_before("Invalid low surrogate"));
+            }
+
+            0x10000 + ((high - 0xD800) << 10) + (low - 0xDC00)
+        } else {
+            high
+        };
+
+        char::from_u32(code).ok_or_else(|| self.error_before("Invalid unicode escape"))
+    }
+
+    fn hex4(&mut self) -> Result<u32, String> {
+        let mut code = 0;
+
+        for _ in 0..4 {
+            let digit = self
+                .advance()
+                .and_then(|c| c.to_digit(16))
+                .ok_or_else(|| self.error_before("Expected four hex digits"))?;
+            code = code * 16 + digit;
+        }
+
+        Ok(code)
+    }
+
+    // The JSON grammar is stricter than Rust's float parser (no leading zeros, no `.5`, no `+`),
+    // so the shape is checked here and only the conversion is left to `parse`.
+    fn number(&mut self) -> Result<Literal, String> {
+        let start = self.current;
+
+        if self.peek() == Some('-') {
+            self.advance();
+        }
+
+        match self.peek() {
+            Some('0') => {
+                self.advance();
+            }
+            Some('1'..='9') => self.digits(),
+            _ => return Err(self.error("Expected a digit")),
+        }
+
+        if self.peek() == Some('.') {
+            self.advance();
+            if !self.peek().is_some_and(|c| c.is_ascii_digit()) {
+                return Err(self.error("Expected a digit after '.'"));
+            }
+            self.digits();
+        }
+
+        if matches!(self.peek(), Some('e' | 'E')) {
+            self.advance();
+            if matches!(self.peek(), Some('+' | '-')) {
+                self.advance();
+            }
+            if !self.peek().is_some_and(|c| c.is_ascii_digit()) {
+                return Err(self.error("Expected a digit in the exponent"));
+            }
+            self.digits();
+        }
+
+        let literal: String = self.chars[start..self.current].iter().collect();
+
+        // Whole numbers that fit become integers, so IDs survive the round trip exactly.
+        if let Ok(value) = literal.parse::<i64>() {
+            return Ok(Literal::Int(value));
+        }
+
+        literal
+            .parse()
+            .map(Literal::Number)
+            .map_err(|_| self.error("Invalid number"))
+    }
+
+    fn digits(&mut self) {
+        while self.peek().is_some_and(|c| c.is_ascii_digit()) {
+            self.advance();
+        }
+    }
+
+    fn keyword(&mut self, word: &str, value: Literal) -> Result<Literal, String> {
+        for expected in word.chars() {
+            if self.peek() != Some(expected) {
+                return Err(self.error("Expected a JSON value"));
+            }
+            self.advance();
+        }
+
+        Ok(value)
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), String> {
+        match self.advance() {
+            Some(c) if c == expected => Ok(()),
+            _ => Err(self.error_before(&format!("Expected '{}'", expected))),
+        }
+    }
+
+    fn skip_whitespace(&mut self) {
+        while matches!(self.peek(), Some(' ' | '\t' | '\n' | '\r')) {
+            self.advance();
+        }
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.current).copied()
+    }
+
+    fn advance(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.current += 1;
+
+        if c == '\n' {
+            self.line += 1;
+            self.column = 1;
+        } else {
+            self.column += 1;
+        }
+
+        Some(c)
+    }
+
+    fn error(&self, message: &str) -> String {
+        format!(
+            "json.parse: {} at line {}, column {}.",
+            message, self.line, self.column
+        )
+    }
+
+    // For errors found after consuming the offending character, which is never a newline here.
+    fn error_before(&self, message: &str) -> String {
+        format!(
+            "json.parse: {} at line {}, column {}.",
+            message,
+            self.line,
+            self.column - 1
+        )
+    }
+}
+
+// `indent` is the number of spaces per level; without it (or with 0) the output is compact.
+fn stringify(_: &mut Interpreter, arguments: &[Literal]) -> Result<Literal, String> {
+    let indent = match arguments.get(1) {
+        Some(_) => whole_number(arguments, 1, "json.stringify")?.min(10),
+        None => 0,
+    };
+
+    let mut output = String::new();
+    write_value(&arguments[0], indent, 0, &mut output)?;
+
+    Ok(Literal::Text(output))
+}
+
+fn write_value(
+    value: &Literal,
+    indent: usize,
+    depth: usize,
+    output: &mut String,
+) -> Result<(), String> {
+    if depth >= MAX_DEPTH {
+        return Err("json.stringify: Too much nesting.".to_string());
+    }
+
+    match value {
+        Literal::Nil => output.push_str("null"),
+        Literal::Boolean(value) => output.push_str(&value.to_string()),
+        Literal::Number(value) if value.is_finite() => output.push_str(&value.to_string()),
+        Literal::Int(value) => output.push_str(&value.to_string()),
+        Literal::Text(value) => write_string(value, output),
+        Literal::List(elements) => {
+            let elements: Vec<&Literal> = elements.iter().collect();
+            write_container(
+                ('[', ']'),
+                &elements,
+                indent,
+                depth,
+                output,
+                |element, output| write_value(element, indent, depth + 1, output),
+            )?;
+        }
+        Literal::Map(entries) => {
+            let entries: Vec<&(String, Literal)> = entries.iter().collect();
+            write_container(
+                ('{', '}'),
+                &entries,
+                indent,
+                depth,
+                output,
+                |(key, value), output| {
+                    write_string(key, output);
+                    output.push_str(if indent > 0 { ": " } else { ":" });
+                    write_value(value, indent, depth + 1, output)
+                },
+            )?;
+        }
+        other => return Err(format!("json.stringify can't convert {} to JSON.", other)),
+    }
+
+    Ok(())
+}
+
+fn write_container<T>(
+    (open, close): (char, char),
+    items: &[T],
+    indent: usize,
+    depth: usize,
+    output: &mut String,
+    mut write_item: impl FnMut(&T, &mut String) -> Result<(), String>,
+) -> Result<(), String> {
+    output.push(open);
+
+    for (index, item) in items.iter().enumerate() {
+        if index > 0 {
+            output.push(',');
+        }
+        if indent > 0 {
+            output.push('\n');
+            output.push_str(&" ".repeat(indent * (depth + 1)));
+        }
+        write_item(item, output)?;
+    }
+
+    if indent > 0 && !items.is_empty() {
+        output.push('\n');
+        output.push_str(&" ".repeat(indent * depth));
+    }
+    output.push(close);
+
+    Ok(())
+}
+
+fn write_string(value: &str, output: &mut String) {
+    output.push('"');
+
+    for c in value.chars() {
+        match c {
+            '"' => output.push_str("\\\""),
+            '\\' => output.push_str("\\\\"),
+            '\n' => output.push_str("\\n"),
+            '\r' => output.push_str("\\r"),
+            '\t' => output.push_str("\\t"),
+            c if (c as u32) < 0x20 => output.push_str(&format!("\\u{:04x}", c as u32)),
+            c => output.push(c),
+        }
+    }
+
+    output.push('"');
+}
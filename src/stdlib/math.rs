@@ -0,0 +1,193 @@
+use std::collections::HashMap;
+use std::f64::consts;
+
+use super::{module_definitions, native, number};
+use crate::{interner::Symbol, Interpreter, Literal};
+
+// `import "math" as math;`
+//
+// Numbers follow IEEE 754 throughout: functions outside of their domain return NaN instead of
+// failing (`sqrt(-1)`, `acos(2)`), `log(0)` is -Infinity, and NaN propagates through every
+// function, including `min` and `max`. NaN is not equal to anything, itself included, so
+// `x != x` is the way to test for it. Only passing something that is not a number is an error.
+//
+// `abs`, `floor`, `ceil`, `round`, `min` and `max` keep integers integers, failing where the result
+// doesn't fit like the arithmetic operators do. Every other function returns a float.
+pub fn definitions() -> HashMap<Symbol, Literal> {
+    module_definitions(
+        &[
+            ("PI", Literal::Number(consts::PI)),
+            ("E", Literal::Number(consts::E)),
+            ("INFINITY", Literal::Number(f64::INFINITY)),
+            ("NAN", Literal::Number(f64::NAN)),
+        ],
+        &[
+            native("sqrt", 1, sqrt),
+            native("pow", 2, pow),
+            native("abs", 1, abs),
+            native("floor", 1, floor),
+            native("ceil", 1, ceil),
+            native("round", 1, round),
+            native("min", 2, min),
+            native("max", 2, max),
+            native("log", 1, log),
+            native("exp", 1, exp),
+            native("sin", 1, sin),
+            native("cos", 1, cos),
+            native("tan", 1, tan),
+            native("asin", 1, asin),
+            native("acos", 1, acos),
+            native("atan", 1, atan),
+            native("atan2", 2, atan2),
+            native("format", 2, format),
+        ],
+    )
+}
+
+fn unary(arguments: &[Literal], function: &str, apply: fn(f64) -> f64) -> Result<Literal, String> {
+    Ok(Literal::Number(apply(number(
+        arguments,
+        0,
+        &format!("math.{}", function),
+    )?)))
+}
+
+fn binary(
+    arguments: &[Literal],
+    function: &str,
+    apply: fn(f64, f64) -> f64,
+) -> Result<Literal, String> {
+    let function = format!("math.{}", function);
+    let left = number(arguments, 0, &function)?;
+    let right = number(arguments, 1, &function)?;
+
+    Ok(Literal::Number(apply(left, right)))
+}
+
+fn sqrt(_: &mut Interpreter, arguments: &[Literal]) -> Result<Literal, String> {
+    unary(arguments, "sqrt", f64::sqrt)
+}
+
+fn pow(_: &mut Interpreter, arguments: &[Literal]) -> Result<Literal, String> {
+    binary(arguments, "pow", f64::powf)
+}
+
+fn abs(_: &mut Interpreter, arguments: &[Literal]) -> Result<Literal, String> {
+    match arguments[0] {
+        Literal::Int(value) => value
+            .checked_abs()
+            .map(Literal::Int)
+            .ok_or_else(|| format!("Integer overflow: math.abs({})", value)),
+        _ => unary(arguments, "abs", f64::abs),
+    }
+}
+
+fn floor(_: &mut Interpreter, arguments: &[Literal]) -> Result<Literal, String> {
+    match arguments[0] {
+        Literal::Int(value) => Ok(Literal::Int(value)),
+        _ => unary(arguments, "floor", f64::floor),
+    }
+}
+
+fn ceil(_: &mut Interpreter, arguments: &[Literal]) -> Result<Literal, String> {
+    match arguments[0] {
+        Literal::Int(value) => Ok(Literal::Int(value)),
+        _ => unary(arguments, "ceil", f64::ceil),
+    }
+}
+
+// Halfway cases round away from zero: `round(2.5)` is 3 and `round(-2.5)` is -3.
+fn round(_: &mut Interpreter, arguments: &[Literal]) -> Result<Literal, String> {
+    match arguments[0] {
+        Literal::Int(value) => Ok(Literal::Int(value)),
+        _ => unary(arguments, "round", f64::round),
+    }
+}
+
+// `f64::min` and `f64::max` would quietly drop a NaN operand. Two integers give an integer, an
+// integer and a float give a float.
+fn min(_: &mut Interpreter, arguments: &[Literal]) -> Result<Literal, String> {
+    if let [Literal::Int(l), Literal::Int(r)] = arguments {
+        return Ok(Literal::Int(*l.min(r)));
+    }
+
+    binary(arguments, "min", |l, r| {
+        if l.is_nan() || r.is_nan() {
+            f64::NAN
+        } else {
+            l.min(r)
+        }
+    })
+}
+
+fn max(_: &mut Interpreter, arguments: &[Literal]) -> Result<Literal, String> {
+    if let [Literal::Int(l), Literal::Int(r)] = arguments {
+        return Ok(Literal::Int(*l.max(r)));
+    }
+
+    binary(arguments, "max", |l, r| {
+        if l.is_nan() || r.is_nan() {
+            f64::NAN
+        } else {
+            l.max(r)
+        }
+    })
+}
+
+fn log(_: &mut Interpreter, arguments: &[Literal]) -> Result<Literal, String> {
+    unary(arguments, "log", f64::ln)
+}
+
+fn exp(_: &mut Interpreter, arguments: &[Literal]) -> Result<Literal, String> {
+    unary(arguments, "exp", f64::exp)
+}
+
+fn sin(_: &mut Interpreter, arguments: &[Literal]) -> Result<Literal, String> {
+    unary(arguments, "sin", f64::sin)
+}
+
+fn cos(_: &mut Interpreter, arguments: &[Literal]) -> Result<Literal, String> {
+    unary(arguments, "cos", f64::cos)
+}
+
+fn tan(_: &mut Interpreter, arguments: &[Literal]) -> Result<Literal, String> {
+    unary(arguments, "tan", f64::tan)
+}
+
+fn asin(_: &mut Interpreter, arguments: &[Literal]) -> Result<Literal, String> {
+    unary(arguments, "asin", f64::asin)
+}
+
+fn acos(_: &mut Interpreter, arguments: &[Literal]) -> Result<Literal, String> {
+    unary(arguments, "acos", f64::acos)
+}
+
+fn atan(_: &mut Interpreter, arguments: &[Literal]) -> Result<Literal, String> {
+    unary(arguments, "atan", f64::atan)
+}
+
+fn atan2(_: &mut Interpreter, arguments: &[Literal]) -> Result<Literal, String> {
+    binary(arguments, "atan2", f64::atan2)
+}
+
+// `format(x, digits)` prints `x` with exactly `digits` digits after the decimal point. NaN and
+// the infinities print the same way they always do.
+fn format(_: &mut Interpreter, arguments: &[Literal]) -> Result<Literal, String> {
+    let value = number(arguments, 0, "math.format")?;
+    let digits = number(arguments, 1, "math.format")?;
+
+    if digits.fract() != 0.0 || !(0.0..=100.0).contains(&digits) {
+        return Err(format!(
+            "math.format expects a whole number of digits between 0 and 100, got {}.",
+            arguments[1]
+        ));
+    }
+
+    let text = if value.is_finite() {
+        format!("{:.*}", digits as usize, value)
+    } else {
+        Literal::Number(value).to_string()
+    };
+
+    Ok(Literal::Text(text))
+}
@@ -0,0 +1,120 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::{interner::Symbol, Environment, Interpreter, Literal, Module};
+
+mod io;
+pub use io::Lines;
+mod json;
+mod math;
+mod string;
+
+// A function implemented in Rust. It gets the interpreter, for natives that need to write output,
+// and the already evaluated arguments, whose count is checked against the arity beforehand. A
+// returned `Err` becomes a runtime error at the call site, and returned text is counted against
+// the memory cap there like any other string the script builds.
+#[derive(Clone, Copy)]
+pub struct NativeFunction {
+    pub name: &'static str,
+    pub min_arity: usize,
+    pub max_arity: usize,
+    pub function: fn(&mut Interpreter, &[Literal]) -> Result<Literal, String>,
+}
+
+impl PartialEq for NativeFunction {
+    fn eq(&self, other: &Self) -> bool {
+        self.name == other.name
+    }
+}
+
+impl fmt::Debug for NativeFunction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "<native fn {}>", self.name)
+    }
+}
+
+// The modules that ship with the interpreter. An `import` of one of these names never touches
+// the file system.
+pub fn module(name: &str) -> Option<Module> {
+    let variables = match name {
+        "io" => io::definitions(),
+        "json" => json::definitions(),
+        "math" => math::definitions(),
+        "string" => string::definitions(),
+        _ => return None,
+    };
+
+    let mut environment = Environment::new();
+    for (name, value) in variables {
+        environment.define(name, value);
+    }
+
+    Some(Module {
+        path: name.to_string(),
+        environment,
+    })
+}
+
+fn module_definitions(
+    constants: &[(&str, Literal)],
+    functions: &[NativeFunction],
+) -> HashMap<Symbol, Literal> {
+    let constants = constants
+        .iter()
+        .map(|(name, value)| (Symbol::from(*name), value.clone()));
+
+    let functions = functions
+        .iter()
+        .map(|function| (Symbol::from(function.name), Literal::Native(*function)));
+
+    constants.chain(functions).collect()
+}
+
+fn native(
+    name: &'static str,
+    arity: usize,
+    function: fn(&mut Interpreter, &[Literal]) -> Result<Literal, String>,
+) -> NativeFunction {
+    NativeFunction {
+        name,
+        min_arity: arity,
+        max_arity: arity,
+        function,
+    }
+}
+
+// Argument accessors shared by the natives. `function` is the qualified name used in the error,
+// like `math.sqrt`.
+fn number(arguments: &[Literal], index: usize, function: &str) -> Result<f64, String> {
+    arguments[index]
+        .as_float()
+        .ok_or_else(|| format!("{} expects a number, got {}.", function, arguments[index]))
+}
+
+fn whole_number(arguments: &[Literal], index: usize, function: &str) -> Result<usize, String> {
+    match arguments[index].as_whole_number() {
+        Some(value) if value >= 0 => Ok(value as usize),
+        _ => Err(format!(
+            "{} expects a non-negative whole number, got {}.",
+            function, arguments[index]
+        )),
+    }
+}
+
+fn text<'a>(arguments: &'a [Literal], index: usize, function: &str) -> Result<&'a str, String> {
+    match &arguments[index] {
+        Literal::Text(value) => Ok(value),
+        other => Err(format!("{} expects a string, got {}.", function, other)),
+    }
+}
+
+fn list<'a>(
+    arguments: &'a [Literal],
+    index: usize,
+    function: &str,
+) -> Result<&'a [Literal], String> {
+    match &arguments[index] {
+        Literal::List(elements) => Ok(elements),
+        other => Err(format!("{} expects a list, got {}.", function, other)),
+    }
+}
@@ -0,0 +1,209 @@
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use super::{list, module_definitions, native, text, whole_number, NativeFunction};
+use crate::{interner::Symbol, Interpreter, List, Literal};
+
+// `import "string" as string;`
+//
+// Positions and lengths count characters (Unicode scalar values), never bytes, so they agree
+// with `s[i]` indexing. Ranges are half-open: `slice("hello", 1, 3)` is "el".
+pub fn definitions() -> HashMap<Symbol, Literal> {
+    module_definitions(
+        &[],
+        &[
+            native("length", 1, length),
+            NativeFunction {
+                name: "slice",
+                min_arity: 2,
+                max_arity: 3,
+                function: slice,
+            },
+            native("upper", 1, upper),
+            native("lower", 1, lower),
+            native("trim", 1, trim),
+            native("split", 2, split),
+            native("join", 2, join),
+            native("find", 2, find),
+            native("replace", 3, replace),
+            native("startsWith", 2, starts_with),
+            native("endsWith", 2, ends_with),
+            native("repeat", 2, repeat),
+            native("num", 1, num),
+            native("str", 1, str),
+        ],
+    )
+}
+
+// Works on lists too, since `s[i]` does.
+fn length(_: &mut Interpreter, arguments: &[Literal]) -> Result<Literal, String> {
+    let length = match &arguments[0] {
+        Literal::List(elements) => elements.len(),
+        _ => text(arguments, 0, "string.length")?.chars().count(),
+    };
+
+    Ok(Literal::Int(length as i64))
+}
+
+// The end defaults to the end of the string. Positions past the end are an error rather than
+// being clamped, so an off-by-one shows up where it happens.
+fn slice(_: &mut Interpreter, arguments: &[Literal]) -> Result<Literal, String> {
+    let value = text(arguments, 0, "string.slice")?;
+    let length = value.chars().count();
+    let start = whole_number(arguments, 1, "string.slice")?;
+    let end = match arguments.get(2) {
+        Some(_) => whole_number(arguments, 2, "string.slice")?,
+        None => length,
+    };
+
+    if start > end || end > length {
+        return Err(format!(
+            "string.slice range {}..{} is out of bounds for a length of {}.",
+            start, end, length
+        ));
+    }
+
+    Ok(Literal::Text(
+        value.chars().skip(start).take(end - start).collect(),
+    ))
+}
+
+fn upper(_: &mut Interpreter, arguments: &[Literal]) -> Result<Literal, String> {
+    Ok(Literal::Text(
+        text(arguments, 0, "string.upper")?.to_uppercase(),
+    ))
+}
+
+fn lower(_: &mut Interpreter, arguments: &[Literal]) -> Result<Literal, String> {
+    Ok(Literal::Text(
+        text(arguments, 0, "string.lower")?.to_lowercase(),
+    ))
+}
+
+fn trim(_: &mut Interpreter, arguments: &[Literal]) -> Result<Literal, String> {
+    Ok(Literal::Text(
+        text(arguments, 0, "string.trim")?.trim().to_string(),
+    ))
+}
+
+// An empty separator splits the string into its characters.
+fn split(_: &mut Interpreter, arguments: &[Literal]) -> Result<Literal, String> {
+    let value = text(arguments, 0, "string.split")?;
+    let separator = text(arguments, 1, "string.split")?;
+
+    let parts: Vec<Literal> = if separator.is_empty() {
+        value
+            .chars()
+            .map(|c| Literal::Text(c.to_string()))
+            .collect()
+    } else {
+        value
+            .split(separator)
+            .map(|part| Literal::Text(part.to_string()))
+            .collect()
+    };
+
+    Ok(Literal::List(Rc::new(List::from(parts))))
+}
+
+// Elements that are not strings are joined by their printed form, so `join([1, 2], "-")` works.
+fn join(_: &mut Interpreter, arguments: &[Literal]) -> Result<Literal, String> {
+    let elements = list(arguments, 0, "string.join")?;
+    let separator = text(arguments, 1, "string.join")?;
+
+    let parts: Vec<String> = elements.iter().map(|element| element.to_string()).collect();
+
+    Ok(Literal::Text(parts.join(separator)))
+}
+
+// The character position of the first occurrence, or -1.
+fn find(_: &mut Interpreter, arguments: &[Literal]) -> Result<Literal, String> {
+    let value = text(arguments, 0, "string.find")?;
+    let needle = text(arguments, 1, "string.find")?;
+
+    let position = match value.find(needle) {
+        Some(byte) => value[..byte].chars().count() as i64,
+        None => -1,
+    };
+
+    Ok(Literal::Int(position))
+}
+
+// Replaces every occurrence.
+fn replace(_: &mut Interpreter, arguments: &[Literal]) -> Result<Literal, String> {
+    let value = text(arguments, 0, "string.replace")?;
+    let from = text(arguments, 1, "string.replace")?;
+    let to = text(arguments, 2, "string.replace")?;
+
+    if from.is_empty() {
+        return Err("string.replace can't replace an empty string.".to_string());
+    }
+
+    Ok(Literal::Text(value.replace(from, to)))
+}
+
+fn starts_with(_: &mut Interpreter, arguments: &[Literal]) -> Result<Literal, String> {
+    let value = text(arguments, 0, "string.startsWith")?;
+    let prefix = text(arguments, 1, "string.startsWith")?;
+
+    Ok(Literal::Boolean(value.starts_with(prefix)))
+}
+
+fn ends_with(_: &mut Interpreter, arguments: &[Literal]) -> Result<Literal, String> {
+    let value = text(arguments, 0, "string.endsWith")?;
+    let suffix = text(arguments, 1, "string.endsWith")?;
+
+    Ok(Literal::Boolean(value.ends_with(suffix)))
+}
+
+// The result is counted against the memory cap before it is built, so `repeat("x", 1e15)` fails
+// on the cap instead of exhausting the host. Without a cap it fails if the memory can't be had.
+fn repeat(interpreter: &mut Interpreter, arguments: &[Literal]) -> Result<Literal, String> {
+    let value = text(arguments, 0, "string.repeat")?;
+    let count = whole_number(arguments, 1, "string.repeat")?;
+    let too_large = || "string.repeat result is too large.".to_string();
+
+    let size = value
+        .len()
+        .checked_mul(count)
+        .filter(|size| interpreter.can_allocate(*size))
+        .ok_or_else(too_large)?;
+
+    let mut repeated = String::new();
+    repeated.try_reserve_exact(size).map_err(|_| too_large())?;
+    // Repeating an empty string takes no time however large the count is.
+    if !value.is_empty() {
+        for _ in 0..count {
+            repeated.push_str(value);
+        }
+    }
+
+    Ok(Literal::Text(repeated))
+}
+
+// Accepts what the scanner accepts as a number literal, plus a sign and surrounding whitespace.
+// Like in source code, the result is an integer unless there is a decimal point.
+fn num(_: &mut Interpreter, arguments: &[Literal]) -> Result<Literal, String> {
+    let value = text(arguments, 0, "string.num")?;
+    let trimmed = value.trim();
+
+    let is_number_syntax = !trimmed.is_empty()
+        && trimmed
+            .trim_start_matches(['-', '+'])
+            .chars()
+            .all(|c| c.is_ascii_digit() || c == '.');
+
+    let number = if !is_number_syntax {
+        None
+    } else if trimmed.contains('.') {
+        trimmed.parse().ok().map(Literal::Number)
+    } else {
+        trimmed.parse().ok().map(Literal::Int)
+    };
+
+    number.ok_or_else(|| format!("string.num can't convert '{}' to a number.", value))
+}
+
+fn str(_: &mut Interpreter, arguments: &[Literal]) -> Result<Literal, String> {
+    Ok(Literal::Text(arguments[0].to_string()))
+}